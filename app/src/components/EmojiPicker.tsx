@@ -6,6 +6,7 @@ interface Emoji {
   name: string;
   url: string;
   user_id: string;
+  animated?: boolean;
 }
 
 interface EmojiPickerProps {
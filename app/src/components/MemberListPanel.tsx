@@ -18,7 +18,8 @@ function membersEqual(a: MemberInfo[], b: MemberInfo[]): boolean {
     if (
       a[i].user_id !== b[i].user_id ||
       a[i].is_online !== b[i].is_online ||
-      a[i].display_name !== b[i].display_name
+      a[i].display_name !== b[i].display_name ||
+      a[i].nickname !== b[i].nickname
     )
       return false;
   }
@@ -89,8 +90,12 @@ export function MemberListPanel({ visible }: MemberListPanelProps) {
         user_id: id.node_id,
         username: id.node_id.substring(0, 8),
         display_name: id.display_name,
+        nickname: null,
+        pending: false,
         avatar_url: null,
         is_online: onlineSet.has(id.node_id),
+        status: onlineSet.has(id.node_id) ? "online" : "offline",
+        custom_status: null,
         is_bot: false,
         roles: [],
         joined_at: new Date().toISOString(),
@@ -257,7 +262,7 @@ const MemberItem = memo(function MemberItem({
           />
         ) : (
           <div className="w-8 h-8 rounded-full bg-bg-surface flex items-center justify-center text-xs font-bold text-accent border border-border/50">
-            {(member.display_name || "?")[0]?.toUpperCase()}
+            {(member.nickname || member.display_name || "?")[0]?.toUpperCase()}
           </div>
         )}
         {/* Online indicator */}
@@ -279,7 +284,7 @@ const MemberItem = memo(function MemberItem({
             className="text-sm font-medium truncate"
             style={{ color: topRole?.color || undefined }}
           >
-            {member.display_name}
+            {member.nickname ?? member.display_name}
           </span>
           {member.is_bot && (
             <span className="text-[8px] font-bold uppercase tracking-wider bg-accent/90 text-white px-1 py-0.5 rounded-sm leading-none flex-shrink-0">
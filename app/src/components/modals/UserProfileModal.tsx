@@ -36,6 +36,8 @@ export function UserProfileModal() {
           display_name: "User " + userId.substr(0, 4),
           avatar_url: null,
           is_online: false,
+          status: "offline",
+          custom_status: null,
           is_bot: false,
           joined_at: new Date().toISOString(),
           roles: [],
@@ -92,6 +94,8 @@ export function UserProfileModal() {
           display_name: "User",
           avatar_url: null,
           is_online: false,
+          status: "offline",
+          custom_status: null,
           is_bot: false,
           joined_at: new Date().toISOString(),
           roles: [],
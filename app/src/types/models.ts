@@ -6,10 +6,12 @@ export interface Channel {
   description: string;
   position: number;
   createdAt: string;
-  channel_type: "text" | "voice" | "plugin";
+  channel_type: "text" | "voice" | "plugin" | "forum" | "announcement";
   category_id?: string | null;
   plugin_url?: string | null;
   encrypted?: boolean;
+  /** Announcement channels only: federation peers may follow it */
+  remote_follows?: boolean;
 }
 
 export interface Category {
@@ -31,6 +33,8 @@ export interface Message {
   replyTo?: string | null;
   repliedMessage?: RepliedMessage | null;
   attachments?: Attachment[];
+  /** Set when the whole message is a sticker */
+  sticker?: Sticker | null;
   isBot?: boolean;
   reactions?: ReactionGroup[];
   pinned_at?: string;
@@ -59,9 +63,26 @@ export interface Attachment {
   filename: string;
   mime_type: string;
   size: number;
+  /** Pixel dimensions, for images */
+  width?: number | null;
+  height?: number | null;
+  /**
+   * Signed for message attachments and expires after about a day
+   * (GET /api/uploads/{id}/url for a fresh one). Images also serve
+   * thumbnails with an added `size=64|256|1024` query parameter.
+   */
   url: string;
 }
 
+/** Resumable upload in progress; PATCH chunks with `Upload-Offset: offset` */
+export interface UploadSession {
+  id: string;
+  filename: string;
+  mime_type: string;
+  size: number;
+  offset: number;
+}
+
 export interface P2PServerConfig {
   ticket: string;
   namespaceId: string;
@@ -115,6 +136,11 @@ export interface Role {
   position: number;
   permissions: number;
   created_at: string;
+  managed?: boolean;
+  /** Upload size limit in bytes for members with this role */
+  max_upload_size?: number | null;
+  /** Storage quota in bytes for members with this role */
+  storage_quota?: number | null;
 }
 
 export interface RoleWithMembers extends Role {
@@ -128,12 +154,321 @@ export interface ChatEntry {
   content: string;
 }
 
+// ─── Server (from /api/servers) ───
+export interface ServerInfo {
+  id: string;
+  name: string;
+  description: string;
+  icon_url: string | null;
+  owner_id: string;
+  join_sound_url: string | null;
+  leave_sound_url: string | null;
+  sound_chance: number;
+  allowed_upload_types: string | null;
+  max_upload_size: number | null;
+  storage_quota: number | null;
+  /** Text channel receiving welcome messages */
+  system_channel_id: string | null;
+  /** Welcome text; `{user}` and `{server}` are replaced */
+  welcome_message: string | null;
+  /** Listed in the public server directory */
+  discoverable: boolean;
+  /** Comma-separated discovery tags */
+  tags: string | null;
+  created_at: string;
+  updated_at: string | null;
+}
+
+// ─── Announcement followers (from /api/channels/{id}/followers) ───
+export interface ChannelFollow {
+  id: string;
+  source_channel_id: string;
+  /** Local follower channel (or the follower is a federation peer) */
+  target_channel_id: string | null;
+  /** `Server #channel` */
+  target_name: string | null;
+  peer_id: string | null;
+  peer_name: string | null;
+  remote_channel_id: string | null;
+  created_at: string;
+}
+
+/** PUT /api/channels/{id}/remote-follows (MANAGE_CHANNELS); disabling drops peer follows */
+export interface SetRemoteFollowsRequest {
+  enabled: boolean;
+}
+
+// ─── Forums (from /api/channels/{id}/posts and /tags) ───
+export interface ForumTag {
+  id: string;
+  channel_id: string;
+  name: string;
+  position: number;
+}
+
+export interface ForumPost {
+  id: string;
+  channel_id: string;
+  author_id: string | null;
+  title: string;
+  /** Listed before other posts */
+  pinned: boolean;
+  /** Only moderators can reply */
+  locked: boolean;
+  created_at: string;
+  last_activity_at: string;
+  tag_ids: string[];
+  /** Messages after the opening one */
+  reply_count: number;
+}
+
+export interface CreatePostRequest {
+  title: string;
+  /** The opening message */
+  content: string;
+  tag_ids?: string[];
+}
+
+/** `pinned` and `locked` need MANAGE_MESSAGES */
+export interface UpdatePostRequest {
+  title?: string;
+  tag_ids?: string[];
+  pinned?: boolean;
+  locked?: boolean;
+}
+
+export interface PostsQuery {
+  sort?: "activity" | "created";
+  tag_id?: string;
+  limit?: number;
+  offset?: number;
+}
+
+/** Leave out `id` to add a tag; tags not listed are removed */
+export interface UpdateForumTagsRequest {
+  tags: { id?: string; name: string }[];
+}
+
+// ─── Discovery (from /api/discovery) ───
+export interface DiscoveryQuery {
+  q?: string;
+  tag?: string;
+  sort?: "members" | "activity";
+  limit?: number;
+  offset?: number;
+}
+
+export interface DiscoverableServer {
+  id: string;
+  name: string;
+  description: string;
+  icon_url: string | null;
+  tags: string[];
+  member_count: number;
+  /** Messages sent in the last week */
+  recent_messages: number;
+  /** The caller is already a member */
+  joined: boolean;
+  /** New members have to pass screening first */
+  screening: boolean;
+}
+
+// ─── Onboarding (from /api/servers/{id}/screening) ───
+export interface ScreeningQuestion {
+  question: string;
+  required: boolean;
+}
+
+export interface ScreeningSettings {
+  enabled: boolean;
+  rules: string;
+  questions: ScreeningQuestion[];
+  /** Screened members wait for a moderator before they can post */
+  manual_approval: boolean;
+}
+
+export type OnboardingStatus = "screening" | "awaiting_approval" | "member";
+
+export interface ScreeningInfo extends ScreeningSettings {
+  /** The caller's progress (null if not a member) */
+  status: OnboardingStatus | null;
+}
+
+export interface ScreeningSubmission {
+  accept_rules: boolean;
+  /** One answer per question, in order */
+  answers: string[];
+}
+
+export interface PendingMember {
+  user_id: string;
+  display_name: string;
+  avatar_url: string | null;
+  joined_at: string;
+  screened_at: string | null;
+  answers: { question: string; answer: string }[];
+}
+
+// ─── Scheduled Events (from /api/servers/{id}/events) ───
+export type EventStatus = "scheduled" | "active" | "completed" | "cancelled";
+export type RsvpStatus = "going" | "interested";
+
+export interface ScheduledEvent {
+  id: string;
+  server_id: string;
+  creator_id: string | null;
+  title: string;
+  description: string;
+  /** UTC, `YYYY-MM-DD HH:MM:SS` */
+  starts_at: string;
+  ends_at: string | null;
+  /** Voice channel the event takes place in (or `location` is set) */
+  channel_id: string | null;
+  location: string | null;
+  status: EventStatus;
+  created_at: string;
+}
+
+export interface EventInfo extends ScheduledEvent {
+  going: number;
+  interested: number;
+  /** The caller's RSVP */
+  rsvp: RsvpStatus | null;
+}
+
+export interface CreateEventRequest {
+  title: string;
+  description?: string;
+  /** RFC 3339 or UTC `YYYY-MM-DD HH:MM:SS` */
+  starts_at: string;
+  ends_at?: string;
+  channel_id?: string;
+  location?: string;
+}
+
+export interface UpdateEventRequest extends Partial<CreateEventRequest> {
+  /** scheduled → active | cancelled, active → completed */
+  status?: EventStatus;
+}
+
+export interface EventRsvp {
+  user_id: string;
+  display_name: string;
+  avatar_url: string | null;
+  status: RsvpStatus;
+}
+
 // ─── Server Stats (from /api/stats) ───
 export interface ServerStats {
   total_users: number;
   total_messages: number;
   total_channels: number;
   total_roles: number;
+  /** Bytes of uploads shared in this server */
+  storage_used?: number;
+  /** Effective storage quota in bytes (null = unlimited) */
+  storage_quota?: number | null;
+}
+
+/** Result of `POST /api/admin/storage/gc` */
+export interface OrphanReport {
+  dry_run: boolean;
+  uploads: number;
+  upload_bytes: number;
+  files: number;
+  bytes_reclaimed: number;
+}
+
+/** Row of the admin largest-uploaders report */
+export interface UploaderUsage {
+  user_id: string;
+  username: string;
+  display_name: string;
+  bytes: number;
+  files: number;
+}
+
+// ─── Emoji & stickers ───
+export interface CustomEmoji {
+  id: string;
+  name: string;
+  url: string;
+  user_id: string;
+  animated: boolean;
+}
+
+/** Result of `POST /api/emoji/import` */
+export interface EmojiImportResult {
+  imported: CustomEmoji[];
+  skipped: { name: string; reason: string }[];
+}
+
+export interface Sticker {
+  id: string;
+  pack_id: string;
+  name: string;
+  url: string;
+  animated: boolean;
+}
+
+export interface StickerPack {
+  id: string;
+  name: string;
+  description?: string | null;
+  user_id: string;
+  stickers: Sticker[];
+}
+
+// ─── Server templates ───
+export interface ServerTemplateRole {
+  key: string;
+  name: string;
+  color?: string | null;
+  position: number;
+  permissions: number;
+  max_upload_size?: number | null;
+  storage_quota?: number | null;
+}
+
+export interface ServerTemplateChannel {
+  name: string;
+  description: string;
+  position: number;
+  channel_type: "text" | "voice" | "plugin" | "forum" | "announcement";
+  encrypted: boolean;
+  plugin_url?: string | null;
+  /** Key of a template category */
+  category?: string | null;
+  /** `role` is a template role key, or "admin" for the server's Admin role */
+  overrides: { role: string; allow: number; deny: number }[];
+}
+
+/** Template JSON; can be passed as `template` when creating a server */
+export interface ServerTemplate {
+  format: "sivyspeak-server-template";
+  version: number;
+  name: string;
+  description: string;
+  settings: {
+    sound_chance: number;
+    allowed_upload_types?: string | null;
+    max_upload_size?: number | null;
+  };
+  roles: ServerTemplateRole[];
+  categories: { key: string; name: string; position: number }[];
+  channels: ServerTemplateChannel[];
+}
+
+/** A server's stored template (`/api/servers/{id}/template`, `/api/templates/{code}`) */
+export interface ServerTemplateInfo {
+  code: string;
+  server_id: string;
+  name: string;
+  description: string;
+  user_id: string;
+  created_at: string;
+  updated_at: string;
+  template: ServerTemplate;
 }
 
 // ─── Invite info (from /api/invites) ───
@@ -142,6 +477,31 @@ export interface InviteInfo {
   uses: number;
   max_uses: number | null;
   created_at: string;
+  server_id: string;
+  expires_at: string | null;
+  created_by: string | null;
+  creator_name: string | null;
+  /** Channel the client opens after joining */
+  channel_id: string | null;
+  /** Members who joined through it are removed when they disconnect, unless given a role */
+  temporary: boolean;
+  vanity: boolean;
+  joins: number;
+}
+
+// ─── Invite creation / acceptance ───
+export interface CreateInviteRequest {
+  max_uses?: number;
+  /** 0 or omitted = never expires */
+  max_age_secs?: number;
+  channel_id?: string;
+  temporary?: boolean;
+}
+
+export interface AcceptInviteResponse {
+  server: ServerInfo;
+  channel_id: string | null;
+  temporary: boolean;
 }
 
 // ─── Audit log entry (from /api/audit-logs) ───
@@ -171,6 +531,12 @@ export interface ApiMessage {
   reactions?: ReactionGroup[];
   pinned_at?: string;
   pinned_by?: string;
+  sticker_id?: string | null;
+  sticker?: Sticker | null;
+  /** Forum post the message belongs to */
+  post_id?: string | null;
+  /** When the announcement was cross-posted to followers */
+  published_at?: string | null;
 }
 
 export interface MessageWithReply {
@@ -194,13 +560,36 @@ export interface BotInfo {
 export interface MemberInfo {
   user_id: string;
   display_name: string;
+  /** Per-server nickname, shown instead of display_name when set */
+  nickname: string | null;
+  /** Hasn't finished onboarding yet */
+  pending: boolean;
   avatar_url: string | null;
   is_bot: boolean;
   is_online: boolean;
+  status: PresenceStatus;
+  custom_status: string | null;
   joined_at: string;
   roles: RoleBrief[];
 }
 
+/** Query for GET /api/servers/{id}/members; pass the last user_id as `after` for the next page */
+export interface MembersQuery {
+  after?: string;
+  limit?: number;
+  q?: string;
+  role_id?: string;
+  online?: boolean;
+}
+
+export type PresenceStatus = "online" | "idle" | "dnd" | "invisible" | "offline";
+
+export interface Presence {
+  status: PresenceStatus;
+  custom_status: string | null;
+  custom_status_expires_at: string | null;
+}
+
 export interface RoleBrief {
   id: string;
   name: string;
@@ -250,6 +639,15 @@ export interface FederatedChannel {
   peer_id: string;
   remote_channel_id: string;
   created_at: string;
+  /** `link` relays both ways; `follow` only receives the remote channel's announcements */
+  mode: "link" | "follow";
+}
+
+/** POST /api/federation/follows */
+export interface FollowRemoteRequest {
+  peer_id: string;
+  remote_channel_id: string;
+  local_channel_id: string;
 }
 
 export interface FederationStatus {
@@ -212,6 +212,27 @@ export const PERMISSION_DEFS: PermissionDef[] = [
     description: "Timeout members to prevent them from chatting",
     category: "voice",
   },
+  {
+    key: "CHANGE_NICKNAME",
+    value: 1 << 26,
+    label: "Change Nickname",
+    description: "Change your own nickname in this server",
+    category: "general",
+  },
+  {
+    key: "MANAGE_NICKNAMES",
+    value: 1 << 27,
+    label: "Manage Nicknames",
+    description: "Change other members' nicknames",
+    category: "general",
+  },
+  {
+    key: "MANAGE_EVENTS",
+    value: 1 << 28,
+    label: "Manage Events",
+    description: "Schedule, edit and cancel server events",
+    category: "general",
+  },
   // ── Advanced ──
   {
     key: "ADMINISTRATOR",
@@ -273,6 +294,7 @@ export const PERMISSION_PRESETS = {
       "SPEAK",
       "VIDEO",
       "USE_VOICE_ACTIVITY",
+      "CHANGE_NICKNAME",
     ].includes(d.key),
   ).reduce((acc, d) => acc | d.value, 0),
 
@@ -295,6 +317,9 @@ export const PERMISSION_PRESETS = {
       "MUTE_MEMBERS",
       "USE_VOICE_ACTIVITY",
       "MODERATE_MEMBERS",
+      "CHANGE_NICKNAME",
+      "MANAGE_NICKNAMES",
+      "MANAGE_EVENTS",
     ].includes(d.key),
   ).reduce((acc, d) => acc | d.value, 0),
 
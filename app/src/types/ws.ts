@@ -1,6 +1,33 @@
 // ─── WebSocket Messages ───
 
-import type { VoicePeer } from "./models";
+import type {
+  Attachment,
+  ForumPost,
+  ForumTag,
+  Presence,
+  ScheduledEvent,
+  ServerInfo,
+  Sticker,
+  VoicePeer,
+} from "./models";
+
+// ─── Gateway intents (pass as `intents=<bits>` on /ws; default: all) ───
+// SYNC NOTE: Bit positions must match server/src/models.rs Intents.
+export const GATEWAY_INTENTS = {
+  MESSAGES: 1 << 0,
+  PRESENCE: 1 << 1,
+  VOICE: 1 << 2,
+  TYPING: 1 << 3,
+  REACTIONS: 1 << 4,
+  MODERATION: 1 << 5,
+} as const;
+
+// ─── Wire format (pass as `encoding=json|msgpack` and `compress=zstd` on /ws) ───
+// msgpack: server frames are binary MessagePack maps; clients may send binary
+// MessagePack or text JSON. zstd: every server frame is binary and continues one
+// zstd stream for the whole connection — feed frames to a single streaming decoder.
+export type WsEncoding = "json" | "msgpack";
+export type WsCompression = "zstd";
 
 export type WsClientMessage =
   | { type: "join_channel"; channel_id: string }
@@ -13,6 +40,12 @@ export type WsClientMessage =
     user_id: string;
     user_name: string;
     reply_to?: string;
+    /** Upload ids to attach, uploaded with this channel's server_id (requires SEND_FILES) */
+    attachments?: string[];
+    /** Send a sticker as the whole message (content must be empty) */
+    sticker_id?: string;
+    /** Post to reply in (required in forum channels) */
+    post_id?: string;
   }
   | { type: "timeout_user"; user_id: string; duration_seconds: number }
   | {
@@ -66,10 +99,16 @@ export type WsClientMessage =
     message_id: string;
     channel_id: string;
   }
-  | { type: "typing_start"; channel_id: string };
+  | { type: "typing_start"; channel_id: string }
+  | { type: "resume"; session_id: string; last_seq: number };
+
+/** Sequenced events carry `seq`; direct replies (errors, pongs, resume acks) do not */
+export type WsServerFrame = WsServerMessage & { seq?: number };
 
 export type WsServerMessage =
-  | { type: "identity"; user_id: string }
+  | { type: "identity"; user_id: string; session_id: string }
+  | { type: "resumed"; session_id: string; replayed: number }
+  | { type: "invalid_session"; reason: string }
   | {
     type: "new_message";
     id: string;
@@ -86,6 +125,10 @@ export type WsServerMessage =
       content: string;
       user_name: string;
     } | null;
+    attachments?: Attachment[];
+    sticker?: Sticker;
+    /** Forum post the message replies in */
+    post_id?: string;
   }
   | {
     type: "message_edited";
@@ -139,6 +182,8 @@ export type WsServerMessage =
     pinned_at?: string;
     pinned_by?: string;
   }
+  /** An announcement was cross-posted to the channel's followers */
+  | { type: "message_published"; id: string; channel_id: string; published_at: string }
   | { type: "error"; message: string }
   | {
     type: "voice_peer_joined";
@@ -191,4 +236,17 @@ export type WsServerMessage =
   | {
     type: "voice_state_sync";
     voice_states: VoicePeer[];
-  };
+  }
+  | ({ type: "presence_update"; user_id: string } & Presence)
+  /** A server's settings or owner changed */
+  | { type: "server_update"; server: ServerInfo }
+  /** You joined or left a server; the connection now receives its events, or stops */
+  | { type: "membership_update"; server_id: string; joined: boolean }
+  | { type: "member_update"; server_id: string; user_id: string; nickname: string | null }
+  | { type: "event_update"; event: ScheduledEvent }
+  | { type: "event_delete"; server_id: string; event_id: string }
+  /** Only sent to users who RSVP'd */
+  | { type: "event_starting"; event: ScheduledEvent }
+  | { type: "forum_post_update"; post: ForumPost }
+  | { type: "forum_post_delete"; channel_id: string; post_id: string }
+  | { type: "forum_tags_update"; channel_id: string; tags: ForumTag[] };
@@ -0,0 +1,36 @@
+-- Bot applications: registered once by a developer, installed into servers via OAuth2
+CREATE TABLE IF NOT EXISTS applications (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    description TEXT NOT NULL DEFAULT '',
+    avatar_url TEXT,
+    owner_id TEXT NOT NULL,
+    client_secret TEXT NOT NULL,
+    redirect_uris TEXT NOT NULL DEFAULT '', -- newline-separated list
+    permissions INTEGER NOT NULL DEFAULT 0, -- permissions requested on install
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (owner_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- Short-lived OAuth2 authorization codes issued when an admin approves an install
+CREATE TABLE IF NOT EXISTS oauth_codes (
+    code TEXT PRIMARY KEY,
+    application_id TEXT NOT NULL,
+    server_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    redirect_uri TEXT NOT NULL,
+    permissions INTEGER NOT NULL DEFAULT 0,
+    expires_at TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (application_id) REFERENCES applications(id) ON DELETE CASCADE
+);
+
+-- Installed bots remember which application they came from and their managed role
+ALTER TABLE bots ADD COLUMN application_id TEXT REFERENCES applications(id) ON DELETE CASCADE;
+ALTER TABLE bots ADD COLUMN role_id TEXT;
+
+-- Managed roles are owned by an installed bot and cannot be edited by hand
+ALTER TABLE roles ADD COLUMN managed INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_applications_owner ON applications(owner_id);
+CREATE INDEX IF NOT EXISTS idx_bots_application ON bots(application_id);
@@ -0,0 +1,4 @@
+-- Rich presence: user-selected status and custom status survive restarts
+ALTER TABLE users ADD COLUMN status TEXT NOT NULL DEFAULT 'online'; -- online | idle | dnd | invisible
+ALTER TABLE users ADD COLUMN custom_status TEXT;
+ALTER TABLE users ADD COLUMN custom_status_expires_at TEXT;
@@ -0,0 +1,13 @@
+-- Content-addressed upload storage: identical bytes share one file
+CREATE TABLE IF NOT EXISTS blobs (
+    hash       TEXT PRIMARY KEY,           -- SHA-256, lowercase hex
+    size       INTEGER NOT NULL,
+    ref_count  INTEGER NOT NULL DEFAULT 0, -- upload rows pointing at this blob
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_blobs_unreferenced ON blobs(ref_count);
+
+-- NULL for uploads stored before deduplication (./uploads/{id}.{ext})
+ALTER TABLE uploads ADD COLUMN blob_hash TEXT REFERENCES blobs(hash);
+CREATE INDEX IF NOT EXISTS idx_uploads_blob ON uploads(blob_hash);
@@ -0,0 +1,3 @@
+-- Pixel dimensions of image uploads (NULL for other files)
+ALTER TABLE uploads ADD COLUMN width INTEGER;
+ALTER TABLE uploads ADD COLUMN height INTEGER;
@@ -0,0 +1,2 @@
+-- Per-server upload allow-list: comma-separated MIME types, NULL = every supported type
+ALTER TABLE servers ADD COLUMN allowed_upload_types TEXT;
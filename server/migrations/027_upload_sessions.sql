@@ -0,0 +1,18 @@
+-- Resumable uploads in progress; bytes live in the partial-upload directory until finalized
+CREATE TABLE IF NOT EXISTS upload_sessions (
+    id         TEXT PRIMARY KEY,
+    user_id    TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    server_id  TEXT REFERENCES servers(id) ON DELETE CASCADE,
+    filename   TEXT NOT NULL,
+    mime_type  TEXT NOT NULL,
+    size       INTEGER NOT NULL,           -- declared total size
+    received   INTEGER NOT NULL DEFAULT 0, -- bytes written so far (the resume offset)
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_upload_sessions_updated ON upload_sessions(updated_at);
+
+-- Upload size limits in bytes (NULL = inherit: role → server → instance default)
+ALTER TABLE servers ADD COLUMN max_upload_size INTEGER;
+ALTER TABLE roles ADD COLUMN max_upload_size INTEGER;
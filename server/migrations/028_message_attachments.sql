@@ -0,0 +1,10 @@
+-- Uploads attached to messages. An upload belongs to at most one message, and
+-- downloading it requires access to that message's channel.
+CREATE TABLE IF NOT EXISTS message_attachments (
+    upload_id  TEXT PRIMARY KEY REFERENCES uploads(id) ON DELETE CASCADE,
+    message_id TEXT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    channel_id TEXT NOT NULL,
+    position   INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS idx_message_attachments_message ON message_attachments(message_id, position);
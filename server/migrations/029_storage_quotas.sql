@@ -0,0 +1,16 @@
+-- Server an upload was shared in, so storage can be counted per server
+ALTER TABLE uploads ADD COLUMN server_id TEXT REFERENCES servers(id) ON DELETE SET NULL;
+
+UPDATE uploads SET server_id = (
+    SELECT c.server_id FROM message_attachments ma
+    JOIN channels c ON c.id = ma.channel_id
+    WHERE ma.upload_id = uploads.id
+);
+
+CREATE INDEX IF NOT EXISTS idx_uploads_user ON uploads(user_id);
+CREATE INDEX IF NOT EXISTS idx_uploads_server ON uploads(server_id);
+
+-- Storage quotas in bytes (NULL = instance default)
+ALTER TABLE servers ADD COLUMN storage_quota INTEGER;
+-- Per-member quota for members with this role (NULL = instance default)
+ALTER TABLE roles ADD COLUMN storage_quota INTEGER;
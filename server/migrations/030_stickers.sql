@@ -0,0 +1,27 @@
+-- Animated (GIF / animated WebP) custom emoji
+ALTER TABLE custom_emoji ADD COLUMN animated INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS sticker_packs (
+    id          TEXT PRIMARY KEY,
+    name        TEXT NOT NULL,
+    description TEXT,
+    user_id     TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at  TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS stickers (
+    id         TEXT PRIMARY KEY,
+    pack_id    TEXT NOT NULL REFERENCES sticker_packs(id) ON DELETE CASCADE,
+    name       TEXT NOT NULL,
+    upload_id  TEXT NOT NULL REFERENCES uploads(id) ON DELETE CASCADE,
+    user_id    TEXT NOT NULL,
+    animated   INTEGER NOT NULL DEFAULT 0,
+    position   INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL,
+    UNIQUE (pack_id, name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_stickers_pack ON stickers(pack_id, position);
+
+-- A sticker sent as a whole message (the message has no text)
+ALTER TABLE messages ADD COLUMN sticker_id TEXT REFERENCES stickers(id) ON DELETE SET NULL;
@@ -0,0 +1,12 @@
+-- Snapshots of a server's structure that new servers can be created from
+CREATE TABLE IF NOT EXISTS server_templates (
+    code        TEXT PRIMARY KEY,
+    server_id   TEXT NOT NULL UNIQUE REFERENCES servers(id) ON DELETE CASCADE,
+    name        TEXT NOT NULL,
+    description TEXT NOT NULL DEFAULT '',
+    user_id     TEXT NOT NULL,
+    -- The template JSON (see src/templates.rs)
+    data        TEXT NOT NULL,
+    created_at  TEXT NOT NULL,
+    updated_at  TEXT NOT NULL
+);
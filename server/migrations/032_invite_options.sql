@@ -0,0 +1,14 @@
+-- Invite expiry, creator tracking, channel targets, vanity codes and temporary membership
+ALTER TABLE invite_codes ADD COLUMN expires_at TEXT;
+ALTER TABLE invite_codes ADD COLUMN created_by TEXT REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE invite_codes ADD COLUMN channel_id TEXT REFERENCES channels(id) ON DELETE SET NULL;
+ALTER TABLE invite_codes ADD COLUMN temporary INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE invite_codes ADD COLUMN vanity INTEGER NOT NULL DEFAULT 0;
+-- Members who joined through the invite (`uses` also counts legacy connect-token joins)
+ALTER TABLE invite_codes ADD COLUMN joins INTEGER NOT NULL DEFAULT 0;
+
+-- A server has at most one vanity code
+CREATE UNIQUE INDEX IF NOT EXISTS idx_invite_codes_vanity ON invite_codes(server_id) WHERE vanity = 1;
+
+-- Joined through a temporary invite: removed on disconnect unless given a role
+ALTER TABLE server_members ADD COLUMN temporary INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,6 @@
+-- Per-server nicknames, shown instead of the user's display name in that server
+ALTER TABLE server_members ADD COLUMN nickname TEXT;
+
+-- CHANGE_NICKNAME (1 << 26) for the default roles, MANAGE_NICKNAMES (1 << 27) for moderators
+UPDATE roles SET permissions = permissions | 67108864 WHERE id IN ('moderator-role', 'member-role');
+UPDATE roles SET permissions = permissions | 134217728 WHERE id = 'moderator-role';
@@ -0,0 +1,17 @@
+-- Membership screening, welcome messages in a system channel and manual approval
+ALTER TABLE servers ADD COLUMN system_channel_id TEXT REFERENCES channels(id) ON DELETE SET NULL;
+ALTER TABLE servers ADD COLUMN welcome_message TEXT;
+
+CREATE TABLE IF NOT EXISTS server_screening (
+    server_id       TEXT PRIMARY KEY REFERENCES servers(id) ON DELETE CASCADE,
+    enabled         INTEGER NOT NULL DEFAULT 0,
+    rules           TEXT NOT NULL DEFAULT '',
+    questions       TEXT NOT NULL DEFAULT '[]',
+    manual_approval INTEGER NOT NULL DEFAULT 0,
+    updated_at      TEXT NOT NULL
+);
+
+-- Pending members haven't passed screening (or been approved) yet and can't post
+ALTER TABLE server_members ADD COLUMN pending INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE server_members ADD COLUMN screened_at TEXT;
+ALTER TABLE server_members ADD COLUMN screening_answers TEXT;
@@ -0,0 +1,30 @@
+-- Scheduled server events, in a voice channel or at an external location, with RSVPs
+CREATE TABLE IF NOT EXISTS scheduled_events (
+    id          TEXT PRIMARY KEY,
+    server_id   TEXT NOT NULL REFERENCES servers(id) ON DELETE CASCADE,
+    creator_id  TEXT REFERENCES users(id) ON DELETE SET NULL,
+    title       TEXT NOT NULL,
+    description TEXT NOT NULL DEFAULT '',
+    starts_at   TEXT NOT NULL,
+    ends_at     TEXT,
+    channel_id  TEXT REFERENCES channels(id) ON DELETE SET NULL,
+    location    TEXT,
+    -- scheduled | active | completed | cancelled
+    status      TEXT NOT NULL DEFAULT 'scheduled',
+    created_at  TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_scheduled_events_server ON scheduled_events(server_id, starts_at);
+CREATE INDEX IF NOT EXISTS idx_scheduled_events_status ON scheduled_events(status, starts_at);
+
+CREATE TABLE IF NOT EXISTS event_rsvps (
+    event_id   TEXT NOT NULL REFERENCES scheduled_events(id) ON DELETE CASCADE,
+    user_id    TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- going | interested
+    status     TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (event_id, user_id)
+);
+
+-- MANAGE_EVENTS (1 << 28) for moderators
+UPDATE roles SET permissions = permissions | 268435456 WHERE id = 'moderator-role';
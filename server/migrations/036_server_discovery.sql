@@ -0,0 +1,5 @@
+-- Opt-in public server directory
+ALTER TABLE servers ADD COLUMN discoverable INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE servers ADD COLUMN tags TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_servers_discoverable ON servers(discoverable);
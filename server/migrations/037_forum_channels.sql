@@ -0,0 +1,34 @@
+-- Forum channels: titled posts, each with its own reply stream, carrying per-forum tags
+CREATE TABLE IF NOT EXISTS forum_posts (
+    id               TEXT PRIMARY KEY,
+    channel_id       TEXT NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    author_id        TEXT REFERENCES users(id) ON DELETE SET NULL,
+    title            TEXT NOT NULL,
+    pinned           INTEGER NOT NULL DEFAULT 0,
+    -- Locked posts only take replies from moderators
+    locked           INTEGER NOT NULL DEFAULT 0,
+    created_at       TEXT NOT NULL,
+    last_activity_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_forum_posts_activity ON forum_posts(channel_id, last_activity_at);
+CREATE INDEX IF NOT EXISTS idx_forum_posts_created ON forum_posts(channel_id, created_at);
+
+CREATE TABLE IF NOT EXISTS forum_tags (
+    id         TEXT PRIMARY KEY,
+    channel_id TEXT NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    name       TEXT NOT NULL,
+    position   INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS idx_forum_tags_channel ON forum_tags(channel_id, position);
+
+CREATE TABLE IF NOT EXISTS forum_post_tags (
+    post_id TEXT NOT NULL REFERENCES forum_posts(id) ON DELETE CASCADE,
+    tag_id  TEXT NOT NULL REFERENCES forum_tags(id) ON DELETE CASCADE,
+    PRIMARY KEY (post_id, tag_id)
+);
+
+-- Messages in a forum channel belong to a post
+ALTER TABLE messages ADD COLUMN post_id TEXT REFERENCES forum_posts(id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS idx_messages_post ON messages(post_id, created_at);
@@ -0,0 +1,21 @@
+-- Announcement channels: published messages are cross-posted to every follower
+ALTER TABLE messages ADD COLUMN published_at TEXT;
+
+-- Followers of a local announcement channel: a local channel, or a channel on a federation peer
+CREATE TABLE IF NOT EXISTS channel_follows (
+    id                TEXT PRIMARY KEY,
+    source_channel_id TEXT NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    target_channel_id TEXT REFERENCES channels(id) ON DELETE CASCADE,
+    peer_id           TEXT REFERENCES federation_peers(id) ON DELETE CASCADE,
+    remote_channel_id TEXT,
+    created_by        TEXT,
+    created_at        TEXT NOT NULL,
+    CHECK ((target_channel_id IS NULL) <> (peer_id IS NULL))
+);
+
+-- NULLs are distinct, so local and remote follows don't collide
+CREATE UNIQUE INDEX IF NOT EXISTS idx_channel_follows_local ON channel_follows(source_channel_id, target_channel_id);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_channel_follows_remote ON channel_follows(source_channel_id, peer_id, remote_channel_id);
+
+-- 'link' relays both ways; 'follow' only receives a peer's published announcements
+ALTER TABLE federated_channels ADD COLUMN mode TEXT NOT NULL DEFAULT 'link';
@@ -0,0 +1,24 @@
+-- Installed bots hold their managed role, so role holders are no longer only users.
+-- SQLite can't drop a foreign key in place: rebuild user_roles without the users(id) reference.
+CREATE TABLE user_roles_new (
+    user_id TEXT NOT NULL,
+    role_id TEXT NOT NULL,
+    assigned_at TEXT NOT NULL,
+    PRIMARY KEY (user_id, role_id),
+    FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE
+);
+
+INSERT INTO user_roles_new (user_id, role_id, assigned_at)
+    SELECT user_id, role_id, assigned_at FROM user_roles;
+
+DROP TABLE user_roles;
+ALTER TABLE user_roles_new RENAME TO user_roles;
+
+CREATE INDEX IF NOT EXISTS idx_user_roles_user ON user_roles(user_id);
+CREATE INDEX IF NOT EXISTS idx_user_roles_role ON user_roles(role_id);
+
+-- Give bots installed before this migration their membership and managed role
+INSERT OR IGNORE INTO server_members (server_id, user_id, joined_at)
+    SELECT server_id, id, created_at FROM bots WHERE application_id IS NOT NULL;
+INSERT OR IGNORE INTO user_roles (user_id, role_id, assigned_at)
+    SELECT id, role_id, created_at FROM bots WHERE role_id IS NOT NULL;
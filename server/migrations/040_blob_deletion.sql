@@ -0,0 +1,3 @@
+-- Set by garbage collection before it removes a blob's file; uploads of the same
+-- content back off until the row is gone and then store the bytes again
+ALTER TABLE blobs ADD COLUMN deleting INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+-- Announcement channels federation peers may follow; off until a channel manager opts in
+ALTER TABLE channels ADD COLUMN remote_follows INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+
+use axum::http::{HeaderMap, StatusCode};
+use hmac::{Hmac, Mac};
+use sea_orm::*;
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+use crate::entities::{channel, custom_emoji, message_attachment, sticker, upload, user};
+use crate::models::{Attachment, Permissions};
+use crate::permissions::check_channel_permission;
+use crate::routes::auth;
+use crate::state::AppState;
+
+/// Most files one message can carry
+pub const MAX_ATTACHMENTS: usize = 10;
+/// Signed URLs stay valid at least this long
+const URL_TTL_SECS: i64 = 24 * 3600;
+/// Expiry is rounded up to this, so a URL stays the same (and cacheable) for a while
+const URL_EXPIRY_STEP_SECS: i64 = 3600;
+
+fn signature(secret: &str, upload_id: &str, expires: i64) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("upload:{upload_id}:{expires}").as_bytes());
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+/// Download URL for an attached upload, valid for at least `URL_TTL_SECS`
+pub fn signed_url(state: &AppState, upload_id: &str) -> String {
+    let now = chrono::Utc::now().timestamp();
+    let expires = (now + URL_TTL_SECS + URL_EXPIRY_STEP_SECS - 1) / URL_EXPIRY_STEP_SECS * URL_EXPIRY_STEP_SECS;
+    let sig = signature(&state.jwt_secret, upload_id, expires);
+    format!("/api/uploads/{upload_id}?expires={expires}&sig={sig}")
+}
+
+fn signature_valid(state: &AppState, upload_id: &str, expires: i64, sig: &str) -> bool {
+    expires > chrono::Utc::now().timestamp()
+        && bool::from(signature(&state.jwt_secret, upload_id, expires).as_bytes().ct_eq(sig.as_bytes()))
+}
+
+pub fn to_attachment(state: &AppState, row: upload::Model) -> Attachment {
+    Attachment {
+        url: signed_url(state, &row.id),
+        id: row.id,
+        filename: row.filename,
+        mime_type: row.mime_type,
+        size: row.size,
+        width: row.width,
+        height: row.height,
+    }
+}
+
+/// Check the caller may download `upload_id`. Uploads attached to a message need a
+/// valid signed URL or a bearer token with VIEW_CHANNELS on the message's channel;
+/// everything else (avatars, emoji, unattached files) is public.
+/// Returns whether the upload is access-controlled.
+pub async fn authorize_download(
+    state: &AppState,
+    upload_id: &str,
+    expires: Option<i64>,
+    sig: Option<&str>,
+    headers: &HeaderMap,
+) -> Result<bool, StatusCode> {
+    let Some(attachment) = message_attachment::Entity::find_by_id(upload_id)
+        .one(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    else {
+        return Ok(false);
+    };
+
+    if let (Some(expires), Some(sig)) = (expires, sig) {
+        if signature_valid(state, upload_id, expires, sig) {
+            return Ok(true);
+        }
+    }
+
+    let claims = auth::extract_claims(&state.jwt_secret, headers).map_err(|_| StatusCode::FORBIDDEN)?;
+    if check_channel_permission(state, &claims.sub, &attachment.channel_id, Permissions::VIEW_CHANNELS).await? {
+        Ok(true)
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+/// Uploads are checked against one server's quota and allow-list when made, so they
+/// can only be attached in that server
+fn check_server(row: &upload::Model, server_id: &str) -> Result<(), String> {
+    if row.server_id.as_deref() != Some(server_id) {
+        return Err(format!("Upload {} wasn't made for this server; upload it here with its server_id", row.id));
+    }
+    Ok(())
+}
+
+/// Check `upload_ids` can be attached by `user_id` in `channel_id`: their own, made
+/// for the channel's server, not yet attached anywhere, and not an avatar or emoji.
+/// Returns the uploads in the given order.
+pub async fn validate(
+    state: &AppState,
+    user_id: &str,
+    channel_id: &str,
+    upload_ids: &[String],
+) -> Result<Vec<upload::Model>, String> {
+    if upload_ids.len() > MAX_ATTACHMENTS {
+        return Err(format!("A message can have at most {MAX_ATTACHMENTS} attachments"));
+    }
+    let server_id = channel::Entity::find_by_id(channel_id)
+        .one(&state.db)
+        .await
+        .map_err(|e| format!("DB error: {e}"))?
+        .map(|c| c.server_id)
+        .ok_or_else(|| "Channel not found".to_string())?;
+    let mut uploads = Vec::with_capacity(upload_ids.len());
+    for (i, id) in upload_ids.iter().enumerate() {
+        if upload_ids[..i].contains(id) {
+            return Err("Duplicate attachment".to_string());
+        }
+        let row = upload::Entity::find_by_id(id)
+            .filter(upload::Column::UserId.eq(user_id))
+            .one(&state.db)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?
+            .ok_or_else(|| format!("Upload {id} not found"))?;
+        check_server(&row, &server_id)?;
+
+        let attached = message_attachment::Entity::find_by_id(id)
+            .count(&state.db)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?
+            > 0;
+        let is_emoji = custom_emoji::Entity::find()
+            .filter(custom_emoji::Column::UploadId.eq(id))
+            .count(&state.db)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?
+            + sticker::Entity::find()
+                .filter(sticker::Column::UploadId.eq(id))
+                .count(&state.db)
+                .await
+                .map_err(|e| format!("DB error: {e}"))?
+            > 0;
+        let is_avatar = user::Entity::find()
+            .filter(user::Column::AvatarUrl.eq(format!("/api/uploads/{id}")))
+            .count(&state.db)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?
+            > 0;
+        if attached || is_emoji || is_avatar {
+            return Err(format!("Upload {id} is already in use"));
+        }
+        uploads.push(row);
+    }
+    Ok(uploads)
+}
+
+/// Record `uploads` as attachments of a message
+pub async fn link<C: ConnectionTrait>(
+    db: &C,
+    message_id: &str,
+    channel_id: &str,
+    uploads: &[upload::Model],
+) -> Result<(), DbErr> {
+    if uploads.is_empty() {
+        return Ok(());
+    }
+    let rows = uploads.iter().enumerate().map(|(i, u)| message_attachment::ActiveModel {
+        upload_id: Set(u.id.clone()),
+        message_id: Set(message_id.to_string()),
+        channel_id: Set(channel_id.to_string()),
+        position: Set(i as i32),
+    });
+    message_attachment::Entity::insert_many(rows)
+        .exec_without_returning(db)
+        .await?;
+    Ok(())
+}
+
+/// Attachments of each message, in order, with fresh signed URLs
+pub async fn for_messages(
+    state: &AppState,
+    message_ids: &[String],
+) -> Result<HashMap<String, Vec<Attachment>>, DbErr> {
+    if message_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let links = message_attachment::Entity::find()
+        .filter(message_attachment::Column::MessageId.is_in(message_ids))
+        .order_by_asc(message_attachment::Column::Position)
+        .all(&state.db)
+        .await?;
+    if links.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let upload_ids: Vec<&String> = links.iter().map(|l| &l.upload_id).collect();
+    let mut uploads: HashMap<String, upload::Model> = upload::Entity::find()
+        .filter(upload::Column::Id.is_in(upload_ids))
+        .all(&state.db)
+        .await?
+        .into_iter()
+        .map(|u| (u.id.clone(), u))
+        .collect();
+
+    let mut result: HashMap<String, Vec<Attachment>> = HashMap::new();
+    for link in links {
+        if let Some(row) = uploads.remove(&link.upload_id) {
+            result.entry(link.message_id).or_default().push(to_attachment(state, row));
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures_bind_upload_and_expiry() {
+        let sig = signature("secret", "upload-a", 1_000);
+        assert_eq!(sig, signature("secret", "upload-a", 1_000));
+        assert_ne!(sig, signature("secret", "upload-b", 1_000));
+        assert_ne!(sig, signature("secret", "upload-a", 2_000));
+        assert_ne!(sig, signature("other", "upload-a", 1_000));
+    }
+
+    #[test]
+    fn uploads_only_attach_in_their_server() {
+        let row = |server_id: Option<&str>| upload::Model {
+            id: "upload".into(),
+            user_id: "alice".into(),
+            server_id: server_id.map(String::from),
+            filename: "a.png".into(),
+            mime_type: "image/png".into(),
+            size: 1,
+            created_at: "2026-01-01 00:00:00".into(),
+            blob_hash: None,
+            width: None,
+            height: None,
+        };
+        assert!(check_server(&row(Some("server-a")), "server-a").is_ok());
+        // Made for another server's quota and allow-list, or for none at all
+        assert!(check_server(&row(Some("server-a")), "server-b").is_err());
+        assert!(check_server(&row(None), "server-b").is_err());
+    }
+}
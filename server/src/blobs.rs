@@ -0,0 +1,457 @@
+use axum::body::Bytes;
+use axum::http::StatusCode;
+use sea_orm::prelude::Expr;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::*;
+use sha2::{Digest, Sha256};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+
+use crate::entities::{
+    application, blob, bot, custom_emoji, message, message_attachment, server, sticker, upload, user,
+    webhook,
+};
+use crate::state::AppState;
+
+/// How long an upload waits for garbage collection to finish removing its blob
+const CLAIM_RETRY: Duration = Duration::from_millis(200);
+const CLAIM_ATTEMPTS: usize = 50;
+
+pub fn hash_bytes(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// SHA-256 and length of a file, read in chunks
+pub async fn hash_file(path: &Path) -> std::io::Result<(String, i64)> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 256 * 1024];
+    let mut size = 0i64;
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        size += n as i64;
+    }
+    Ok((format!("{:x}", hasher.finalize()), size))
+}
+
+/// Blobs are sharded by the first two hex characters to keep directories small
+pub fn blob_key(hash: &str) -> String {
+    format!("blobs/{}/{hash}", &hash[..2])
+}
+
+/// Thumbnails belong to the blob they were rendered from
+pub fn thumbnail_key(hash: &str, size: u32) -> String {
+    format!("thumbs/{}/{hash}/{size}", &hash[..2])
+}
+
+/// Storage key of an upload's bytes
+pub fn upload_key(row: &upload::Model) -> String {
+    match &row.blob_hash {
+        Some(hash) => blob_key(hash),
+        None => format!("{}.{}", row.id, crate::routes::uploads::get_extension(&row.mime_type)),
+    }
+}
+
+/// Store `data` and its `thumbnails` (once per distinct content) and insert `row`
+/// pointing at it. Returns the content hash.
+pub async fn insert_upload(
+    state: &AppState,
+    row: upload::ActiveModel,
+    data: Bytes,
+    thumbnails: Vec<(u32, Vec<u8>)>,
+) -> Result<String, (StatusCode, String)> {
+    let hash = hash_bytes(&data);
+    let size = data.len() as i64;
+    link_blob(state, row, hash, size, BlobSource::Memory(data, thumbnails)).await
+}
+
+/// Like `insert_upload`, streaming the bytes from a local file (no thumbnails)
+pub async fn insert_upload_file(
+    state: &AppState,
+    row: upload::ActiveModel,
+    path: &Path,
+) -> Result<String, (StatusCode, String)> {
+    let (hash, size) = hash_file(path)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Read error: {e}")))?;
+    link_blob(state, row, hash, size, BlobSource::File(path)).await
+}
+
+enum BlobSource<'a> {
+    Memory(Bytes, Vec<(u32, Vec<u8>)>),
+    File(&'a Path),
+}
+
+async fn link_blob(
+    state: &AppState,
+    mut row: upload::ActiveModel,
+    hash: String,
+    size: i64,
+    source: BlobSource<'_>,
+) -> Result<String, (StatusCode, String)> {
+    let db_err = |e: DbErr| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"));
+    let key = blob_key(&hash);
+
+    // Reference the blob before looking for its file: GC leaves referenced blobs alone
+    take_reference(state, &hash, size).await?;
+
+    let stored = async {
+        if state.storage.exists(&key).await? {
+            return Ok(());
+        }
+        match source {
+            BlobSource::Memory(data, thumbnails) => {
+                // Thumbnails first: once the blob exists, later uploads of it skip this
+                for (size, thumb) in thumbnails {
+                    state.storage.put(&thumbnail_key(&hash, size), thumb.into()).await?;
+                }
+                state.storage.put(&key, data).await
+            }
+            BlobSource::File(path) => state.storage.put_file(&key, path).await,
+        }
+    }
+    .await;
+    if let Err(e) = stored {
+        release_reference(&state.db, &hash).await.map_err(db_err)?;
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Write error: {e}")));
+    }
+
+    row.blob_hash = Set(Some(hash.clone()));
+    if let Err(e) = upload::Entity::insert(row).exec_without_returning(&state.db).await {
+        release_reference(&state.db, &hash).await.map_err(db_err)?;
+        return Err(db_err(e));
+    }
+
+    Ok(hash)
+}
+
+/// Count one more reference to a blob, creating its row if needed. While GC is
+/// removing the blob the reference is given back and retried once it's gone.
+async fn take_reference(state: &AppState, hash: &str, size: i64) -> Result<(), (StatusCode, String)> {
+    let db_err = |e: DbErr| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"));
+    for _ in 0..CLAIM_ATTEMPTS {
+        blob::Entity::insert(blob::ActiveModel {
+            hash: Set(hash.to_string()),
+            size: Set(size),
+            ref_count: Set(1),
+            created_at: Set(chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()),
+            deleting: Set(false),
+        })
+        .on_conflict(
+            OnConflict::column(blob::Column::Hash)
+                .value(blob::Column::RefCount, Expr::col(blob::Column::RefCount).add(1))
+                .to_owned(),
+        )
+        .exec_without_returning(&state.db)
+        .await
+        .map_err(db_err)?;
+
+        let claimed = blob::Entity::find_by_id(hash)
+            .one(&state.db)
+            .await
+            .map_err(db_err)?
+            .is_none_or(|b| b.deleting);
+        if !claimed {
+            return Ok(());
+        }
+        release_reference(&state.db, hash).await.map_err(db_err)?;
+        tokio::time::sleep(CLAIM_RETRY).await;
+    }
+    Err((StatusCode::SERVICE_UNAVAILABLE, "Storage is busy, try again".into()))
+}
+
+async fn release_reference<C: ConnectionTrait>(db: &C, hash: &str) -> Result<(), DbErr> {
+    blob::Entity::update_many()
+        .col_expr(blob::Column::RefCount, Expr::col(blob::Column::RefCount).sub(1))
+        .filter(blob::Column::Hash.eq(hash))
+        .exec(db)
+        .await
+        .map(|_| ())
+}
+
+/// Delete an upload row and drop its reference; the blob itself goes at the next GC
+pub async fn delete_upload(state: &AppState, row: &upload::Model) -> Result<(), DbErr> {
+    let txn = state.db.begin().await?;
+    upload::Entity::delete_by_id(&row.id).exec(&txn).await?;
+    if let Some(hash) = &row.blob_hash {
+        release_reference(&txn, hash).await?;
+    }
+    txn.commit().await?;
+
+    if row.blob_hash.is_none() {
+        // Legacy per-upload file: nobody else can be using it
+        if let Err(e) = state.storage.delete(&upload_key(row)).await {
+            tracing::warn!("Failed to remove upload {}: {e}", row.id);
+        }
+    }
+    Ok(())
+}
+
+/// Upload ids linked from message content (`/api/uploads/{id}`)
+fn linked_upload_ids(content: &str) -> Vec<String> {
+    content
+        .match_indices("/api/uploads/")
+        .filter_map(|(i, marker)| {
+            let rest = &content[i + marker.len()..];
+            let id: String = rest
+                .chars()
+                .take_while(|c| c.is_ascii_hexdigit() || *c == '-')
+                .collect();
+            (id.len() == 36).then_some(id)
+        })
+        .collect()
+}
+
+/// After a message is deleted, drop its attachments, and the uploads its author
+/// linked in it unless something else (another message, an emoji, an avatar) still uses them
+pub async fn release_message_uploads(state: &AppState, msg: &message::Model) -> Result<(), DbErr> {
+    let db = &state.db;
+
+    let attached = message_attachment::Entity::find()
+        .filter(message_attachment::Column::MessageId.eq(&msg.id))
+        .all(db)
+        .await?;
+    for link in attached {
+        message_attachment::Entity::delete_by_id(&link.upload_id).exec(db).await?;
+        if let Some(row) = upload::Entity::find_by_id(&link.upload_id).one(db).await? {
+            delete_upload(state, &row).await?;
+        }
+    }
+    for upload_id in linked_upload_ids(&msg.content) {
+        let Some(row) = upload::Entity::find_by_id(&upload_id)
+            .filter(upload::Column::UserId.eq(&msg.user_id))
+            .one(db)
+            .await?
+        else {
+            continue;
+        };
+
+        let url = format!("/api/uploads/{upload_id}");
+        let in_other_messages = message::Entity::find()
+            .filter(message::Column::Id.ne(&msg.id))
+            .filter(message::Column::DeletedAt.is_null())
+            .filter(message::Column::Content.contains(&upload_id))
+            .count(db)
+            .await?
+            > 0;
+        let is_emoji = custom_emoji::Entity::find()
+            .filter(custom_emoji::Column::UploadId.eq(&upload_id))
+            .count(db)
+            .await?
+            + sticker::Entity::find()
+                .filter(sticker::Column::UploadId.eq(&upload_id))
+                .count(db)
+                .await?
+            > 0;
+        let is_avatar = user::Entity::find()
+            .filter(user::Column::AvatarUrl.eq(&url))
+            .count(db)
+            .await?
+            > 0;
+
+        if !in_other_messages && !is_emoji && !is_avatar {
+            delete_upload(state, &row).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Remove blobs nothing references anymore; returns (blobs removed, bytes reclaimed).
+/// Each blob is claimed while its reference count is still 0, its files are removed,
+/// then its row; uploads of the same content wait for the row to go. Claims left by
+/// an interrupted run are finished here too.
+pub async fn collect_garbage(state: &AppState) -> Result<(usize, u64), DbErr> {
+    let db = &state.db;
+
+    let candidates = blob::Entity::find()
+        .filter(
+            Condition::any()
+                .add(blob::Column::RefCount.lte(0))
+                .add(blob::Column::Deleting.eq(true)),
+        )
+        .all(db)
+        .await?;
+
+    let mut removed = 0;
+    let mut reclaimed = 0u64;
+    for b in candidates {
+        let claim = blob::Entity::update_many()
+            .col_expr(blob::Column::Deleting, Expr::value(true))
+            .filter(blob::Column::Hash.eq(&b.hash))
+            .filter(
+                Condition::any()
+                    .add(blob::Column::RefCount.lte(0))
+                    .add(blob::Column::Deleting.eq(true)),
+            )
+            .exec(db)
+            .await?;
+        if claim.rows_affected == 0 {
+            continue;
+        }
+        if let Err(e) = state.storage.delete(&blob_key(&b.hash)).await {
+            tracing::warn!("Failed to remove blob {}: {e}", b.hash);
+            blob::Entity::update_many()
+                .col_expr(blob::Column::Deleting, Expr::value(false))
+                .filter(blob::Column::Hash.eq(&b.hash))
+                .exec(db)
+                .await?;
+            continue;
+        }
+        for &size in crate::images::THUMBNAIL_SIZES {
+            let _ = state.storage.delete(&thumbnail_key(&b.hash, size)).await;
+        }
+        blob::Entity::delete_by_id(&b.hash).exec(db).await?;
+        removed += 1;
+        reclaimed += b.size.max(0) as u64;
+    }
+    Ok((removed, reclaimed))
+}
+
+/// Values of `column` that link an upload
+async fn upload_urls_in<E: EntityTrait>(db: &DatabaseConnection, column: E::Column) -> Result<Vec<String>, DbErr> {
+    E::find()
+        .select_only()
+        .column(column)
+        .filter(column.contains("/api/uploads/"))
+        .into_tuple()
+        .all(db)
+        .await
+}
+
+/// Ids of every upload something still points at: attachments, emoji, stickers, message
+/// links and the avatar/icon/sound URLs of users, bots, apps, webhooks and servers
+async fn referenced_upload_ids(db: &DatabaseConnection) -> Result<HashSet<String>, DbErr> {
+    let mut ids: HashSet<String> = message_attachment::Entity::find()
+        .select_only()
+        .column(message_attachment::Column::UploadId)
+        .into_tuple::<String>()
+        .all(db)
+        .await?
+        .into_iter()
+        .collect();
+    ids.extend(
+        custom_emoji::Entity::find()
+            .select_only()
+            .column(custom_emoji::Column::UploadId)
+            .into_tuple::<String>()
+            .all(db)
+            .await?,
+    );
+    ids.extend(
+        sticker::Entity::find()
+            .select_only()
+            .column(sticker::Column::UploadId)
+            .into_tuple::<String>()
+            .all(db)
+            .await?,
+    );
+
+    let mut urls: Vec<String> = message::Entity::find()
+        .select_only()
+        .column(message::Column::Content)
+        .filter(message::Column::DeletedAt.is_null())
+        .filter(message::Column::Content.contains("/api/uploads/"))
+        .into_tuple()
+        .all(db)
+        .await?;
+    urls.extend(upload_urls_in::<message::Entity>(db, message::Column::AvatarUrl).await?);
+    urls.extend(upload_urls_in::<user::Entity>(db, user::Column::AvatarUrl).await?);
+    urls.extend(upload_urls_in::<bot::Entity>(db, bot::Column::AvatarUrl).await?);
+    urls.extend(upload_urls_in::<application::Entity>(db, application::Column::AvatarUrl).await?);
+    urls.extend(upload_urls_in::<webhook::Entity>(db, webhook::Column::AvatarUrl).await?);
+    urls.extend(upload_urls_in::<server::Entity>(db, server::Column::IconUrl).await?);
+    urls.extend(upload_urls_in::<server::Entity>(db, server::Column::JoinSoundUrl).await?);
+    urls.extend(upload_urls_in::<server::Entity>(db, server::Column::LeaveSoundUrl).await?);
+
+    ids.extend(urls.iter().flat_map(|url| linked_upload_ids(url)));
+    Ok(ids)
+}
+
+/// Outcome of an orphan collection (or what it would do, for a dry run)
+#[derive(Debug, Default, Serialize)]
+pub struct OrphanReport {
+    pub dry_run: bool,
+    /// Upload rows nothing references
+    pub uploads: usize,
+    pub upload_bytes: u64,
+    /// Stored files (blobs and legacy per-upload files) removed
+    pub files: usize,
+    pub bytes_reclaimed: u64,
+}
+
+/// Delete uploads older than `grace` that nothing references, then the blobs
+/// left without references. With `dry_run`, only report what would go.
+pub async fn collect_orphans(state: &AppState, grace: chrono::Duration, dry_run: bool) -> Result<OrphanReport, DbErr> {
+    let db = &state.db;
+    // Uploads are made before the message or profile that uses them
+    let cutoff = (chrono::Utc::now() - grace).format("%Y-%m-%d %H:%M:%S").to_string();
+
+    let referenced = referenced_upload_ids(db).await?;
+    let orphans: Vec<upload::Model> = upload::Entity::find()
+        .filter(upload::Column::CreatedAt.lt(cutoff))
+        .all(db)
+        .await?
+        .into_iter()
+        .filter(|u| !referenced.contains(&u.id))
+        .collect();
+
+    let mut report = OrphanReport {
+        dry_run,
+        uploads: orphans.len(),
+        upload_bytes: orphans.iter().map(|u| u.size.max(0) as u64).sum(),
+        ..Default::default()
+    };
+
+    if dry_run {
+        let mut released: HashMap<&str, i64> = HashMap::new();
+        for row in &orphans {
+            match &row.blob_hash {
+                Some(hash) => *released.entry(hash).or_default() += 1,
+                None => {
+                    report.files += 1;
+                    report.bytes_reclaimed += row.size.max(0) as u64;
+                }
+            }
+        }
+        let doomed = blob::Entity::find()
+            .filter(
+                Condition::any()
+                    .add(blob::Column::RefCount.lte(0))
+                    .add(blob::Column::Hash.is_in(released.keys().copied())),
+            )
+            .all(db)
+            .await?;
+        for b in doomed {
+            if b.ref_count - released.get(b.hash.as_str()).copied().unwrap_or(0) <= 0 {
+                report.files += 1;
+                report.bytes_reclaimed += b.size.max(0) as u64;
+            }
+        }
+        return Ok(report);
+    }
+
+    for row in &orphans {
+        // It may have been attached since the scan started
+        if message_attachment::Entity::find_by_id(&row.id).count(db).await? > 0 {
+            report.uploads -= 1;
+            report.upload_bytes -= row.size.max(0) as u64;
+            continue;
+        }
+        delete_upload(state, row).await?;
+        if row.blob_hash.is_none() {
+            report.files += 1;
+            report.bytes_reclaimed += row.size.max(0) as u64;
+        }
+    }
+    let (blobs, bytes) = collect_garbage(state).await?;
+    report.files += blobs;
+    report.bytes_reclaimed += bytes;
+    Ok(report)
+}
@@ -0,0 +1,144 @@
+//! Emoji packs: a zip holding `manifest.json` and one image per emoji, so a set
+//! of emoji can be exported from one server and imported into another.
+
+use std::io::{Cursor, Read, Write};
+
+use serde::{Deserialize, Serialize};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+const MANIFEST: &str = "manifest.json";
+const FORMAT: &str = "sivyspeak-emoji-pack";
+const VERSION: u32 = 1;
+const MAX_MANIFEST_SIZE: u64 = 256 * 1024;
+/// Most emoji one pack may hold
+pub const MAX_PACK_EMOJI: usize = 250;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    format: String,
+    version: u32,
+    #[serde(default)]
+    name: String,
+    emoji: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestEntry {
+    name: String,
+    /// Path of the image inside the zip
+    file: String,
+    #[serde(default)]
+    animated: bool,
+}
+
+/// One emoji going into or coming out of a pack
+pub struct PackEmoji {
+    pub name: String,
+    /// File extension, for export
+    pub extension: String,
+    pub animated: bool,
+    pub data: Vec<u8>,
+}
+
+/// Build a pack zip. Images are stored as-is, since they are already compressed.
+pub fn write(pack_name: &str, emoji: &[PackEmoji]) -> Result<Vec<u8>, String> {
+    let zip_err = |e: zip::result::ZipError| format!("Zip error: {e}");
+    let io_err = |e: std::io::Error| format!("Zip error: {e}");
+
+    let manifest = Manifest {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        name: pack_name.to_string(),
+        emoji: emoji
+            .iter()
+            .map(|e| ManifestEntry {
+                name: e.name.clone(),
+                file: format!("{}.{}", e.name, e.extension),
+                animated: e.animated,
+            })
+            .collect(),
+    };
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+    zip.start_file(MANIFEST, deflated).map_err(zip_err)?;
+    let json = serde_json::to_vec_pretty(&manifest).map_err(|e| format!("Manifest error: {e}"))?;
+    zip.write_all(&json).map_err(io_err)?;
+    for (entry, e) in manifest.emoji.iter().zip(emoji) {
+        zip.start_file(entry.file.as_str(), stored).map_err(zip_err)?;
+        zip.write_all(&e.data).map_err(io_err)?;
+    }
+    Ok(zip.finish().map_err(zip_err)?.into_inner())
+}
+
+/// Read the emoji listed in a pack's manifest; images over `max_file_size` are refused
+pub fn read(data: &[u8], max_file_size: u64) -> Result<Vec<PackEmoji>, String> {
+    let mut zip = ZipArchive::new(Cursor::new(data)).map_err(|e| format!("Not a valid zip: {e}"))?;
+
+    let manifest: Manifest = {
+        let file = zip.by_name(MANIFEST).map_err(|_| "Pack has no manifest.json".to_string())?;
+        if file.size() > MAX_MANIFEST_SIZE {
+            return Err("manifest.json is too large".into());
+        }
+        let mut json = Vec::new();
+        file.take(MAX_MANIFEST_SIZE).read_to_end(&mut json).map_err(|e| format!("Read error: {e}"))?;
+        serde_json::from_slice(&json).map_err(|e| format!("Invalid manifest.json: {e}"))?
+    };
+    if manifest.format != FORMAT || manifest.version != VERSION {
+        return Err(format!("Unsupported pack format (expected {FORMAT} version {VERSION})"));
+    }
+    if manifest.emoji.len() > MAX_PACK_EMOJI {
+        return Err(format!("A pack can hold at most {MAX_PACK_EMOJI} emoji"));
+    }
+
+    let mut emoji = Vec::with_capacity(manifest.emoji.len());
+    for entry in manifest.emoji {
+        let file = zip
+            .by_name(&entry.file)
+            .map_err(|_| format!("{} is listed in the manifest but missing", entry.file))?;
+        // The header's size can lie; never read past the limit either way
+        if file.size() > max_file_size {
+            return Err(format!("{} is too large", entry.file));
+        }
+        let mut bytes = Vec::new();
+        file.take(max_file_size + 1)
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("Read error in {}: {e}", entry.file))?;
+        if bytes.len() as u64 > max_file_size {
+            return Err(format!("{} is too large", entry.file));
+        }
+        emoji.push(PackEmoji {
+            extension: entry.file.rsplit_once('.').map(|(_, ext)| ext.to_string()).unwrap_or_default(),
+            name: entry.name,
+            animated: entry.animated,
+            data: bytes,
+        });
+    }
+    Ok(emoji)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_round_trip() {
+        let emoji = vec![
+            PackEmoji { name: "wave".into(), extension: "gif".into(), animated: true, data: b"GIF89a...".to_vec() },
+            PackEmoji { name: "ok".into(), extension: "png".into(), animated: false, data: vec![7; 100] },
+        ];
+        let zip = write("test", &emoji).unwrap();
+
+        let read_back = read(&zip, 1024).unwrap();
+        assert_eq!(read_back.len(), 2);
+        assert_eq!(read_back[0].name, "wave");
+        assert!(read_back[0].animated);
+        assert_eq!(read_back[1].data, vec![7; 100]);
+
+        assert!(read(&zip, 50).is_err());
+        assert!(read(b"not a zip", 1024).is_err());
+    }
+}
@@ -0,0 +1,43 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "applications")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub avatar_url: Option<String>,
+    pub owner_id: String,
+    #[serde(skip_serializing)]
+    pub client_secret: String,
+    pub redirect_uris: String, // newline-separated
+    pub permissions: i64,
+    pub created_at: String,
+}
+
+impl Model {
+    /// Registered redirect URIs, one per line in the DB
+    pub fn redirect_uri_list(&self) -> Vec<&str> {
+        self.redirect_uris
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .collect()
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::bot::Entity")]
+    Bot,
+}
+
+impl Related<super::bot::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Bot.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -22,6 +22,9 @@ pub struct Model {
     pub category_id: Option<String>,
     #[serde(default)]
     pub plugin_url: Option<String>,
+    /// Announcement channels only: federation peers may follow it
+    #[serde(default)]
+    pub remote_follows: bool,
 }
 
 fn default_channel_type() -> String {
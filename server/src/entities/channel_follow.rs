@@ -0,0 +1,36 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "channel_follows")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+    /// The followed announcement channel
+    pub source_channel_id: String,
+    /// Local follower channel (or `peer_id` is set)
+    pub target_channel_id: Option<String>,
+    /// Federation peer following with its `remote_channel_id`
+    pub peer_id: Option<String>,
+    pub remote_channel_id: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::federation_peer::Entity",
+        from = "Column::PeerId",
+        to = "super::federation_peer::Column::Id"
+    )]
+    FederationPeer,
+}
+
+impl Related<super::federation_peer::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::FederationPeer.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -11,6 +11,9 @@ pub struct Model {
     pub upload_id: String,
     pub user_id: String,
     pub created_at: String,
+    /// GIF or animated WebP
+    #[serde(default)]
+    pub animated: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -10,6 +10,13 @@ pub struct Model {
     pub peer_id: String,
     pub remote_channel_id: String,
     pub created_at: String,
+    /// `link` relays both ways; `follow` only receives the remote channel's announcements
+    #[serde(default = "default_mode")]
+    pub mode: String,
+}
+
+fn default_mode() -> String {
+    "link".to_string()
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -0,0 +1,34 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "forum_posts")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+    /// The forum channel
+    pub channel_id: String,
+    pub author_id: Option<String>,
+    pub title: String,
+    /// Listed before other posts
+    pub pinned: bool,
+    /// Only moderators can reply
+    pub locked: bool,
+    pub created_at: String,
+    /// Time of the latest reply
+    pub last_activity_at: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::forum_post_tag::Entity")]
+    ForumPostTag,
+}
+
+impl Related<super::forum_post_tag::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ForumPostTag.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
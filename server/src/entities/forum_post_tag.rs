@@ -0,0 +1,29 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "forum_post_tags")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub post_id: String,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub tag_id: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::forum_post::Entity",
+        from = "Column::PostId",
+        to = "super::forum_post::Column::Id"
+    )]
+    ForumPost,
+}
+
+impl Related<super::forum_post::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ForumPost.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
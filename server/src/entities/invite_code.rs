@@ -11,6 +11,22 @@ pub struct Model {
     pub max_uses: Option<i64>,
     #[serde(default = "default_server_id")]
     pub server_id: String,
+    /// NULL = never expires
+    #[serde(default)]
+    pub expires_at: Option<String>,
+    #[serde(default)]
+    pub created_by: Option<String>,
+    /// Channel the invite points to
+    #[serde(default)]
+    pub channel_id: Option<String>,
+    /// Members who join through it are removed on disconnect unless given a role
+    #[serde(default)]
+    pub temporary: bool,
+    /// The server's custom code
+    #[serde(default)]
+    pub vanity: bool,
+    #[serde(default)]
+    pub joins: i64,
 }
 
 fn default_server_id() -> String {
@@ -18,6 +34,19 @@ fn default_server_id() -> String {
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::CreatedBy",
+        to = "super::user::Column::Id"
+    )]
+    Creator,
+}
+
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Creator.def()
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
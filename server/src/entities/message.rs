@@ -17,6 +17,15 @@ pub struct Model {
     pub reply_to: Option<String>,
     pub pinned_at: Option<String>,
     pub pinned_by: Option<String>,
+    /// Sticker sent as the whole message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sticker_id: Option<String>,
+    /// Forum post this message belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_id: Option<String>,
+    /// When the announcement was cross-posted to followers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published_at: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
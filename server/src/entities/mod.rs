@@ -1,17 +1,32 @@
+pub mod application;
 pub mod audit_log;
 pub mod ban;
+pub mod blob;
 pub mod bot;
 pub mod channel;
+pub mod channel_follow;
 pub mod custom_emoji;
+pub mod event_rsvp;
 pub mod federated_channel;
 pub mod federation_peer;
+pub mod forum_post;
+pub mod forum_post_tag;
+pub mod forum_tag;
 pub mod invite_code;
 pub mod message;
+pub mod message_attachment;
+pub mod oauth_code;
 pub mod reaction;
 pub mod role;
+pub mod scheduled_event;
 pub mod server;
 pub mod server_member;
+pub mod server_screening;
+pub mod server_template;
+pub mod sticker;
+pub mod sticker_pack;
 pub mod upload;
+pub mod upload_session;
 pub mod user;
 pub mod user_key;
 pub mod user_role;
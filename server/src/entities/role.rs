@@ -13,6 +13,15 @@ pub struct Model {
     pub created_at: String,
     #[serde(default = "default_server_id")]
     pub server_id: String,
+    /// Owned by an installed bot application; not editable by hand
+    #[serde(default)]
+    pub managed: bool,
+    /// Upload size limit for members with this role, in bytes
+    #[serde(default)]
+    pub max_upload_size: Option<i64>,
+    /// Storage quota for members with this role, in bytes
+    #[serde(default)]
+    pub storage_quota: Option<i64>,
 }
 
 fn default_server_id() -> String {
@@ -0,0 +1,36 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "scheduled_events")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+    pub server_id: String,
+    pub creator_id: Option<String>,
+    pub title: String,
+    pub description: String,
+    pub starts_at: String,
+    pub ends_at: Option<String>,
+    /// Voice channel the event takes place in
+    pub channel_id: Option<String>,
+    /// Where it happens, for events outside the server
+    pub location: Option<String>,
+    /// scheduled | active | completed | cancelled
+    pub status: String,
+    pub created_at: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::event_rsvp::Entity")]
+    EventRsvp,
+}
+
+impl Related<super::event_rsvp::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::EventRsvp.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -13,6 +13,21 @@ pub struct Model {
     pub join_sound_url: Option<String>,
     pub leave_sound_url: Option<String>,
     pub sound_chance: i64,
+    /// Comma-separated MIME types members may upload here (NULL = all supported)
+    pub allowed_upload_types: Option<String>,
+    /// Upload size limit in bytes (NULL = instance default)
+    pub max_upload_size: Option<i64>,
+    /// Total storage quota in bytes (NULL = instance default)
+    pub storage_quota: Option<i64>,
+    /// Channel receiving automated welcome messages
+    pub system_channel_id: Option<String>,
+    /// Welcome text; `{user}` and `{server}` are replaced
+    pub welcome_message: Option<String>,
+    /// Listed in the public server directory
+    #[serde(default)]
+    pub discoverable: bool,
+    /// Comma-separated lowercase discovery tags
+    pub tags: Option<String>,
     pub created_at: String,
     pub updated_at: Option<String>,
 }
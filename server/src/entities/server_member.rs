@@ -9,6 +9,20 @@ pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub user_id: String,
     pub joined_at: String,
+    /// Joined through a temporary invite
+    #[serde(default)]
+    pub temporary: bool,
+    /// Shown instead of the display name in this server
+    pub nickname: Option<String>,
+    /// Hasn't passed screening or been approved yet; can't post
+    #[serde(default)]
+    pub pending: bool,
+    /// When the member accepted the rules
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub screened_at: Option<String>,
+    /// Serialized `Vec<ScreeningAnswer>`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub screening_answers: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -7,10 +7,16 @@ pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub id: String,
     pub user_id: String,
+    /// Server the file was shared in (None = avatars, emoji, DMs)
+    pub server_id: Option<String>,
     pub filename: String,
     pub mime_type: String,
     pub size: i64,
     pub created_at: String,
+    /// Content hash of the stored bytes (None = legacy file at ./uploads/{id}.{ext})
+    pub blob_hash: Option<String>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
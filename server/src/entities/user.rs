@@ -14,6 +14,17 @@ pub struct Model {
     pub avatar_url: Option<String>,
     pub created_at: String,
     pub timeout_until: Option<String>, // New field for timeout expiration
+    /// Status the user picked (online, idle, dnd, invisible)
+    #[serde(default = "default_status")]
+    pub status: String,
+    #[serde(default)]
+    pub custom_status: Option<String>,
+    #[serde(default)]
+    pub custom_status_expires_at: Option<String>,
+}
+
+fn default_status() -> String {
+    "online".to_string()
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
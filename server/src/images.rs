@@ -0,0 +1,391 @@
+use std::io::Cursor;
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPDecoder;
+use image::metadata::Orientation;
+use image::{AnimationDecoder, DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits};
+
+/// Longest-side sizes generated for `GET /api/uploads/{id}?size=`
+pub const THUMBNAIL_SIZES: &[u32] = &[64, 256, 1024];
+/// Avatars are cropped to a square of this size
+pub const AVATAR_SIZE: u32 = 256;
+/// Images larger than this on either side are rejected
+const MAX_DIMENSION: u32 = 12_000;
+/// Upper bound on decoder allocations (a 12k × 12k RGBA image is ~550MB)
+const MAX_DECODE_ALLOC: u64 = 512 * 1024 * 1024;
+const JPEG_QUALITY: u8 = 85;
+/// Most frames an animated emoji or sticker may have
+pub const MAX_ANIMATION_FRAMES: usize = 200;
+
+/// An image ready to store: metadata stripped, plus any generated thumbnails
+pub struct ProcessedImage {
+    pub data: Vec<u8>,
+    pub mime_type: String,
+    pub width: u32,
+    pub height: u32,
+    /// (longest side, encoded bytes), only for sizes smaller than the original
+    pub thumbnails: Vec<(u32, Vec<u8>)>,
+}
+
+fn image_format(mime: &str) -> Option<ImageFormat> {
+    match mime {
+        "image/png" => Some(ImageFormat::Png),
+        "image/jpeg" => Some(ImageFormat::Jpeg),
+        "image/gif" => Some(ImageFormat::Gif),
+        "image/webp" => Some(ImageFormat::WebP),
+        _ => None,
+    }
+}
+
+pub fn is_image(mime: &str) -> bool {
+    image_format(mime).is_some()
+}
+
+/// Content type of a generated thumbnail (PNG when the source had alpha, JPEG otherwise)
+pub fn thumbnail_mime(data: &[u8]) -> &'static str {
+    if data.starts_with(b"\x89PNG") {
+        "image/png"
+    } else {
+        "image/jpeg"
+    }
+}
+
+/// Decode the first frame, applying the EXIF orientation
+fn decode(data: &[u8], format: ImageFormat) -> Result<(DynamicImage, Orientation), String> {
+    let mut reader = ImageReader::with_format(Cursor::new(data), format);
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOC);
+    reader.limits(limits);
+
+    let mut decoder = reader.into_decoder().map_err(|e| format!("Invalid image: {e}"))?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut img = DynamicImage::from_decoder(decoder).map_err(|e| format!("Invalid image: {e}"))?;
+    img.apply_orientation(orientation);
+    Ok((img, orientation))
+}
+
+/// Content type `encode_flat` produces for `img`
+fn flat_mime(img: &DynamicImage) -> &'static str {
+    if img.color().has_alpha() {
+        "image/png"
+    } else {
+        "image/jpeg"
+    }
+}
+
+/// Encode as PNG when there is transparency to keep, JPEG otherwise
+fn encode_flat(img: &DynamicImage) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    if img.color().has_alpha() {
+        img.write_to(&mut Cursor::new(&mut out), ImageFormat::Png)
+            .map_err(|e| format!("Encode error: {e}"))?;
+    } else {
+        let encoder = JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY);
+        DynamicImage::ImageRgb8(img.to_rgb8())
+            .write_with_encoder(encoder)
+            .map_err(|e| format!("Encode error: {e}"))?;
+    }
+    Ok(out)
+}
+
+/// Validate an uploaded image, strip its metadata and render thumbnails
+pub fn process_upload(data: &[u8], mime: &str) -> Result<ProcessedImage, String> {
+    let format = image_format(mime).ok_or("Not an image")?;
+    let (img, orientation) = decode(data, format)?;
+
+    // Rotated JPEGs are re-encoded upright, since dropping EXIF drops the rotation.
+    // Everything else keeps its pixels (and animation) untouched.
+    let (data, mime_type) = if format == ImageFormat::Jpeg && orientation != Orientation::NoTransforms {
+        (encode_flat(&img)?, flat_mime(&img).to_string())
+    } else {
+        (strip_metadata(data, format)?, mime.to_string())
+    };
+
+    let longest = img.width().max(img.height());
+    let thumbnails = THUMBNAIL_SIZES
+        .iter()
+        .filter(|&&size| size < longest)
+        .map(|&size| encode_flat(&img.thumbnail(size, size)).map(|bytes| (size, bytes)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(ProcessedImage {
+        data,
+        mime_type,
+        width: img.width(),
+        height: img.height(),
+        thumbnails,
+    })
+}
+
+/// Crop to a centered square and scale to `AVATAR_SIZE` (animated avatars keep their first frame)
+pub fn process_avatar(data: &[u8], mime: &str) -> Result<ProcessedImage, String> {
+    let format = image_format(mime).ok_or("Not an image")?;
+    let (img, _) = decode(data, format)?;
+    let avatar = img.resize_to_fill(AVATAR_SIZE, AVATAR_SIZE, image::imageops::FilterType::Lanczos3);
+    Ok(ProcessedImage {
+        data: encode_flat(&avatar)?,
+        mime_type: flat_mime(&avatar).to_string(),
+        width: avatar.width(),
+        height: avatar.height(),
+        thumbnails: Vec::new(),
+    })
+}
+
+/// Frames in a GIF or WebP, decoding each so broken animations are rejected
+fn frame_count(data: &[u8], format: ImageFormat) -> Result<usize, String> {
+    let invalid = |e: image::ImageError| format!("Invalid image: {e}");
+    let frames = match format {
+        ImageFormat::Gif => GifDecoder::new(Cursor::new(data)).map_err(invalid)?.into_frames(),
+        ImageFormat::WebP => {
+            let decoder = WebPDecoder::new(Cursor::new(data)).map_err(invalid)?;
+            if !decoder.has_animation() {
+                return Ok(1);
+            }
+            decoder.into_frames()
+        }
+        _ => return Ok(1),
+    };
+    let mut count = 0;
+    for frame in frames {
+        frame.map_err(invalid)?;
+        count += 1;
+        if count > MAX_ANIMATION_FRAMES {
+            return Err(format!("Too many frames (max {MAX_ANIMATION_FRAMES})"));
+        }
+    }
+    Ok(count)
+}
+
+/// Validate an emoji or sticker: at most `max_side` pixels per side and
+/// `MAX_ANIMATION_FRAMES` frames, metadata stripped and animation kept.
+/// Returns the image and whether it is animated.
+pub fn process_emoji(data: &[u8], mime: &str, max_side: u32) -> Result<(ProcessedImage, bool), String> {
+    let format = image_format(mime).ok_or("Not an image")?;
+    let (img, _) = decode(data, format)?;
+    if img.width() > max_side || img.height() > max_side {
+        return Err(format!("Image must be at most {max_side}x{max_side} pixels"));
+    }
+    let animated = frame_count(data, format)? > 1;
+    let processed = ProcessedImage {
+        data: strip_metadata(data, format)?,
+        mime_type: mime.to_string(),
+        width: img.width(),
+        height: img.height(),
+        thumbnails: Vec::new(),
+    };
+    Ok((processed, animated))
+}
+
+/// Drop EXIF/XMP/text metadata at the container level, without re-encoding pixels
+fn strip_metadata(data: &[u8], format: ImageFormat) -> Result<Vec<u8>, String> {
+    let stripped = match format {
+        ImageFormat::Jpeg => strip_jpeg(data),
+        ImageFormat::Png => strip_png(data),
+        ImageFormat::WebP => strip_webp(data),
+        ImageFormat::Gif => strip_gif(data),
+        _ => None,
+    };
+    stripped.ok_or_else(|| "Invalid image: malformed container".to_string())
+}
+
+fn strip_jpeg(data: &[u8]) -> Option<Vec<u8>> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut out = vec![0xFF, 0xD8];
+    let mut pos = 2;
+    loop {
+        if *data.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(pos + 1)?;
+        match marker {
+            // Fill byte
+            0xFF => pos += 1,
+            // Start of scan: entropy-coded data and the rest of the file follow verbatim
+            0xDA => {
+                out.extend_from_slice(&data[pos..]);
+                return Some(out);
+            }
+            // Markers without a length
+            0x01 | 0xD0..=0xD7 => {
+                out.extend_from_slice(&data[pos..pos + 2]);
+                pos += 2;
+            }
+            0xD9 => {
+                out.extend_from_slice(&data[pos..pos + 2]);
+                return Some(out);
+            }
+            _ => {
+                let len = u16::from_be_bytes([*data.get(pos + 2)?, *data.get(pos + 3)?]) as usize;
+                let end = pos + 2 + len;
+                let segment = data.get(pos..end)?;
+                // Keep JFIF (APP0), ICC profiles (APP2) and Adobe color info (APP14);
+                // drop EXIF/XMP (APP1), IPTC (APP13), other APPn and comments
+                let drop = matches!(marker, 0xE1 | 0xE3..=0xED | 0xEF | 0xFE);
+                if !drop {
+                    out.extend_from_slice(segment);
+                }
+                pos = end;
+            }
+        }
+    }
+}
+
+fn strip_png(data: &[u8]) -> Option<Vec<u8>> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    if !data.starts_with(SIGNATURE) {
+        return None;
+    }
+    let mut out = SIGNATURE.to_vec();
+    let mut pos = SIGNATURE.len();
+    while pos < data.len() {
+        let len = u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?) as usize;
+        let kind = data.get(pos + 4..pos + 8)?;
+        let end = pos + 12 + len;
+        let chunk = data.get(pos..end)?;
+        if !matches!(kind, b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt" | b"tIME") {
+            out.extend_from_slice(chunk);
+        }
+        pos = end;
+        if kind == b"IEND" {
+            break;
+        }
+    }
+    Some(out)
+}
+
+fn strip_webp(data: &[u8]) -> Option<Vec<u8>> {
+    if data.get(0..4)? != b"RIFF" || data.get(8..12)? != b"WEBP" {
+        return None;
+    }
+    let mut out = data[0..12].to_vec();
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let kind = &data[pos..pos + 4];
+        let len = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().ok()?) as usize;
+        // Chunks are padded to an even size
+        let end = (pos + 8 + len + (len & 1)).min(data.len());
+        let chunk = data.get(pos..end)?;
+        match kind {
+            b"EXIF" | b"XMP " => {}
+            b"VP8X" => {
+                let start = out.len();
+                out.extend_from_slice(chunk);
+                // Clear the EXIF (0x08) and XMP (0x04) presence flags
+                *out.get_mut(start + 8)? &= !0x0C;
+            }
+            _ => out.extend_from_slice(chunk),
+        }
+        pos = end;
+    }
+    let riff_size = u32::try_from(out.len() - 8).ok()?;
+    out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Some(out)
+}
+
+fn strip_gif(data: &[u8]) -> Option<Vec<u8>> {
+    if !data.starts_with(b"GIF8") {
+        return None;
+    }
+
+    /// Length of a run of sub-blocks starting at `pos`, including the terminator
+    fn sub_blocks(data: &[u8], mut pos: usize) -> Option<usize> {
+        let start = pos;
+        loop {
+            let size = *data.get(pos)? as usize;
+            pos += 1 + size;
+            if size == 0 {
+                return Some(pos - start);
+            }
+        }
+    }
+
+    let color_table = |flags: u8| if flags & 0x80 != 0 { 3 << ((flags & 0x07) + 1) } else { 0 };
+
+    let screen_flags = *data.get(10)?;
+    let mut pos = 13 + color_table(screen_flags);
+    let mut out = data.get(..pos)?.to_vec();
+
+    loop {
+        match *data.get(pos)? {
+            // Trailer
+            0x3B => {
+                out.push(0x3B);
+                return Some(out);
+            }
+            // Extension: keep graphic control and animation looping, drop comments and XMP
+            0x21 => {
+                let label = *data.get(pos + 1)?;
+                let end = pos + 2 + sub_blocks(data, pos + 2)?;
+                let keep = match label {
+                    0xFE => false,
+                    0xFF => matches!(data.get(pos + 3..pos + 14), Some(b"NETSCAPE2.0") | Some(b"ANIMEXTS1.0")),
+                    _ => true,
+                };
+                if keep {
+                    out.extend_from_slice(data.get(pos..end)?);
+                }
+                pos = end;
+            }
+            // Image descriptor, optional local color table, LZW code size, image data
+            0x2C => {
+                let flags = *data.get(pos + 9)?;
+                let data_start = pos + 10 + color_table(flags) + 1;
+                let end = data_start + sub_blocks(data, data_start)?;
+                out.extend_from_slice(data.get(pos..end)?);
+                pos = end;
+            }
+            _ => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jpeg_exif_is_stripped() {
+        let img = DynamicImage::new_rgb8(8, 8);
+        let mut jpeg = Vec::new();
+        img.write_to(&mut Cursor::new(&mut jpeg), ImageFormat::Jpeg).unwrap();
+
+        // Splice an APP1 Exif segment with a fake GPS tag right after SOI
+        let payload = b"Exif\0\0GPS-SECRET";
+        let mut with_exif = jpeg[..2].to_vec();
+        with_exif.extend_from_slice(&[0xFF, 0xE1]);
+        with_exif.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+        with_exif.extend_from_slice(payload);
+        with_exif.extend_from_slice(&jpeg[2..]);
+
+        let processed = process_upload(&with_exif, "image/jpeg").unwrap();
+        assert!(!processed.data.windows(10).any(|w| w == b"GPS-SECRET"));
+        assert_eq!(processed.data, jpeg);
+        assert_eq!((processed.width, processed.height), (8, 8));
+    }
+
+    #[test]
+    fn animated_gifs_are_detected() {
+        use image::codecs::gif::GifEncoder;
+        use image::{Frame, RgbaImage};
+
+        let mut gif = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut gif);
+            let frames = (0..3).map(|i| Frame::new(RgbaImage::from_pixel(16, 16, image::Rgba([i * 80, 0, 0, 255]))));
+            encoder.encode_frames(frames).unwrap();
+        }
+        let (processed, animated) = process_emoji(&gif, "image/gif", 64).unwrap();
+        assert!(animated);
+        assert_eq!((processed.width, processed.height), (16, 16));
+
+        let mut png = Vec::new();
+        DynamicImage::new_rgba8(16, 16).write_to(&mut Cursor::new(&mut png), ImageFormat::Png).unwrap();
+        assert!(!process_emoji(&png, "image/png", 64).unwrap().1);
+        assert!(process_emoji(&png, "image/png", 8).is_err());
+    }
+}
@@ -1,19 +1,32 @@
+mod attachments;
+mod blobs;
 mod db;
+mod emoji_pack;
 mod entities;
+mod images;
 mod models;
 mod permissions;
+mod presence;
+mod pubsub;
+mod quotas;
 mod routes;
+mod session;
+mod sniff;
 mod state;
+mod storage;
+mod templates;
 mod token;
+mod wire;
 mod ws;
 
 use axum::{
     extract::{DefaultBodyLimit, Request},
     middleware::{self, Next},
     response::{IntoResponse, Response},
-    routing::{delete, get, post, put},
+    routing::{delete, get, patch, post, put},
     Router,
 };
+use std::sync::Arc;
 use clap::Parser;
 use tower_http::cors::CorsLayer;
 
@@ -41,6 +54,66 @@ struct Args {
     /// External port for invite tokens (e.g. 443 for HTTPS)
     #[arg(long, env = "EXTERNAL_PORT")]
     external_port: Option<u16>,
+
+    /// Real-time fan-out between instances: memory (single instance) or postgres
+    #[arg(long, env = "PUBSUB", default_value = "memory")]
+    pubsub: String,
+
+    /// Postgres URL for LISTEN/NOTIFY (defaults to the database URL)
+    #[arg(long, env = "PUBSUB_URL")]
+    pubsub_url: Option<String>,
+
+    /// Upload storage backend: local or s3
+    #[arg(long, env = "STORAGE", default_value = "local")]
+    storage: String,
+
+    /// Directory for local upload storage
+    #[arg(long, env = "STORAGE_PATH", default_value = "./uploads")]
+    storage_path: String,
+
+    /// S3 bucket for uploads (required with --storage s3)
+    #[arg(long, env = "S3_BUCKET")]
+    s3_bucket: Option<String>,
+
+    /// S3 region
+    #[arg(long, env = "S3_REGION", default_value = "us-east-1")]
+    s3_region: String,
+
+    /// Custom S3 endpoint for S3-compatible services (e.g. http://localhost:9000 for MinIO)
+    #[arg(long, env = "S3_ENDPOINT")]
+    s3_endpoint: Option<String>,
+
+    /// S3 access key (falls back to AWS_ACCESS_KEY_ID)
+    #[arg(long, env = "S3_ACCESS_KEY_ID")]
+    s3_access_key_id: Option<String>,
+
+    /// S3 secret key (falls back to AWS_SECRET_ACCESS_KEY)
+    #[arg(long, env = "S3_SECRET_ACCESS_KEY", hide_env_values = true)]
+    s3_secret_access_key: Option<String>,
+
+    /// Redirect upload downloads to presigned S3 URLs instead of proxying them
+    #[arg(long, env = "S3_PRESIGN")]
+    s3_presign: bool,
+
+    /// Default upload size limit in MB (servers and roles can override it)
+    #[arg(long, env = "MAX_UPLOAD_MB", default_value_t = 100)]
+    max_upload_mb: u64,
+
+    /// Directory for resumable uploads in progress
+    #[arg(long, env = "UPLOAD_PARTIAL_PATH", default_value = "./uploads/partial")]
+    upload_partial_path: String,
+
+    /// Default storage quota per user in MB, 0 = unlimited (roles can override it)
+    #[arg(long, env = "USER_QUOTA_MB", default_value_t = 1024)]
+    user_quota_mb: u64,
+
+    /// Default storage quota per server in MB, 0 = unlimited (instance admins can override it)
+    #[arg(long, env = "SERVER_QUOTA_MB", default_value_t = 10240)]
+    server_quota_mb: u64,
+
+    /// Hours an upload may stay unreferenced (e.g. before its message is sent) before GC removes it
+    #[arg(long, env = "ORPHAN_GRACE_HOURS", default_value_t = 24)]
+    orphan_grace_hours: i64,
 }
 
 #[tokio::main]
@@ -76,8 +149,25 @@ async fn main() {
         secret
     });
 
-    // Ensure uploads directory exists
-    tokio::fs::create_dir_all("./uploads").await.ok();
+    let storage: Arc<dyn storage::Storage> = match args.storage.as_str() {
+        "local" => {
+            tokio::fs::create_dir_all(&args.storage_path).await.ok();
+            Arc::new(storage::LocalStorage::new(&args.storage_path))
+        }
+        "s3" => {
+            let config = storage::S3Config {
+                bucket: args.s3_bucket.clone().expect("--s3-bucket is required with --storage s3"),
+                region: args.s3_region.clone(),
+                endpoint: args.s3_endpoint.clone(),
+                access_key_id: args.s3_access_key_id.clone(),
+                secret_access_key: args.s3_secret_access_key.clone(),
+                presign: args.s3_presign,
+            };
+            tracing::info!("Upload storage: S3 bucket {}", config.bucket);
+            Arc::new(storage::S3Storage::new(config).expect("Failed to configure S3 storage"))
+        }
+        other => panic!("Unknown storage backend '{other}' (expected local or s3)"),
+    };
 
     // Build database URL: prefer --db-url, fall back to --db-path (SQLite)
     let db_url = args.db_url.unwrap_or_else(|| format!("sqlite:{}?mode=rwc", args.db_path));
@@ -96,6 +186,7 @@ async fn main() {
             uses: Set(0),
             max_uses: Set(None),
             server_id: Set("default".to_string()),
+            ..Default::default()
         };
         let _ = invite_code::Entity::insert(seed)
             .on_conflict(
@@ -147,13 +238,33 @@ async fn main() {
     };
     let encoded_token = token::encode_token(&conn_token);
 
+    tokio::fs::create_dir_all(&args.upload_partial_path).await.ok();
+
+    let pubsub_url = args.pubsub_url.clone().unwrap_or_else(|| db_url.clone());
+    let (pubsub, remote_events) = pubsub::connect(&args.pubsub, &pubsub_url).await;
+
     let state = AppState::new(
         db.clone(),
         jwt_secret,
         args.external_host,
         args.external_port.unwrap_or(port),
+        pubsub,
+        storage,
+        storage::UploadConfig {
+            max_size: args.max_upload_mb * 1024 * 1024,
+            partial_dir: args.upload_partial_path.clone().into(),
+            user_quota: (args.user_quota_mb > 0).then(|| args.user_quota_mb * 1024 * 1024),
+            server_quota: (args.server_quota_mb > 0).then(|| args.server_quota_mb * 1024 * 1024),
+            orphan_grace: chrono::Duration::hours(args.orphan_grace_hours.max(1)),
+        },
     );
 
+    if let Some(remote_events) = remote_events {
+        tokio::spawn(pubsub::apply_remote_events(state.clone(), remote_events));
+        // Ask the other instances for the voice state we missed while down
+        state.pubsub.publish(&pubsub::BusEvent::InstanceStarted);
+    }
+
     // --- Setup Key: generate if no users exist ---
     {
         use sea_orm::{EntityTrait, PaginatorTrait};
@@ -195,23 +306,69 @@ async fn main() {
         .route("/api/me", get(routes::auth::get_me))
         .route("/api/setup-status", get(routes::auth::setup_status))
         // Uploads
-        .route("/api/upload", post(routes::uploads::upload_file))
+        .route(
+            "/api/upload",
+            // Multipart overhead on top of the largest single-request file
+            post(routes::uploads::upload_file)
+                .layer(DefaultBodyLimit::max(routes::uploads::MAX_FILE_SIZE + 64 * 1024)),
+        )
         .route("/api/uploads/{id}", get(routes::uploads::serve_upload))
+        .route("/api/uploads/{id}/url", get(routes::uploads::upload_url))
+        .route("/api/uploads/sessions", post(routes::upload_sessions::create_session))
+        .route(
+            "/api/uploads/sessions/{id}",
+            get(routes::upload_sessions::get_session)
+                .patch(routes::upload_sessions::upload_chunk)
+                .delete(routes::upload_sessions::cancel_session),
+        )
+        .route("/api/uploads/sessions/{id}/finalize", post(routes::upload_sessions::finalize_session))
         .route("/api/uploads/emoji/{name}", get(routes::emoji::serve_emoji_by_name))
-        .route("/api/me/avatar", put(routes::uploads::upload_avatar))
+        .route("/api/admin/storage/uploaders", get(routes::quotas::list_uploaders))
+        .route("/api/admin/storage/uploaders/{user_id}", delete(routes::quotas::purge_uploader))
+        .route("/api/admin/storage/gc", post(routes::quotas::run_gc))
+        .route(
+            "/api/me/avatar",
+            put(routes::uploads::upload_avatar)
+                .layer(DefaultBodyLimit::max(routes::uploads::MAX_AVATAR_SIZE + 64 * 1024)),
+        )
+        .route("/api/me/presence", put(routes::presence::update_presence))
+        .route("/api/users/{user_id}/presence", get(routes::presence::get_presence))
         // Emoji
         .route("/api/emoji", get(routes::emoji::list_emoji))
         .route("/api/emoji", post(routes::emoji::create_emoji))
+        .route("/api/emoji/export", get(routes::emoji::export_emoji))
+        .route(
+            "/api/emoji/import",
+            post(routes::emoji::import_emoji).layer(DefaultBodyLimit::max(routes::emoji::MAX_PACK_SIZE)),
+        )
         .route("/api/emoji/{id}", delete(routes::emoji::delete_emoji))
+        // Stickers
+        .route("/api/sticker-packs", get(routes::stickers::list_packs))
+        .route("/api/sticker-packs", post(routes::stickers::create_pack))
+        .route("/api/sticker-packs/{id}", delete(routes::stickers::delete_pack))
+        .route("/api/sticker-packs/{id}/stickers", post(routes::stickers::add_sticker))
+        .route("/api/stickers/{id}", delete(routes::stickers::delete_sticker))
         // REST API
         .route("/api/channels", get(routes::channels::list_channels))
         .route("/api/channels", post(routes::channels::create_channel))
         .route("/api/channels/reorder", put(routes::channels::reorder_channels))
         .route("/api/channels/{channel_id}/messages", get(routes::messages::get_messages))
+        .route("/api/channels/{channel_id}/posts", get(routes::forums::list_posts))
+        .route("/api/channels/{channel_id}/posts", post(routes::forums::create_post))
+        .route("/api/channels/{channel_id}/posts/{post_id}", get(routes::forums::get_post))
+        .route("/api/channels/{channel_id}/posts/{post_id}", patch(routes::forums::update_post))
+        .route("/api/channels/{channel_id}/posts/{post_id}", delete(routes::forums::delete_post))
+        .route("/api/channels/{channel_id}/followers", get(routes::announcements::list_followers))
+        .route("/api/channels/{channel_id}/followers", post(routes::announcements::follow_channel))
+        .route("/api/channels/{channel_id}/followers/{follow_id}", delete(routes::announcements::unfollow_channel))
+        .route("/api/channels/{channel_id}/remote-follows", put(routes::announcements::set_remote_follows))
+        .route("/api/channels/{channel_id}/tags", get(routes::forums::list_tags))
+        .route("/api/channels/{channel_id}/tags", put(routes::forums::update_tags))
         .route("/api/channels/{channel_id}/overrides", get(routes::channels::get_channel_overrides))
         .route("/api/channels/{channel_id}/overrides/{target_id}", put(routes::channels::update_channel_override))
         .route("/api/channels/{channel_id}/overrides/{target_id}", delete(routes::channels::delete_channel_override))
         .route("/api/invites", post(routes::invite::create_invite))
+        .route("/api/invites/{code}/accept", post(routes::invite::accept_invite))
         .route("/api/join", post(routes::invite::join_server))
         .route("/api/join-direct", post(routes::invite::join_direct))
         .route("/api/server", get(routes::server_info::get_server_info))
@@ -248,6 +405,15 @@ async fn main() {
         .route("/api/bots/{bot_id}", delete(routes::bots::delete_bot))
         .route("/api/bots/{bot_id}/regenerate-token", post(routes::bots::regenerate_bot_token))
         .route("/api/bots/message", post(routes::bots::bot_send_message))
+        // Bot applications (OAuth2 install flow)
+        .route("/api/applications", get(routes::applications::list_applications))
+        .route("/api/applications", post(routes::applications::create_application))
+        .route("/api/applications/{app_id}", put(routes::applications::update_application))
+        .route("/api/applications/{app_id}", delete(routes::applications::delete_application))
+        .route("/api/applications/{app_id}/reset-secret", post(routes::applications::reset_client_secret))
+        .route("/api/oauth2/authorize", get(routes::oauth2::get_authorize))
+        .route("/api/oauth2/authorize", post(routes::oauth2::approve_authorize))
+        .route("/api/oauth2/token", post(routes::oauth2::exchange_token))
         // Webhooks
         .route("/api/webhooks", get(routes::webhooks::list_webhooks))
         .route("/api/webhooks", post(routes::webhooks::create_webhook))
@@ -260,6 +426,7 @@ async fn main() {
         .route("/api/channels/{channel_id}/encrypted", put(routes::encryption::set_channel_encrypted))
         // Messages
         .route("/api/messages/{message_id}", delete(routes::messages::delete_message))
+        .route("/api/messages/{message_id}/publish", post(routes::announcements::publish_message))
         .route("/api/messages/{message_id}/pin", post(routes::messages::pin_message))
         .route("/api/messages/{message_id}/pin", delete(routes::messages::unpin_message))
         .route(
@@ -276,6 +443,9 @@ async fn main() {
         .route("/api/federation/peers/{peer_id}/activate", post(routes::federation::activate_peer))
         .route("/api/federation/channels", post(routes::federation::link_channel))
         .route("/api/federation/channels/{link_id}", delete(routes::federation::unlink_channel))
+        .route("/api/federation/follows", post(routes::federation::follow_remote_channel))
+        .route("/api/federation/follow", post(routes::federation::receive_follow))
+        .route("/api/federation/follow", delete(routes::federation::receive_unfollow))
         .route("/api/federation/message", post(routes::federation::receive_federated_message)
             .layer(DefaultBodyLimit::max(65_536))) // 64KB limit for federation messages
         // Multi-Server (Guilds)
@@ -286,7 +456,29 @@ async fn main() {
         .route("/api/servers/{server_id}", delete(routes::servers::delete_server))
         .route("/api/servers/{server_id}/join", post(routes::servers::join_server_by_id))
         .route("/api/servers/{server_id}/leave", post(routes::servers::leave_server))
+        .route("/api/servers/{server_id}/transfer", post(routes::servers::transfer_ownership))
+        .route("/api/servers/{server_id}/vanity", put(routes::invite::set_vanity_code))
         .route("/api/servers/{server_id}/members", get(routes::servers::list_server_members))
+        .route("/api/servers/{server_id}/members/{user_id}/nickname", put(routes::members::set_nickname))
+        .route("/api/discovery", get(routes::discovery::list_discoverable))
+        .route("/api/discovery/{server_id}/join", post(routes::discovery::join_discoverable))
+        .route("/api/servers/{server_id}/events", get(routes::events::list_events))
+        .route("/api/servers/{server_id}/events", post(routes::events::create_event))
+        .route("/api/servers/{server_id}/events/{event_id}", patch(routes::events::update_event))
+        .route("/api/servers/{server_id}/events/{event_id}", delete(routes::events::delete_event))
+        .route("/api/servers/{server_id}/events/{event_id}/rsvp", put(routes::events::set_rsvp))
+        .route("/api/servers/{server_id}/events/{event_id}/rsvp", delete(routes::events::delete_rsvp))
+        .route("/api/servers/{server_id}/events/{event_id}/rsvps", get(routes::events::list_rsvps))
+        .route("/api/servers/{server_id}/screening", get(routes::onboarding::get_screening))
+        .route("/api/servers/{server_id}/screening", put(routes::onboarding::update_screening))
+        .route("/api/servers/{server_id}/screening", post(routes::onboarding::submit_screening))
+        .route("/api/servers/{server_id}/pending-members", get(routes::onboarding::list_pending_members))
+        .route("/api/servers/{server_id}/pending-members/{user_id}", delete(routes::onboarding::reject_member))
+        .route("/api/servers/{server_id}/pending-members/{user_id}/approve", post(routes::onboarding::approve_member))
+        .route("/api/servers/{server_id}/template", get(routes::templates::get_server_template))
+        .route("/api/servers/{server_id}/template", post(routes::templates::sync_template))
+        .route("/api/servers/{server_id}/template", delete(routes::templates::delete_server_template))
+        .route("/api/templates/{code}", get(routes::templates::get_template))
         // WebSocket
         .route("/ws", get(ws::ws_handler))
         // Middleware
@@ -322,6 +514,92 @@ async fn main() {
         }
     });
 
+    // Auto-idle, custom status expiry and presence/voice heartbeat to other instances
+    let presence_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(30));
+        loop {
+            interval.tick().await;
+            for (user_id, presence) in presence_state.presence.sweep() {
+                presence::broadcast_presence(&presence_state, &user_id, presence).await;
+            }
+            presence::heartbeat(&presence_state).await;
+            pubsub::voice_heartbeat(&presence_state).await;
+        }
+    });
+
+    // Remove uploads nothing references anymore (replaced avatars, deleted emoji,
+    // files never sent), then the blobs no upload row points at
+    let upload_gc_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            match blobs::collect_orphans(&upload_gc_state, upload_gc_state.uploads.orphan_grace, false).await {
+                Ok(report) if report.uploads == 0 && report.files == 0 => {}
+                Ok(report) => tracing::info!(
+                    "Upload GC: removed {} uploads and {} files, reclaimed {} bytes",
+                    report.uploads,
+                    report.files,
+                    report.bytes_reclaimed
+                ),
+                Err(e) => tracing::warn!("Upload GC failed: {e}"),
+            }
+        }
+    });
+
+    // Discard resumable uploads abandoned halfway
+    let upload_session_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            match routes::upload_sessions::expire_sessions(&upload_session_state).await {
+                Ok(0) => {}
+                Ok(n) => tracing::info!("Discarded {n} abandoned upload sessions"),
+                Err(e) => tracing::warn!("Upload session cleanup failed: {e}"),
+            }
+        }
+    });
+
+    // Start scheduled events when they're due and complete finished ones
+    let events_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(30));
+        loop {
+            interval.tick().await;
+            if let Err(e) = routes::events::run_scheduler(&events_state).await {
+                tracing::warn!("Event scheduler failed: {e}");
+            }
+        }
+    });
+
+    // Drop temporary members left behind without a connection. The first pass waits
+    // until other instances have reported who is connected to them
+    let temporary_state = state.clone();
+    tokio::spawn(async move {
+        let period = std::time::Duration::from_secs(300);
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + presence::REMOTE_TTL, period);
+        loop {
+            interval.tick().await;
+            match routes::invite::sweep_temporary_members(&temporary_state).await {
+                Ok(0) => {}
+                Ok(n) => tracing::info!("Dropped temporary memberships of {n} disconnected users"),
+                Err(e) => tracing::warn!("Temporary member sweep failed: {e}"),
+            }
+        }
+    });
+
+    // Expire dropped WebSocket sessions once their resume window has passed
+    let session_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(session::RESUME_WINDOW);
+        loop {
+            interval.tick().await;
+            session_state.sessions.cleanup_expired();
+        }
+    });
+
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
     axum::serve(listener, app).await.unwrap();
 }
@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
 
+use crate::presence::{Presence, PresenceStatus};
+use crate::templates::Template;
+
 // ─── Re-export entity models for backward compatibility ───
 // Route handlers now use entities directly (e.g., entities::channel::Model).
 // The types below are kept for API request/response DTOs and non-DB types.
@@ -22,6 +25,44 @@ pub struct ReactionGroup {
     pub user_ids: Vec<String>,
 }
 
+/// A file attached to a message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: String,
+    pub filename: String,
+    pub mime_type: String,
+    pub size: i64,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    /// Signed download URL; expires, so clients re-fetch messages (or `/url`) for a fresh one
+    pub url: String,
+}
+
+/// A sticker, as shown in packs and on messages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sticker {
+    pub id: String,
+    pub pack_id: String,
+    pub name: String,
+    pub url: String,
+    pub animated: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StickerPack {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub user_id: String,
+    pub stickers: Vec<Sticker>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateStickerPackRequest {
+    pub name: String,
+    pub description: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageWithReply {
     #[serde(flatten)]
@@ -29,6 +70,10 @@ pub struct MessageWithReply {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub replied_message: Option<RepliedMessage>,
     pub reactions: Vec<ReactionGroup>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sticker: Option<Sticker>,
 }
 pub use crate::entities::bot::Model as Bot;
 pub use crate::entities::webhook::Model as Webhook;
@@ -79,6 +124,11 @@ bitflags::bitflags! {
         const USE_VOICE_ACTIVITY = 1 << 23; // 8388608
         const PRIORITY_SPEAKER   = 1 << 24; // 16777216
         const MODERATE_MEMBERS   = 1 << 25; // 33554432
+
+        // Members
+        const CHANGE_NICKNAME    = 1 << 26; // 67108864
+        const MANAGE_NICKNAMES   = 1 << 27; // 134217728
+        const MANAGE_EVENTS      = 1 << 28; // 268435456
         
         // Advanced
         const ADMINISTRATOR      = 1 << 30; // 1073741824
@@ -108,6 +158,9 @@ impl Permissions {
             | Self::MUTE_MEMBERS
             | Self::USE_VOICE_ACTIVITY
             | Self::MODERATE_MEMBERS
+            | Self::CHANGE_NICKNAME
+            | Self::MANAGE_NICKNAMES
+            | Self::MANAGE_EVENTS
     }
     
     pub fn default_member() -> Self {
@@ -123,6 +176,7 @@ impl Permissions {
             | Self::SPEAK
             | Self::VIDEO
             | Self::USE_VOICE_ACTIVITY
+            | Self::CHANGE_NICKNAME
     }
 }
 
@@ -159,6 +213,10 @@ pub struct UpdateRoleRequest {
     pub color: Option<String>,
     pub permissions: Option<i64>,
     pub position: Option<i64>,
+    /// Upload size limit in bytes; 0 clears it
+    pub max_upload_size: Option<i64>,
+    /// Per-member storage quota in bytes; 0 clears it
+    pub storage_quota: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -275,6 +333,15 @@ pub struct ServerInfo {
     pub online: usize,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct CreateUploadSessionRequest {
+    pub filename: String,
+    pub mime_type: String,
+    /// Total size in bytes
+    pub size: i64,
+    pub server_id: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateServerRequest {
     pub name: Option<String>,
@@ -282,6 +349,278 @@ pub struct UpdateServerRequest {
     pub join_sound_url: Option<String>,
     pub leave_sound_url: Option<String>,
     pub sound_chance: Option<i64>,
+    /// Upload allow-list; an empty list restores the default
+    pub allowed_upload_types: Option<Vec<String>>,
+    /// Upload size limit in bytes; 0 restores the instance default
+    pub max_upload_size: Option<i64>,
+    /// Total storage quota in bytes (instance admins only); 0 restores the instance default
+    pub storage_quota: Option<i64>,
+    /// Text channel for welcome messages; empty to turn them off
+    pub system_channel_id: Option<String>,
+    /// Welcome text with `{user}` / `{server}` placeholders; empty restores the default
+    pub welcome_message: Option<String>,
+    /// List the server in the public directory (needs a description)
+    pub discoverable: Option<bool>,
+    /// Discovery tags; an empty list clears them
+    pub tags: Option<Vec<String>>,
+}
+
+// ─── Discovery ───
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscoverySort {
+    /// Most members first
+    #[default]
+    Members,
+    /// Most messages in the last week first
+    Activity,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiscoveryQuery {
+    /// Searched for in the name and description
+    pub q: Option<String>,
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub sort: DiscoverySort,
+    pub limit: Option<u64>,
+    pub offset: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiscoverableServer {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub icon_url: Option<String>,
+    pub tags: Vec<String>,
+    pub member_count: i64,
+    /// Messages sent in the last week
+    pub recent_messages: i64,
+    /// The caller is already a member
+    pub joined: bool,
+    /// New members have to pass screening first
+    pub screening: bool,
+}
+
+// ─── Scheduled events ───
+
+pub use crate::entities::scheduled_event::Model as ScheduledEvent;
+
+/// Times are RFC 3339 or `YYYY-MM-DD HH:MM:SS` (UTC). Give a voice `channel_id` or a `location`.
+#[derive(Debug, Deserialize)]
+pub struct CreateEventRequest {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    pub starts_at: String,
+    pub ends_at: Option<String>,
+    pub channel_id: Option<String>,
+    pub location: Option<String>,
+}
+
+/// Any field left out is unchanged; `status` may start (`active`), end (`completed`)
+/// or cancel (`cancelled`) the event
+#[derive(Debug, Deserialize)]
+pub struct UpdateEventRequest {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub starts_at: Option<String>,
+    pub ends_at: Option<String>,
+    pub channel_id: Option<String>,
+    pub location: Option<String>,
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    /// Include completed and cancelled events
+    #[serde(default)]
+    pub include_past: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EventInfo {
+    #[serde(flatten)]
+    pub event: ScheduledEvent,
+    pub going: u64,
+    pub interested: u64,
+    /// The caller's RSVP
+    pub rsvp: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RsvpRequest {
+    /// going | interested
+    pub status: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EventRsvpInfo {
+    pub user_id: String,
+    pub display_name: String,
+    pub avatar_url: Option<String>,
+    pub status: String,
+}
+
+// ─── Forums ───
+
+pub use crate::entities::forum_post::Model as ForumPost;
+pub use crate::entities::forum_tag::Model as ForumTag;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForumPostInfo {
+    #[serde(flatten)]
+    pub post: ForumPost,
+    pub tag_ids: Vec<String>,
+    /// Messages after the opening one
+    pub reply_count: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePostRequest {
+    pub title: String,
+    /// The opening message
+    pub content: String,
+    #[serde(default)]
+    pub tag_ids: Vec<String>,
+}
+
+/// Title and tags can be changed by the author; `pinned` and `locked` need MANAGE_MESSAGES
+#[derive(Debug, Deserialize)]
+pub struct UpdatePostRequest {
+    pub title: Option<String>,
+    pub tag_ids: Option<Vec<String>>,
+    pub pinned: Option<bool>,
+    pub locked: Option<bool>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PostSort {
+    /// Latest reply first
+    #[default]
+    Activity,
+    /// Newest post first
+    Created,
+}
+
+/// Pinned posts always come first
+#[derive(Debug, Deserialize)]
+pub struct PostsQuery {
+    #[serde(default)]
+    pub sort: PostSort,
+    pub tag_id: Option<String>,
+    pub limit: Option<u64>,
+    pub offset: Option<u64>,
+}
+
+/// Leave out `id` to add a tag; tags not listed are removed
+#[derive(Debug, Deserialize)]
+pub struct ForumTagInput {
+    pub id: Option<String>,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateForumTagsRequest {
+    pub tags: Vec<ForumTagInput>,
+}
+
+// ─── Announcement channels ───
+
+#[derive(Debug, Deserialize)]
+pub struct FollowChannelRequest {
+    /// Local text channel that receives the announcements
+    pub target_channel_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetRemoteFollowsRequest {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChannelFollowInfo {
+    pub id: String,
+    pub source_channel_id: String,
+    pub target_channel_id: Option<String>,
+    /// `Server #channel` for local followers
+    pub target_name: Option<String>,
+    pub peer_id: Option<String>,
+    pub peer_name: Option<String>,
+    pub remote_channel_id: Option<String>,
+    pub created_at: String,
+}
+
+// ─── Onboarding ───
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScreeningQuestion {
+    pub question: String,
+    #[serde(default)]
+    pub required: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScreeningAnswer {
+    pub question: String,
+    pub answer: String,
+}
+
+/// A server's membership screening (PUT body and part of the GET response)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScreeningSettings {
+    pub enabled: bool,
+    #[serde(default)]
+    pub rules: String,
+    #[serde(default)]
+    pub questions: Vec<ScreeningQuestion>,
+    /// Members wait for a moderator after accepting the rules
+    #[serde(default)]
+    pub manual_approval: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnboardingStatus {
+    /// Has to accept the rules
+    Screening,
+    /// Screened, waiting for a moderator
+    AwaitingApproval,
+    Member,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScreeningInfo {
+    #[serde(flatten)]
+    pub settings: ScreeningSettings,
+    /// The caller's progress (None if not a member)
+    pub status: Option<OnboardingStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScreeningSubmission {
+    pub accept_rules: bool,
+    /// One answer per question, in order
+    #[serde(default)]
+    pub answers: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OnboardingStatusResponse {
+    pub status: OnboardingStatus,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PendingMember {
+    pub user_id: String,
+    pub display_name: String,
+    pub avatar_url: Option<String>,
+    pub joined_at: String,
+    pub screened_at: Option<String>,
+    pub answers: Vec<ScreeningAnswer>,
 }
 
 // AuditLog and Ban are re-exported from entities above
@@ -305,23 +644,90 @@ pub struct ServerStats {
     pub total_channels: i64,
     pub total_roles: i64,
     pub total_invites: i64,
+    /// Bytes of uploads shared in this server
+    pub storage_used: i64,
+    /// Effective storage quota in bytes (None = unlimited)
+    pub storage_quota: Option<i64>,
+}
+
+/// One row of the largest-uploaders report
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploaderUsage {
+    pub user_id: String,
+    pub username: String,
+    pub display_name: String,
+    /// Bytes uploaded
+    pub bytes: i64,
+    pub files: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateInviteRequest {
     pub max_uses: Option<i64>,
+    /// Seconds until the invite expires (None or 0 = never)
+    #[serde(default)]
+    pub max_age_secs: Option<i64>,
+    /// Channel to open after joining
+    #[serde(default)]
+    pub channel_id: Option<String>,
+    /// Joined members are removed on disconnect unless given a role
+    #[serde(default)]
+    pub temporary: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InviteResponse {
     pub code: String,
     pub token: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+}
+
+/// An invite as listed by `GET /api/invites`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InviteDetails {
+    #[serde(flatten)]
+    pub invite: InviteCode,
+    /// Display name of `created_by`
+    pub creator_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetVanityRequest {
+    /// None removes the vanity code
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AcceptInviteResponse {
+    pub server: Server,
+    pub channel_id: Option<String>,
+    pub temporary: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MessagesQuery {
     pub before: Option<String>,
     pub limit: Option<i64>,
+    /// Replies of one forum post (required for forum channels)
+    pub post_id: Option<String>,
+}
+
+/// GET /api/servers/{id}/members filters; `after` is the last user_id of the previous page
+#[derive(Debug, Deserialize)]
+pub struct MembersQuery {
+    pub after: Option<String>,
+    pub limit: Option<u64>,
+    /// Prefix of a nickname, display name or username
+    pub q: Option<String>,
+    pub role_id: Option<String>,
+    pub online: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetNicknameRequest {
+    /// None or empty to reset to the display name
+    pub nickname: Option<String>,
 }
 
 // ─── WebSocket Types ───
@@ -341,6 +747,15 @@ pub enum WsClientMessage {
         user_name: String,
         #[serde(default)]
         reply_to: Option<String>,
+        /// Ids of the sender's uploads to attach, made for the channel's server (needs SEND_FILES)
+        #[serde(default)]
+        attachments: Vec<String>,
+        /// Send a sticker instead of text (content must be empty)
+        #[serde(default)]
+        sticker_id: Option<String>,
+        /// Post to reply in (required in forum channels)
+        #[serde(default)]
+        post_id: Option<String>,
     },
     #[serde(rename = "timeout_user")]
     TimeoutUser {
@@ -410,13 +825,25 @@ pub enum WsClientMessage {
     },
     #[serde(rename = "ping")]
     Ping,
+    /// Re-attach to a dropped session and replay everything after `last_seq`
+    #[serde(rename = "resume")]
+    Resume {
+        session_id: String,
+        last_seq: u64,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum WsServerMessage {
     #[serde(rename = "identity")]
-    Identity { user_id: String },
+    Identity { user_id: String, session_id: String },
+    /// Resume succeeded; missed events follow with their original `seq`
+    #[serde(rename = "resumed")]
+    Resumed { session_id: String, replayed: usize },
+    /// Resume is not possible; the client must refetch state over REST
+    #[serde(rename = "invalid_session")]
+    InvalidSession { reason: String },
     #[serde(rename = "pong")]
     Pong,
     #[serde(rename = "new_message")]
@@ -434,6 +861,13 @@ pub enum WsServerMessage {
         reply_to: Option<String>,
         #[serde(skip_serializing_if = "Option::is_none")]
         replied_message: Option<RepliedMessage>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        attachments: Vec<Attachment>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sticker: Option<Box<Sticker>>,
+        /// Forum post the message replies in
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        post_id: Option<String>,
     },
     #[serde(rename="user_timedout")]
     UserTimedOut {
@@ -550,6 +984,107 @@ pub enum WsServerMessage {
     VoiceStateSync {
         voice_states: Vec<VoicePeer>,
     },
+    #[serde(rename = "presence_update")]
+    PresenceUpdate {
+        user_id: String,
+        #[serde(flatten)]
+        presence: Presence,
+    },
+    /// A server's settings or owner changed
+    #[serde(rename = "server_update")]
+    ServerUpdate { server: Server },
+    /// Sent to a user who joined or left a server; their connections rescope to match
+    #[serde(rename = "membership_update")]
+    MembershipUpdate { server_id: String, joined: bool },
+    #[serde(rename = "member_update")]
+    MemberUpdate {
+        server_id: String,
+        user_id: String,
+        nickname: Option<String>,
+    },
+    /// A scheduled event was created or changed
+    #[serde(rename = "event_update")]
+    EventUpdate { event: ScheduledEvent },
+    #[serde(rename = "event_delete")]
+    EventDelete { server_id: String, event_id: String },
+    /// Sent to users who RSVP'd when an event begins
+    #[serde(rename = "event_starting")]
+    EventStarting { event: ScheduledEvent },
+    /// An announcement was cross-posted to the channel's followers
+    #[serde(rename = "message_published")]
+    MessagePublished { id: String, channel_id: String, published_at: String },
+    /// A forum post was created, edited, pinned or locked
+    #[serde(rename = "forum_post_update")]
+    ForumPostUpdate { post: ForumPostInfo },
+    #[serde(rename = "forum_post_delete")]
+    ForumPostDelete { channel_id: String, post_id: String },
+    #[serde(rename = "forum_tags_update")]
+    ForumTagsUpdate { channel_id: String, tags: Vec<ForumTag> },
+}
+
+impl WsServerMessage {
+    /// The gateway intent a connection needs to receive this event (None = always delivered)
+    pub fn intent(&self) -> Option<Intents> {
+        match self {
+            Self::NewMessage { .. }
+            | Self::MessageEdited { .. }
+            | Self::MessageDeleted { .. }
+            | Self::MessagePinned { .. }
+            | Self::MessagePublished { .. }
+            | Self::ForumPostUpdate { .. }
+            | Self::ForumPostDelete { .. } => Some(Intents::MESSAGES),
+            Self::UserJoined { .. }
+            | Self::UserLeft { .. }
+            | Self::PresenceUpdate { .. }
+            | Self::MemberUpdate { .. } => Some(Intents::PRESENCE),
+            Self::VoicePeerJoined { .. }
+            | Self::VoicePeerLeft { .. }
+            | Self::VoiceMembers { .. }
+            | Self::VoiceOffer { .. }
+            | Self::VoiceAnswer { .. }
+            | Self::IceCandidate { .. }
+            | Self::VoiceTalking { .. }
+            | Self::VoiceStatusUpdate { .. }
+            | Self::VoiceStateSync { .. } => Some(Intents::VOICE),
+            Self::TypingStart { .. } => Some(Intents::TYPING),
+            Self::ReactionAdd { .. } | Self::ReactionRemove { .. } => Some(Intents::REACTIONS),
+            Self::UserTimedOut { .. } => Some(Intents::MODERATION),
+            Self::Identity { .. }
+            | Self::Resumed { .. }
+            | Self::InvalidSession { .. }
+            | Self::Pong
+            | Self::ServerUpdate { .. }
+            | Self::MembershipUpdate { .. }
+            | Self::EventUpdate { .. }
+            | Self::EventDelete { .. }
+            | Self::EventStarting { .. }
+            | Self::ForumTagsUpdate { .. }
+            | Self::Error { .. } => None,
+        }
+    }
+}
+
+/// A `global_tx` broadcast tagged with the servers it belongs to (None = every server),
+/// optionally narrowed to some users' connections
+#[derive(Debug, Clone)]
+pub struct ServerEvent {
+    pub server_ids: Option<Vec<String>>,
+    pub user_ids: Option<Vec<String>>,
+    pub message: WsServerMessage,
+}
+
+// ─── Gateway Intents ───
+// SYNC NOTE: Bit positions must match app/src/types/ws.ts GATEWAY_INTENTS.
+bitflags::bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct Intents: u32 {
+        const MESSAGES   = 1 << 0; // 1
+        const PRESENCE   = 1 << 1; // 2
+        const VOICE      = 1 << 2; // 4
+        const TYPING     = 1 << 3; // 8
+        const REACTIONS  = 1 << 4; // 16
+        const MODERATION = 1 << 5; // 32
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -569,6 +1104,33 @@ pub struct VoicePeer {
 pub struct CreateServerRequest {
     pub name: String,
     pub description: Option<String>,
+    /// Build the server from a stored template instead of the default layout
+    #[serde(default)]
+    pub template_code: Option<String>,
+    /// ...or from an uploaded template file
+    #[serde(default)]
+    pub template: Option<Template>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransferOwnershipRequest {
+    /// Must already be a member of the server
+    pub new_owner_id: String,
+    /// The current owner's password
+    pub password: String,
+}
+
+/// A server's stored template, shareable by `code`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerTemplateInfo {
+    pub code: String,
+    pub server_id: String,
+    pub name: String,
+    pub description: String,
+    pub user_id: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub template: Template,
 }
 
 // ─── Connection Token ───
@@ -586,9 +1148,15 @@ pub struct ConnectionToken {
 pub struct MemberInfo {
     pub user_id: String,
     pub display_name: String,
+    /// Per-server nickname, if set
+    pub nickname: Option<String>,
+    /// Hasn't completed onboarding yet
+    pub pending: bool,
     pub avatar_url: Option<String>,
     pub is_bot: bool,
     pub is_online: bool,
+    pub status: PresenceStatus,
+    pub custom_status: Option<String>,
     pub joined_at: String,
     pub roles: Vec<RoleBrief>,
 }
@@ -68,7 +68,12 @@ pub fn calculate_permissions(
 use axum::http::StatusCode;
 use sea_orm::*;
 use crate::state::AppState;
-use crate::entities::{role, user_role, channel_override};
+use crate::entities::{channel, role, user_role, channel_override};
+
+/// What members who haven't finished onboarding may not do
+pub const PENDING_DENIED: Permissions = Permissions::SEND_MESSAGES
+    .union(Permissions::SEND_FILES)
+    .union(Permissions::ADD_REACTIONS);
 
 /// Checks if a user has a specific permission in a specific channel.
 pub async fn check_channel_permission(
@@ -104,6 +109,23 @@ pub async fn check_channel_permission(
         return Ok(true);
     }
 
+    // Members still in onboarding can read but not post
+    if required.intersects(PENDING_DENIED) {
+        let server_id = channel::Entity::find_by_id(channel_id)
+            .one(&state.db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .map(|c| c.server_id);
+        if let Some(server_id) = server_id {
+            if crate::routes::onboarding::is_pending(state, &server_id, user_id)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            {
+                return Ok(false);
+            }
+        }
+    }
+
     // 2. Fetch channel overrides
     let overrides: Vec<channel_override::Model> = channel_override::Entity::find()
         .filter(channel_override::Column::ChannelId.eq(channel_id))
@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use dashmap::DashMap;
+use sea_orm::*;
+use serde::{Deserialize, Serialize};
+
+use crate::entities::{bot, server_member};
+use crate::models::WsServerMessage;
+use crate::pubsub::BusEvent;
+use crate::state::AppState;
+
+/// Connections with no client activity for this long count as idle
+pub const IDLE_AFTER: Duration = Duration::from_secs(5 * 60);
+/// Presence and voice state reported by another instance are dropped if not refreshed within this window
+pub const REMOTE_TTL: Duration = Duration::from_secs(90);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PresenceStatus {
+    Online,
+    Idle,
+    Dnd,
+    Invisible,
+    Offline,
+}
+
+impl PresenceStatus {
+    /// Parse a status a user may pick; `offline` is derived, never chosen
+    pub fn parse_chosen(s: &str) -> Option<Self> {
+        match s {
+            "online" => Some(Self::Online),
+            "idle" => Some(Self::Idle),
+            "dnd" => Some(Self::Dnd),
+            "invisible" => Some(Self::Invisible),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Online => "online",
+            Self::Idle => "idle",
+            Self::Dnd => "dnd",
+            Self::Invisible => "invisible",
+            Self::Offline => "offline",
+        }
+    }
+
+    /// Which status wins when a user is connected to several instances
+    fn rank(&self) -> u8 {
+        match self {
+            Self::Dnd => 3,
+            Self::Online => 2,
+            Self::Idle => 1,
+            Self::Invisible | Self::Offline => 0,
+        }
+    }
+}
+
+/// What other users see
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Presence {
+    pub status: PresenceStatus,
+    pub custom_status: Option<String>,
+    pub custom_status_expires_at: Option<String>,
+}
+
+impl Presence {
+    pub fn offline() -> Self {
+        Self {
+            status: PresenceStatus::Offline,
+            custom_status: None,
+            custom_status_expires_at: None,
+        }
+    }
+}
+
+/// Persisted part of a user's presence (users.status / custom_status columns)
+#[derive(Debug, Clone)]
+pub struct PresenceSettings {
+    pub status: PresenceStatus,
+    pub custom_status: Option<String>,
+    pub custom_status_expires_at: Option<DateTime<Utc>>,
+}
+
+impl PresenceSettings {
+    pub fn from_user(u: &crate::entities::user::Model) -> Self {
+        Self {
+            status: PresenceStatus::parse_chosen(&u.status).unwrap_or(PresenceStatus::Online),
+            custom_status: u.custom_status.clone(),
+            custom_status_expires_at: u
+                .custom_status_expires_at
+                .as_deref()
+                .and_then(|s| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").ok())
+                .map(|t| t.and_utc()),
+        }
+    }
+}
+
+impl Default for PresenceSettings {
+    fn default() -> Self {
+        Self {
+            status: PresenceStatus::Online,
+            custom_status: None,
+            custom_status_expires_at: None,
+        }
+    }
+}
+
+struct UserPresence {
+    settings: PresenceSettings,
+    /// connection id -> last client activity
+    connections: HashMap<String, Instant>,
+    /// Last presence computed from this instance's connections
+    visible: Presence,
+}
+
+impl UserPresence {
+    fn compute(&self) -> Presence {
+        if self.connections.is_empty() {
+            return Presence::offline();
+        }
+        let status = match self.settings.status {
+            PresenceStatus::Invisible => return Presence::offline(),
+            PresenceStatus::Online
+                if self.connections.values().all(|t| t.elapsed() >= IDLE_AFTER) =>
+            {
+                PresenceStatus::Idle
+            }
+            chosen => chosen,
+        };
+        let custom_live = self
+            .settings
+            .custom_status_expires_at
+            .is_none_or(|t| t > Utc::now());
+        Presence {
+            status,
+            custom_status: self.settings.custom_status.clone().filter(|_| custom_live),
+            custom_status_expires_at: self
+                .settings
+                .custom_status_expires_at
+                .filter(|_| custom_live)
+                .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string()),
+        }
+    }
+
+    /// Recompute and return the new presence if it differs from the last one
+    fn refresh(&mut self) -> Option<Presence> {
+        let next = self.compute();
+        if next == self.visible {
+            return None;
+        }
+        self.visible = next.clone();
+        Some(next)
+    }
+}
+
+/// Aggregated presence across every connection of every user, on every instance
+#[derive(Default)]
+pub struct PresenceTracker {
+    /// Connections to this instance
+    users: DashMap<String, UserPresence>,
+    /// user_id -> instance_id -> (presence there, last heard)
+    remote: DashMap<String, HashMap<String, (Presence, Instant)>>,
+    /// Last merged presence sent to clients, to only announce changes
+    announced: DashMap<String, Presence>,
+}
+
+impl PresenceTracker {
+    /// Register a connection; `settings` is only used when it is the user's first one
+    pub fn connect(&self, user_id: &str, conn_id: &str, settings: PresenceSettings) -> Option<Presence> {
+        let mut entry = self.users.entry(user_id.to_string()).or_insert_with(|| UserPresence {
+            settings,
+            connections: HashMap::new(),
+            visible: Presence::offline(),
+        });
+        entry.connections.insert(conn_id.to_string(), Instant::now());
+        entry.refresh()
+    }
+
+    pub fn disconnect(&self, user_id: &str, conn_id: &str) -> Option<Presence> {
+        let changed = {
+            let mut entry = self.users.get_mut(user_id)?;
+            entry.connections.remove(conn_id);
+            entry.refresh()
+        };
+        self.users.remove_if(user_id, |_, p| p.connections.is_empty());
+        changed
+    }
+
+    /// Whether the user still has a connection on this or another instance
+    pub fn is_connected(&self, user_id: &str) -> bool {
+        self.users.contains_key(user_id) || self.remote.contains_key(user_id)
+    }
+
+    /// Record client activity on a connection (brings an auto-idle user back online)
+    pub fn touch(&self, user_id: &str, conn_id: &str) -> Option<Presence> {
+        let mut entry = self.users.get_mut(user_id)?;
+        if let Some(last) = entry.connections.get_mut(conn_id) {
+            *last = Instant::now();
+        }
+        entry.refresh()
+    }
+
+    /// Apply new user-chosen settings to a connected user
+    pub fn update(&self, user_id: &str, settings: PresenceSettings) -> Option<Presence> {
+        let mut entry = self.users.get_mut(user_id)?;
+        entry.settings = settings;
+        entry.refresh()
+    }
+
+    /// Auto-idle and custom status expiry; returns every local presence that changed
+    pub fn sweep(&self) -> Vec<(String, Presence)> {
+        self.users
+            .iter_mut()
+            .filter_map(|mut entry| entry.refresh().map(|p| (entry.key().clone(), p)))
+            .collect()
+    }
+
+    /// Presence of every user connected here, as republished to other instances
+    pub fn local_snapshot(&self) -> Vec<(String, Presence)> {
+        self.users
+            .iter()
+            .map(|e| (e.key().clone(), e.visible.clone()))
+            .collect()
+    }
+
+    /// Record what another instance reports for a user
+    pub fn set_remote(&self, instance_id: &str, user_id: &str, presence: Presence) {
+        if presence.status == PresenceStatus::Offline {
+            if let Some(mut entry) = self.remote.get_mut(user_id) {
+                entry.remove(instance_id);
+            }
+            self.remote.remove_if(user_id, |_, m| m.is_empty());
+        } else {
+            self.remote
+                .entry(user_id.to_string())
+                .or_default()
+                .insert(instance_id.to_string(), (presence, Instant::now()));
+        }
+    }
+
+    /// Forget instances that stopped reporting; returns the users affected
+    pub fn expire_remote(&self) -> Vec<String> {
+        let mut affected = vec![];
+        self.remote.retain(|user_id, instances| {
+            let before = instances.len();
+            instances.retain(|_, (_, seen)| seen.elapsed() < REMOTE_TTL);
+            if instances.len() < before {
+                affected.push(user_id.clone());
+            }
+            !instances.is_empty()
+        });
+        affected
+    }
+
+    /// Record the merged presence as announced; returns it if clients haven't seen it yet
+    pub fn announce(&self, user_id: &str) -> Option<Presence> {
+        let merged = self.get(user_id);
+        let previous = self
+            .announced
+            .get(user_id)
+            .map(|p| p.clone())
+            .unwrap_or_else(Presence::offline);
+        if merged == previous {
+            return None;
+        }
+        if merged.status == PresenceStatus::Offline {
+            self.announced.remove(user_id);
+        } else {
+            self.announced.insert(user_id.to_string(), merged.clone());
+        }
+        Some(merged)
+    }
+
+    /// Presence across all instances: the most present status wins
+    pub fn get(&self, user_id: &str) -> Presence {
+        let local = self.users.get(user_id).map(|p| p.visible.clone());
+        let remote = self
+            .remote
+            .get(user_id)
+            .map(|m| m.values().map(|(p, _)| p.clone()).collect::<Vec<_>>())
+            .unwrap_or_default();
+        local
+            .into_iter()
+            .chain(remote)
+            .max_by_key(|p| p.status.rank())
+            .filter(|p| p.status != PresenceStatus::Offline)
+            .unwrap_or_else(Presence::offline)
+    }
+
+    /// Users that appear online to others (any status except offline/invisible)
+    pub fn visible_online(&self) -> HashMap<String, Presence> {
+        self.users
+            .iter()
+            .map(|e| e.key().clone())
+            .chain(self.remote.iter().map(|e| e.key().clone()))
+            .collect::<std::collections::HashSet<_>>()
+            .into_iter()
+            .map(|user_id| {
+                let presence = self.get(&user_id);
+                (user_id, presence)
+            })
+            .filter(|(_, p)| p.status != PresenceStatus::Offline)
+            .collect()
+    }
+}
+
+/// Share a change in this instance's view of a user with the other instances,
+/// and tell clients if the user's overall presence changed
+pub async fn broadcast_presence(state: &AppState, user_id: &str, presence: Presence) {
+    state.pubsub.publish(&BusEvent::Presence {
+        user_id: user_id.to_string(),
+        presence,
+    });
+    announce(state, user_id).await;
+}
+
+/// Republish local presence so other instances keep it alive, and drop instances
+/// that went quiet (call from a background task, well within `REMOTE_TTL`)
+pub async fn heartbeat(state: &AppState) {
+    for (user_id, presence) in state.presence.local_snapshot() {
+        state.pubsub.publish(&BusEvent::Presence { user_id, presence });
+    }
+    for user_id in state.presence.expire_remote() {
+        announce(state, &user_id).await;
+    }
+}
+
+/// Send a `PresenceUpdate` to this instance's connections scoped to any server the user
+/// belongs to, if the merged presence changed. Every instance does this on its own.
+pub async fn announce(state: &AppState, user_id: &str) {
+    let Some(presence) = state.presence.announce(user_id) else {
+        return;
+    };
+
+    let server_ids: Vec<String> = server_member::Entity::find()
+        .filter(server_member::Column::UserId.eq(user_id))
+        .all(&state.db)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|m| m.server_id)
+        .collect();
+    // Bots aren't server members; they live in the server they were created/installed in
+    let server_ids = if server_ids.is_empty() {
+        match bot::Entity::find_by_id(user_id).one(&state.db).await {
+            Ok(Some(b)) => vec![b.server_id],
+            _ => return,
+        }
+    } else {
+        server_ids
+    };
+
+    state.broadcast_to_servers_local(
+        server_ids,
+        WsServerMessage::PresenceUpdate {
+            user_id: user_id.to_string(),
+            presence,
+        },
+    );
+}
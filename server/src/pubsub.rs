@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use sea_orm::EntityTrait;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgListener, PgPoolOptions};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::entities::{channel, user};
+use crate::models::{ServerEvent, VoicePeer, WsServerMessage};
+use crate::presence::{self, Presence, PresenceSettings};
+use crate::state::AppState;
+
+/// Postgres channel every instance LISTENs on
+const NOTIFY_CHANNEL: &str = "sivyspeak_realtime";
+/// NOTIFY payloads are capped at 8000 bytes; larger events are sent in chunks
+const MAX_CHUNK: usize = 7000;
+/// Outgoing NOTIFYs queued before new ones are dropped
+const OUTGOING_QUEUE: usize = 4096;
+
+/// Real-time traffic that has to reach every server instance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BusEvent {
+    /// Fan-out to subscribers of a text/voice channel
+    Channel { channel_id: String, message: WsServerMessage },
+    /// Fan-out through `global_tx`, scoped to servers and optionally to users
+    Global {
+        server_ids: Option<Vec<String>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        user_ids: Option<Vec<String>>,
+        message: WsServerMessage,
+    },
+    VoiceJoin { peer: VoicePeer },
+    /// Every voice member connected to the origin; replaces what it reported before
+    VoiceSnapshot { peers: Vec<VoicePeer> },
+    /// A new instance came up and has no voice state yet
+    InstanceStarted,
+    VoiceLeave { channel_id: String, user_id: String },
+    VoiceStatus {
+        channel_id: String,
+        user_id: String,
+        is_muted: bool,
+        is_deafened: bool,
+    },
+    /// One instance's view of a user's presence, aggregated by the others
+    Presence { user_id: String, presence: Presence },
+    /// A user changed their chosen status; instances reload it from the database
+    PresenceSettings { user_id: String },
+}
+
+/// An event published by another instance
+pub struct RemoteEvent {
+    pub origin: String,
+    pub event: BusEvent,
+}
+
+/// Broadcast backend shared by all server instances.
+/// Callers deliver to their own sockets first; `publish` only has to reach the others.
+pub trait PubSub: Send + Sync {
+    fn publish(&self, event: &BusEvent);
+}
+
+/// Single-process deployment: there is nobody else to tell
+pub struct InProcessPubSub;
+
+impl PubSub for InProcessPubSub {
+    fn publish(&self, _event: &BusEvent) {}
+}
+
+/// Postgres LISTEN/NOTIFY backend for running several instances behind a load balancer
+pub struct PostgresPubSub {
+    /// Tags our own NOTIFYs so the listener can skip them
+    instance_id: String,
+    outgoing: mpsc::Sender<String>,
+}
+
+impl PostgresPubSub {
+    /// Connect, LISTEN and start publishing. Events from other instances arrive on the
+    /// returned receiver; hand it to `apply_remote_events` once the state exists.
+    pub async fn connect(url: &str) -> Result<(Self, mpsc::Receiver<RemoteEvent>), sqlx::Error> {
+        let instance_id = Uuid::new_v4().to_string();
+        let pool = PgPoolOptions::new().max_connections(2).connect(url).await?;
+        let mut listener = PgListener::connect_with(&pool).await?;
+        listener.listen(NOTIFY_CHANNEL).await?;
+
+        let (outgoing, mut outgoing_rx) = mpsc::channel::<String>(OUTGOING_QUEUE);
+        tokio::spawn(async move {
+            while let Some(payload) = outgoing_rx.recv().await {
+                if let Err(e) = sqlx::query("SELECT pg_notify($1, $2)")
+                    .bind(NOTIFY_CHANNEL)
+                    .bind(&payload)
+                    .execute(&pool)
+                    .await
+                {
+                    tracing::warn!("pubsub: NOTIFY failed: {e}");
+                }
+            }
+        });
+
+        let (incoming_tx, incoming_rx) = mpsc::channel::<RemoteEvent>(OUTGOING_QUEUE);
+        let own_id = instance_id.clone();
+        tokio::spawn(async move {
+            let mut partial: HashMap<String, Vec<Option<String>>> = HashMap::new();
+            loop {
+                // recv() reconnects on its own; events sent while disconnected are lost
+                let notification = match listener.recv().await {
+                    Ok(n) => n,
+                    Err(e) => {
+                        tracing::warn!("pubsub: LISTEN connection error: {e}");
+                        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                        continue;
+                    }
+                };
+                let Some((origin, json)) = decode_frame(notification.payload(), &mut partial) else {
+                    continue;
+                };
+                if origin == own_id {
+                    continue;
+                }
+                match serde_json::from_str::<BusEvent>(&json) {
+                    Ok(event) => {
+                        if incoming_tx.send(RemoteEvent { origin, event }).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => tracing::warn!("pubsub: undecodable event: {e}"),
+                }
+            }
+        });
+
+        Ok((Self { instance_id, outgoing }, incoming_rx))
+    }
+}
+
+impl PubSub for PostgresPubSub {
+    fn publish(&self, event: &BusEvent) {
+        let json = match serde_json::to_string(event) {
+            Ok(json) => json,
+            Err(e) => {
+                tracing::warn!("pubsub: failed to serialize event: {e}");
+                return;
+            }
+        };
+        for frame in encode_frames(&self.instance_id, &json) {
+            if self.outgoing.try_send(frame).is_err() {
+                tracing::warn!("pubsub: outgoing queue full, dropping event");
+                return;
+            }
+        }
+    }
+}
+
+/// Frame a payload for NOTIFY: `F|origin|json`, or `C|origin|id|index|total|part` chunks
+fn encode_frames(origin: &str, json: &str) -> Vec<String> {
+    if json.len() <= MAX_CHUNK {
+        return vec![format!("F|{origin}|{json}")];
+    }
+
+    let mut parts = Vec::new();
+    let mut rest = json;
+    while !rest.is_empty() {
+        let mut end = rest.len().min(MAX_CHUNK);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (head, tail) = rest.split_at(end);
+        parts.push(head);
+        rest = tail;
+    }
+
+    let id = Uuid::new_v4();
+    let total = parts.len();
+    parts
+        .into_iter()
+        .enumerate()
+        .map(|(i, part)| format!("C|{origin}|{id}|{i}|{total}|{part}"))
+        .collect()
+}
+
+/// Decode one NOTIFY payload, returning `(origin, json)` once an event is complete
+fn decode_frame(payload: &str, partial: &mut HashMap<String, Vec<Option<String>>>) -> Option<(String, String)> {
+    let (kind, rest) = payload.split_once('|')?;
+    let (origin, rest) = rest.split_once('|')?;
+    match kind {
+        "F" => Some((origin.to_string(), rest.to_string())),
+        "C" => {
+            let mut fields = rest.splitn(4, '|');
+            let id = fields.next()?;
+            let index: usize = fields.next()?.parse().ok()?;
+            let total: usize = fields.next()?.parse().ok()?;
+            let part = fields.next()?;
+            if index >= total || total > 1024 {
+                return None;
+            }
+
+            // Chunks of a lost event would otherwise pile up forever
+            if partial.len() > 256 {
+                partial.clear();
+            }
+            let key = format!("{origin}|{id}");
+            let slots = partial.entry(key.clone()).or_insert_with(|| vec![None; total]);
+            if slots.len() != total {
+                partial.remove(&key);
+                return None;
+            }
+            slots[index] = Some(part.to_string());
+            if slots.iter().any(Option::is_none) {
+                return None;
+            }
+            let json: String = partial.remove(&key)?.into_iter().flatten().collect();
+            Some((origin.to_string(), json))
+        }
+        _ => None,
+    }
+}
+
+/// Deliver events from other instances to this instance's sockets and shared state
+pub async fn apply_remote_events(state: AppState, mut incoming: mpsc::Receiver<RemoteEvent>) {
+    while let Some(RemoteEvent { origin, event }) = incoming.recv().await {
+        match event {
+            BusEvent::Channel { channel_id, message } => {
+                // Nobody here listens to this channel: nothing to do
+                if let Some(tx) = state.channels.get(&channel_id) {
+                    let _ = tx.send(message);
+                }
+            }
+            BusEvent::Global { server_ids, user_ids, message } => {
+                let _ = state.global_tx.send(ServerEvent { server_ids, user_ids, message });
+            }
+            BusEvent::VoiceJoin { peer } => {
+                state.join_voice_remote(&origin, peer);
+            }
+            BusEvent::VoiceLeave { channel_id, user_id } => {
+                state.leave_voice_remote(&channel_id, &user_id);
+            }
+            BusEvent::VoiceSnapshot { peers } => {
+                let (joined, left) = state.sync_voice_remote(&origin, peers);
+                for peer in joined {
+                    let message = WsServerMessage::VoicePeerJoined {
+                        channel_id: peer.channel_id.clone(),
+                        user_id: peer.user_id,
+                        user_name: peer.user_name,
+                    };
+                    notify_voice_local(&state, &peer.channel_id, message).await;
+                }
+                for (channel_id, user_id) in left {
+                    let message = WsServerMessage::VoicePeerLeft { channel_id: channel_id.clone(), user_id };
+                    notify_voice_local(&state, &channel_id, message).await;
+                }
+            }
+            BusEvent::InstanceStarted => {
+                state.pubsub.publish(&BusEvent::VoiceSnapshot { peers: state.local_voice_snapshot() });
+            }
+            BusEvent::VoiceStatus { channel_id, user_id, is_muted, is_deafened } => {
+                state.update_voice_status_local(&channel_id, &user_id, is_muted, is_deafened);
+            }
+            BusEvent::Presence { user_id, presence } => {
+                state.presence.set_remote(&origin, &user_id, presence);
+                presence::announce(&state, &user_id).await;
+            }
+            BusEvent::PresenceSettings { user_id } => {
+                let Ok(Some(u)) = user::Entity::find_by_id(&user_id).one(&state.db).await else {
+                    continue;
+                };
+                if let Some(local) = state.presence.update(&user_id, PresenceSettings::from_user(&u)) {
+                    presence::broadcast_presence(&state, &user_id, local).await;
+                }
+            }
+        }
+    }
+}
+
+/// Tell this instance's connections about a voice change they missed: the channel's
+/// subscribers and everyone scoped to its server
+async fn notify_voice_local(state: &AppState, channel_id: &str, message: WsServerMessage) {
+    if let Some(tx) = state.channels.get(channel_id) {
+        let _ = tx.send(message.clone());
+    }
+    let server_id = channel::Entity::find_by_id(channel_id)
+        .one(&state.db)
+        .await
+        .ok()
+        .flatten()
+        .map(|c| c.server_id);
+    if let Some(server_id) = server_id {
+        state.broadcast_to_servers_local(vec![server_id], message);
+    }
+}
+
+/// Republish this instance's voice members so the others keep them, and drop members of
+/// instances that went quiet (call from a background task, well within `REMOTE_TTL`)
+pub async fn voice_heartbeat(state: &AppState) {
+    state.pubsub.publish(&BusEvent::VoiceSnapshot { peers: state.local_voice_snapshot() });
+    for (channel_id, user_id) in state.expire_remote_voice() {
+        let message = WsServerMessage::VoicePeerLeft { channel_id: channel_id.clone(), user_id };
+        notify_voice_local(state, &channel_id, message).await;
+    }
+}
+
+/// Pick the backend from the `--pubsub` setting
+pub async fn connect(kind: &str, url: &str) -> (Arc<dyn PubSub>, Option<mpsc::Receiver<RemoteEvent>>) {
+    match kind {
+        "postgres" => match PostgresPubSub::connect(url).await {
+            Ok((backend, incoming)) => {
+                tracing::info!("Real-time pub/sub: Postgres LISTEN/NOTIFY");
+                (Arc::new(backend), Some(incoming))
+            }
+            Err(e) => panic!("Failed to connect Postgres pub/sub: {e}"),
+        },
+        "memory" => (Arc::new(InProcessPubSub), None),
+        other => panic!("Unknown pub/sub backend '{other}' (expected memory or postgres)"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_roundtrip_through_chunks() {
+        let json = format!("{{\"sdp\":\"{}\"}}", "é".repeat(MAX_CHUNK));
+        let frames = encode_frames("origin-a", &json);
+        assert!(frames.len() > 1);
+
+        let mut partial = HashMap::new();
+        let mut decoded = None;
+        for frame in &frames {
+            assert!(frame.len() < 8000);
+            decoded = decode_frame(frame, &mut partial);
+        }
+        assert_eq!(decoded, Some(("origin-a".to_string(), json)));
+        assert!(partial.is_empty());
+    }
+}
@@ -0,0 +1,88 @@
+use axum::http::StatusCode;
+use sea_orm::*;
+
+use crate::entities::{server, upload};
+use crate::routes::uploads::UploadPolicy;
+use crate::state::AppState;
+
+async fn total_size<C: ConnectionTrait>(db: &C, condition: Condition) -> Result<i64, DbErr> {
+    let total: Option<Option<i64>> = upload::Entity::find()
+        .select_only()
+        .column_as(upload::Column::Size.sum(), "total")
+        .filter(condition)
+        .into_tuple()
+        .one(db)
+        .await?;
+    Ok(total.flatten().unwrap_or(0))
+}
+
+/// Bytes uploaded by `user_id`, wherever they were shared
+pub async fn user_usage<C: ConnectionTrait>(db: &C, user_id: &str) -> Result<i64, DbErr> {
+    total_size(db, Condition::all().add(upload::Column::UserId.eq(user_id))).await
+}
+
+/// Bytes shared in `server_id`
+pub async fn server_usage<C: ConnectionTrait>(db: &C, server_id: &str) -> Result<i64, DbErr> {
+    total_size(db, Condition::all().add(upload::Column::ServerId.eq(server_id))).await
+}
+
+/// The server's own quota, else the instance default (None = unlimited)
+pub fn server_quota(state: &AppState, srv: &server::Model) -> Option<u64> {
+    srv.storage_quota
+        .map(|quota| quota.max(0) as u64)
+        .or(state.uploads.server_quota)
+}
+
+/// Whether `size` more bytes fit in `quota` with `used` already taken
+fn fits(used: i64, quota: Option<u64>, size: u64) -> bool {
+    quota.is_none_or(|quota| used.max(0) as u64 + size <= quota)
+}
+
+fn exceeded(whose: &str, used: i64, quota: u64) -> (StatusCode, String) {
+    let mb = |bytes: f64| bytes / 1024.0 / 1024.0;
+    (
+        StatusCode::PAYLOAD_TOO_LARGE,
+        format!(
+            "Storage quota of {whose} exceeded ({:.1}MB of {:.1}MB used)",
+            mb(used.max(0) as f64),
+            mb(quota as f64)
+        ),
+    )
+}
+
+/// Check that `size` more bytes from `user_id` fit in their quota and the server's
+pub async fn check(
+    state: &AppState,
+    user_id: &str,
+    policy: &UploadPolicy,
+    size: u64,
+) -> Result<(), (StatusCode, String)> {
+    let db_err = |e: DbErr| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"));
+
+    if let Some(quota) = policy.user_quota {
+        let used = user_usage(&state.db, user_id).await.map_err(db_err)?;
+        if !fits(used, Some(quota), size) {
+            return Err(exceeded("your account", used, quota));
+        }
+    }
+    if let (Some(server_id), Some(quota)) = (&policy.server_id, policy.server_quota) {
+        let used = server_usage(&state.db, server_id).await.map_err(db_err)?;
+        if !fits(used, Some(quota), size) {
+            return Err(exceeded("this server", used, quota));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quota_boundaries() {
+        assert!(fits(0, None, u64::MAX));
+        assert!(fits(90, Some(100), 10));
+        assert!(!fits(90, Some(100), 11));
+        assert!(!fits(0, Some(0), 1));
+    }
+}
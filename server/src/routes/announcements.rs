@@ -0,0 +1,391 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use sea_orm::*;
+use uuid::Uuid;
+
+use crate::entities::{channel, channel_follow, federation_peer, message, server};
+use crate::models::{
+    ChannelFollowInfo, FollowChannelRequest, Message, Permissions, SetRemoteFollowsRequest, WsServerMessage,
+};
+use crate::permissions::check_channel_permission;
+use crate::routes::auth;
+use crate::routes::federation::{peer_client, peer_url};
+use crate::state::AppState;
+
+/// Follows per announcement channel
+pub const MAX_FOLLOWERS: u64 = 100;
+
+fn db_err(e: DbErr) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"))
+}
+
+async fn require(
+    state: &AppState,
+    user_id: &str,
+    channel_id: &str,
+    perm: Permissions,
+) -> Result<(), (StatusCode, String)> {
+    let allowed = check_channel_permission(state, user_id, channel_id, perm)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Permission error: {e}")))?;
+    if !allowed {
+        return Err((StatusCode::FORBIDDEN, "Missing permissions in this channel".into()));
+    }
+    Ok(())
+}
+
+async fn announcement_channel(state: &AppState, channel_id: &str) -> Result<channel::Model, (StatusCode, String)> {
+    channel::Entity::find_by_id(channel_id)
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .filter(|c| c.channel_type == "announcement")
+        .ok_or((StatusCode::NOT_FOUND, "Announcement channel not found".to_string()))
+}
+
+/// Only members who can manage messages post in announcement channels
+pub async fn check_can_post(state: &AppState, user_id: &str, channel_id: &str) -> Result<(), String> {
+    let is_announcement = channel::Entity::find_by_id(channel_id)
+        .one(&state.db)
+        .await
+        .map_err(|e| format!("DB error: {e}"))?
+        .is_some_and(|c| c.channel_type == "announcement");
+    if is_announcement
+        && !check_channel_permission(state, user_id, channel_id, Permissions::MANAGE_MESSAGES)
+            .await
+            .unwrap_or(false)
+    {
+        return Err("Only moderators can post in announcement channels".to_string());
+    }
+    Ok(())
+}
+
+/// GET /api/channels/{channel_id}/followers (MANAGE_CHANNELS)
+pub async fn list_followers(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(channel_id): Path<String>,
+) -> Result<Json<Vec<ChannelFollowInfo>>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    announcement_channel(&state, &channel_id).await?;
+    require(&state, &claims.sub, &channel_id, Permissions::MANAGE_CHANNELS).await?;
+
+    let follows = channel_follow::Entity::find()
+        .filter(channel_follow::Column::SourceChannelId.eq(&channel_id))
+        .find_also_related(federation_peer::Entity)
+        .order_by_asc(channel_follow::Column::CreatedAt)
+        .all(&state.db)
+        .await
+        .map_err(db_err)?;
+
+    let mut result = Vec::with_capacity(follows.len());
+    for (follow, peer) in follows {
+        let target_name = match &follow.target_channel_id {
+            Some(id) => target_name(&state, id).await.map_err(db_err)?,
+            None => None,
+        };
+        result.push(ChannelFollowInfo {
+            id: follow.id,
+            source_channel_id: follow.source_channel_id,
+            target_channel_id: follow.target_channel_id,
+            target_name,
+            peer_id: follow.peer_id,
+            peer_name: peer.map(|p| p.name),
+            remote_channel_id: follow.remote_channel_id,
+            created_at: follow.created_at,
+        });
+    }
+    Ok(Json(result))
+}
+
+/// `Server #channel`
+async fn target_name(state: &AppState, channel_id: &str) -> Result<Option<String>, DbErr> {
+    let Some((ch, srv)) = channel::Entity::find_by_id(channel_id)
+        .find_also_related(server::Entity)
+        .one(&state.db)
+        .await?
+    else {
+        return Ok(None);
+    };
+    Ok(Some(match srv {
+        Some(srv) => format!("{} #{}", srv.name, ch.name),
+        None => format!("#{}", ch.name),
+    }))
+}
+
+/// POST /api/channels/{channel_id}/followers — follow an announcement channel into a local
+/// text channel (MANAGE_CHANNELS there)
+pub async fn follow_channel(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(channel_id): Path<String>,
+    Json(req): Json<FollowChannelRequest>,
+) -> Result<(StatusCode, Json<ChannelFollowInfo>), (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    announcement_channel(&state, &channel_id).await?;
+    require(&state, &claims.sub, &channel_id, Permissions::VIEW_CHANNELS).await?;
+
+    let is_text = channel::Entity::find_by_id(&req.target_channel_id)
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .is_some_and(|c| c.channel_type == "text");
+    if !is_text {
+        return Err((StatusCode::BAD_REQUEST, "Announcements can only be followed into a text channel".into()));
+    }
+    require(&state, &claims.sub, &req.target_channel_id, Permissions::MANAGE_CHANNELS).await?;
+
+    let followers = channel_follow::Entity::find()
+        .filter(channel_follow::Column::SourceChannelId.eq(&channel_id))
+        .count(&state.db)
+        .await
+        .map_err(db_err)?;
+    if followers >= MAX_FOLLOWERS {
+        return Err((StatusCode::BAD_REQUEST, format!("Channels can have at most {MAX_FOLLOWERS} followers")));
+    }
+
+    let follow = channel_follow::Model {
+        id: Uuid::new_v4().to_string(),
+        source_channel_id: channel_id,
+        target_channel_id: Some(req.target_channel_id.clone()),
+        peer_id: None,
+        remote_channel_id: None,
+        created_by: Some(claims.sub),
+        created_at: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    };
+    let inserted = channel_follow::Entity::insert(follow.clone().into_active_model().reset_all())
+        .on_conflict(
+            sea_query::OnConflict::columns([
+                channel_follow::Column::SourceChannelId,
+                channel_follow::Column::TargetChannelId,
+            ])
+            .do_nothing()
+            .to_owned(),
+        )
+        .do_nothing()
+        .exec(&state.db)
+        .await
+        .map_err(db_err)?;
+    if !matches!(inserted, TryInsertResult::Inserted(_)) {
+        return Err((StatusCode::CONFLICT, "That channel already follows this one".into()));
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ChannelFollowInfo {
+            id: follow.id,
+            source_channel_id: follow.source_channel_id,
+            target_name: target_name(&state, &req.target_channel_id).await.map_err(db_err)?,
+            target_channel_id: follow.target_channel_id,
+            peer_id: None,
+            peer_name: None,
+            remote_channel_id: None,
+            created_at: follow.created_at,
+        }),
+    ))
+}
+
+/// DELETE /api/channels/{channel_id}/followers/{follow_id} — by either side's channel managers
+pub async fn unfollow_channel(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((channel_id, follow_id)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let follow = channel_follow::Entity::find_by_id(&follow_id)
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .filter(|f| f.source_channel_id == channel_id)
+        .ok_or((StatusCode::NOT_FOUND, "Follow not found".to_string()))?;
+
+    let mut allowed = check_channel_permission(&state, &claims.sub, &channel_id, Permissions::MANAGE_CHANNELS)
+        .await
+        .unwrap_or(false);
+    if let (false, Some(target)) = (allowed, &follow.target_channel_id) {
+        allowed = check_channel_permission(&state, &claims.sub, target, Permissions::MANAGE_CHANNELS)
+            .await
+            .unwrap_or(false);
+    }
+    if !allowed {
+        return Err((StatusCode::FORBIDDEN, "Not authorized to remove this follow".into()));
+    }
+
+    channel_follow::Entity::delete_by_id(&follow_id)
+        .exec(&state.db)
+        .await
+        .map_err(db_err)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// PUT /api/channels/{channel_id}/remote-follows — let federation peers follow this channel,
+/// or stop them and drop their follows (MANAGE_CHANNELS)
+pub async fn set_remote_follows(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(channel_id): Path<String>,
+    Json(req): Json<SetRemoteFollowsRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    announcement_channel(&state, &channel_id).await?;
+    require(&state, &claims.sub, &channel_id, Permissions::MANAGE_CHANNELS).await?;
+
+    let txn = state.db.begin().await.map_err(db_err)?;
+    channel::Entity::update_many()
+        .col_expr(channel::Column::RemoteFollows, sea_query::Expr::value(req.enabled))
+        .filter(channel::Column::Id.eq(&channel_id))
+        .exec(&txn)
+        .await
+        .map_err(db_err)?;
+    if !req.enabled {
+        channel_follow::Entity::delete_many()
+            .filter(channel_follow::Column::SourceChannelId.eq(&channel_id))
+            .filter(channel_follow::Column::PeerId.is_not_null())
+            .exec(&txn)
+            .await
+            .map_err(db_err)?;
+    }
+    txn.commit().await.map_err(db_err)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /api/messages/{message_id}/publish — cross-post an announcement to every follower
+/// (MANAGE_MESSAGES)
+pub async fn publish_message(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(message_id): Path<String>,
+) -> Result<Json<Message>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let msg = message::Entity::find_by_id(&message_id)
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .filter(|m| m.deleted_at.is_none())
+        .ok_or((StatusCode::NOT_FOUND, "Message not found".to_string()))?;
+    let source = announcement_channel(&state, &msg.channel_id)
+        .await
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Only announcements can be published".to_string()))?;
+    require(&state, &claims.sub, &source.id, Permissions::MANAGE_MESSAGES).await?;
+    if msg.content.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Only text announcements can be published".into()));
+    }
+
+    // Conditional, so a message is only ever published once
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let marked = message::Entity::update_many()
+        .col_expr(message::Column::PublishedAt, sea_query::Expr::value(now.clone()))
+        .filter(message::Column::Id.eq(&message_id))
+        .filter(message::Column::PublishedAt.is_null())
+        .exec(&state.db)
+        .await
+        .map_err(db_err)?;
+    if marked.rows_affected == 0 {
+        return Err((StatusCode::CONFLICT, "This announcement is already published".into()));
+    }
+
+    state.publish_channel(
+        &source.id,
+        WsServerMessage::MessagePublished {
+            id: message_id,
+            channel_id: source.id.clone(),
+            published_at: now.clone(),
+        },
+    );
+
+    let msg = Message { published_at: Some(now), ..msg };
+    let fanout_state = state.clone();
+    let fanout_msg = msg.clone();
+    tokio::spawn(async move {
+        if let Err(e) = crosspost(&fanout_state, &source, &fanout_msg).await {
+            tracing::error!("Failed to cross-post announcement {}: {e}", fanout_msg.id);
+        }
+    });
+    Ok(Json(msg))
+}
+
+/// Deliver `msg` to every follower of `source`, crediting its author and origin
+async fn crosspost(state: &AppState, source: &channel::Model, msg: &Message) -> Result<(), DbErr> {
+    let srv = server::Entity::find_by_id(&source.server_id).one(&state.db).await?;
+    let server_name = srv.as_ref().map(|s| s.name.clone()).unwrap_or_default();
+    let icon_url = srv.and_then(|s| s.icon_url);
+
+    let follows = channel_follow::Entity::find()
+        .filter(channel_follow::Column::SourceChannelId.eq(&source.id))
+        .find_also_related(federation_peer::Entity)
+        .all(&state.db)
+        .await?;
+
+    for (follow, peer) in follows {
+        if let Some(target) = &follow.target_channel_id {
+            let id = Uuid::new_v4().to_string();
+            let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+            let author_id = format!("follow:{}", source.id);
+            let author_name = format!("{} [{} #{}]", msg.user_name, server_name, source.name);
+            let inserted = message::Entity::insert(message::ActiveModel {
+                id: Set(id.clone()),
+                channel_id: Set(target.clone()),
+                user_id: Set(author_id.clone()),
+                user_name: Set(author_name.clone()),
+                avatar_url: Set(icon_url.clone()),
+                content: Set(msg.content.clone()),
+                created_at: Set(now.clone()),
+                ..Default::default()
+            })
+            .exec(&state.db)
+            .await;
+            // One broken follower doesn't keep the announcement from the others
+            if let Err(e) = inserted {
+                tracing::warn!("Failed to cross-post announcement {} to channel {target}: {e}", msg.id);
+                continue;
+            }
+            state.publish_channel(
+                target,
+                WsServerMessage::NewMessage {
+                    id,
+                    channel_id: target.clone(),
+                    user_id: author_id,
+                    user_name: author_name,
+                    avatar_url: icon_url.clone(),
+                    content: msg.content.clone(),
+                    created_at: now,
+                    is_bot: true,
+                    reply_to: None,
+                    replied_message: None,
+                    attachments: Vec::new(),
+                    sticker: None,
+                    post_id: None,
+                },
+            );
+        } else if let Some(peer) = peer.filter(|p| p.status == "active") {
+            // The follower has a `follow` link for our channel; it answers 404 once that's gone
+            let sent = peer_client()
+                .post(peer_url(&peer, "/api/federation/message"))
+                .header("Authorization", format!("Federation {}", peer.shared_secret))
+                .json(&serde_json::json!({
+                    "channel_id": source.id,
+                    "user_name": msg.user_name,
+                    "content": msg.content,
+                    "server_name": format!("{} #{}", server_name, source.name),
+                }))
+                .send()
+                .await;
+            match sent {
+                Ok(r) if r.status() == reqwest::StatusCode::NOT_FOUND => {
+                    if let Err(e) = channel_follow::Entity::delete_by_id(&follow.id).exec(&state.db).await {
+                        tracing::warn!("Failed to drop follow {} of peer {}: {e}", follow.id, peer.name);
+                    }
+                }
+                Ok(r) if !r.status().is_success() => {
+                    tracing::warn!("Peer {} rejected announcement {}: {}", peer.name, msg.id, r.status());
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Failed to send announcement {} to peer {}: {e}", msg.id, peer.name),
+            }
+        }
+    }
+    Ok(())
+}
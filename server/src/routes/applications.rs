@@ -0,0 +1,289 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use rand::Rng;
+use sea_orm::*;
+use sea_orm::prelude::Expr;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::entities::application;
+use crate::models::Permissions;
+use crate::routes::auth;
+use crate::state::AppState;
+
+const MAX_REDIRECT_URIS: usize = 10;
+
+// ─── Request / Response types ───
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApplicationRequest {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub redirect_uris: Vec<String>,
+    /// Permissions the bot asks for when installed into a server
+    #[serde(default)]
+    pub permissions: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateApplicationRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub redirect_uris: Option<Vec<String>>,
+    pub permissions: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApplicationResponse {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub avatar_url: Option<String>,
+    pub owner_id: String,
+    pub redirect_uris: Vec<String>,
+    pub permissions: i64,
+    pub created_at: String,
+}
+
+impl From<application::Model> for ApplicationResponse {
+    fn from(app: application::Model) -> Self {
+        let redirect_uris = app.redirect_uri_list().into_iter().map(String::from).collect();
+        Self {
+            id: app.id,
+            name: app.name,
+            description: app.description,
+            avatar_url: app.avatar_url,
+            owner_id: app.owner_id,
+            redirect_uris,
+            permissions: app.permissions,
+            created_at: app.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreateApplicationResponse {
+    pub application: ApplicationResponse,
+    /// The client id is the application id
+    pub client_id: String,
+    /// The secret is only shown once at creation time
+    pub client_secret: String,
+}
+
+/// Generate a random client secret: `app_<random64>`
+fn generate_client_secret() -> String {
+    let secret: String = rand::thread_rng()
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(64)
+        .map(char::from)
+        .collect();
+    format!("app_{}", secret)
+}
+
+/// Validate and normalize redirect URIs into the newline-separated DB form
+fn normalize_redirect_uris(uris: &[String]) -> Result<String, (StatusCode, String)> {
+    if uris.is_empty() || uris.len() > MAX_REDIRECT_URIS {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Provide 1-{MAX_REDIRECT_URIS} redirect URIs"),
+        ));
+    }
+    let mut out = Vec::with_capacity(uris.len());
+    for uri in uris {
+        let uri = uri.trim();
+        let parsed = reqwest::Url::parse(uri)
+            .map_err(|_| (StatusCode::BAD_REQUEST, format!("Invalid redirect URI: {uri}")))?;
+        if parsed.scheme() != "https" && parsed.scheme() != "http" {
+            return Err((StatusCode::BAD_REQUEST, "Redirect URIs must be http(s)".into()));
+        }
+        if parsed.fragment().is_some() {
+            return Err((StatusCode::BAD_REQUEST, "Redirect URIs must not contain a fragment".into()));
+        }
+        out.push(uri.to_string());
+    }
+    Ok(out.join("\n"))
+}
+
+fn validate_name(name: &str) -> Result<(), (StatusCode, String)> {
+    if name.is_empty() || name.len() > 32 {
+        return Err((StatusCode::BAD_REQUEST, "Application name must be 1-32 characters".into()));
+    }
+    Ok(())
+}
+
+/// Load an application and make sure the caller owns it
+async fn find_owned_application(
+    state: &AppState,
+    app_id: &str,
+    user_id: &str,
+) -> Result<application::Model, (StatusCode, String)> {
+    let app = application::Entity::find_by_id(app_id)
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+        .ok_or((StatusCode::NOT_FOUND, "Application not found".into()))?;
+
+    if app.owner_id != user_id {
+        return Err((StatusCode::FORBIDDEN, "Only the application owner can manage it".into()));
+    }
+    Ok(app)
+}
+
+/// Drop bits that aren't permissions
+fn known_permissions(bits: i64) -> i64 {
+    Permissions::from_bits_truncate(bits).bits()
+}
+
+// ─── Routes ───
+
+/// POST /api/applications — register a new bot application
+pub async fn create_application(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<CreateApplicationRequest>,
+) -> Result<(StatusCode, Json<CreateApplicationResponse>), (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+
+    let name = req.name.trim().to_string();
+    validate_name(&name)?;
+    let description = req.description.chars().take(400).collect::<String>();
+    let redirect_uris = normalize_redirect_uris(&req.redirect_uris)?;
+    let permissions = known_permissions(req.permissions);
+
+    let app_id = Uuid::new_v4().to_string();
+    let client_secret = generate_client_secret();
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+
+    let new_app = application::ActiveModel {
+        id: Set(app_id.clone()),
+        name: Set(name.clone()),
+        description: Set(description.clone()),
+        avatar_url: Set(None),
+        owner_id: Set(claims.sub.clone()),
+        client_secret: Set(client_secret.clone()),
+        redirect_uris: Set(redirect_uris.clone()),
+        permissions: Set(permissions),
+        created_at: Set(now.clone()),
+    };
+
+    application::Entity::insert(new_app)
+        .exec(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+    let app = application::Model {
+        id: app_id.clone(),
+        name,
+        description,
+        avatar_url: None,
+        owner_id: claims.sub,
+        client_secret: String::new(),
+        redirect_uris,
+        permissions,
+        created_at: now,
+    };
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateApplicationResponse {
+            application: app.into(),
+            client_id: app_id,
+            client_secret,
+        }),
+    ))
+}
+
+/// GET /api/applications — list applications owned by the current user
+pub async fn list_applications(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<ApplicationResponse>>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+
+    let apps = application::Entity::find()
+        .filter(application::Column::OwnerId.eq(&claims.sub))
+        .order_by_desc(application::Column::CreatedAt)
+        .all(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+    Ok(Json(apps.into_iter().map(ApplicationResponse::from).collect()))
+}
+
+/// PUT /api/applications/:app_id — update application details (owner only)
+pub async fn update_application(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(app_id): Path<String>,
+    Json(req): Json<UpdateApplicationRequest>,
+) -> Result<Json<ApplicationResponse>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let existing = find_owned_application(&state, &app_id, &claims.sub).await?;
+
+    let mut active: application::ActiveModel = existing.into();
+    if let Some(name) = req.name {
+        let name = name.trim().to_string();
+        validate_name(&name)?;
+        active.name = Set(name);
+    }
+    if let Some(description) = req.description {
+        active.description = Set(description.chars().take(400).collect());
+    }
+    if let Some(uris) = req.redirect_uris {
+        active.redirect_uris = Set(normalize_redirect_uris(&uris)?);
+    }
+    if let Some(permissions) = req.permissions {
+        active.permissions = Set(known_permissions(permissions));
+    }
+
+    let updated = active
+        .update(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+    Ok(Json(updated.into()))
+}
+
+/// DELETE /api/applications/:app_id — delete an application and uninstall its bots (owner only)
+pub async fn delete_application(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(app_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    find_owned_application(&state, &app_id, &claims.sub).await?;
+
+    crate::routes::oauth2::uninstall_application_bots(&state, &app_id).await?;
+
+    application::Entity::delete_by_id(&app_id)
+        .exec(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /api/applications/:app_id/reset-secret — rotate the client secret (owner only)
+pub async fn reset_client_secret(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(app_id): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    find_owned_application(&state, &app_id, &claims.sub).await?;
+
+    let new_secret = generate_client_secret();
+
+    application::Entity::update_many()
+        .col_expr(application::Column::ClientSecret, Expr::value(&new_secret))
+        .filter(application::Column::Id.eq(&app_id))
+        .exec(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+    Ok(Json(serde_json::json!({ "client_secret": new_secret })))
+}
@@ -8,7 +8,7 @@ use axum::{
     Json,
 };
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
-use sea_orm::{EntityTrait, QueryFilter, ColumnTrait, Set, ActiveModelTrait};
+use sea_orm::{EntityTrait, QueryFilter, ColumnTrait, Set, ActiveModelTrait, TryInsertResult};
 use sea_orm::sea_query::OnConflict;
 use serde::{Deserialize, Serialize};
 use subtle::ConstantTimeEq;
@@ -154,6 +154,9 @@ pub async fn register(
         avatar_url: Set(None),
         created_at: Set(now.clone()),
         timeout_until: Set(None),
+        status: Set("online".to_string()),
+        custom_status: Set(None),
+        custom_status_expires_at: Set(None),
     };
 
     user::Entity::insert(new_user)
@@ -162,12 +165,20 @@ pub async fn register(
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
 
     // Add user to the default server
+    let pending = crate::routes::onboarding::screening_required(&state.db, "default")
+        .await
+        .unwrap_or(false);
     let sm = server_member::ActiveModel {
         server_id: Set("default".to_string()),
         user_id: Set(user_id.clone()),
         joined_at: Set(now.clone()),
+        temporary: Set(false),
+        nickname: Set(None),
+        pending: Set(pending),
+        screened_at: Set(None),
+        screening_answers: Set(None),
     };
-    let _ = server_member::Entity::insert(sm)
+    let joined = server_member::Entity::insert(sm)
         .on_conflict(
             OnConflict::columns([server_member::Column::ServerId, server_member::Column::UserId])
                 .do_nothing()
@@ -176,6 +187,9 @@ pub async fn register(
         .do_nothing()
         .exec(&state.db)
         .await;
+    if matches!(joined, Ok(TryInsertResult::Inserted(_))) && !pending {
+        crate::routes::onboarding::send_welcome(&state, "default", &user_id).await;
+    }
 
     // If setup_key provided, validate and grant admin role
     if let Some(key) = &req.setup_key {
@@ -199,6 +213,9 @@ pub async fn register(
                         permissions: Set(crate::models::Permissions::ADMINISTRATOR.bits()),
                         created_at: Set(now.clone()),
                         server_id: Set("default".to_string()),
+                        managed: Set(false),
+                        max_upload_size: Set(None),
+                        storage_quota: Set(None),
                     };
                     let _ = role::Entity::insert(admin_role)
                         .on_conflict(
@@ -255,6 +272,13 @@ pub async fn register(
     ))
 }
 
+/// Whether `password` is the user's current password
+pub fn verify_password(user: &user::Model, password: &str) -> Result<bool, (StatusCode, String)> {
+    let parsed_hash = PasswordHash::new(&user.password_hash)
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Hash parse error".to_string()))?;
+    Ok(Argon2::default().verify_password(password.as_bytes(), &parsed_hash).is_ok())
+}
+
 pub async fn login(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -276,17 +300,9 @@ pub async fn login(
         .ok_or((StatusCode::UNAUTHORIZED, "Invalid username or password".into()))?;
 
     // Verify password
-    let parsed_hash = PasswordHash::new(&user_row.password_hash)
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Hash parse error".into()))?;
-
-    Argon2::default()
-        .verify_password(req.password.as_bytes(), &parsed_hash)
-        .map_err(|_| {
-            (
-                StatusCode::UNAUTHORIZED,
-                "Invalid username or password".to_string(),
-            )
-        })?;
+    if !verify_password(&user_row, &req.password)? {
+        return Err((StatusCode::UNAUTHORIZED, "Invalid username or password".into()));
+    }
 
     let token = create_jwt(
         &state.jwt_secret,
@@ -10,8 +10,11 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::entities::{bot, channel, message};
-use crate::models::Bot;
+use crate::models::{Bot, Permissions};
+use crate::routes::announcements::check_can_post;
 use crate::routes::auth;
+use crate::routes::oauth2::uninstall_bot;
+use crate::routes::roles::user_has_server_permission;
 use crate::state::AppState;
 
 // ─── Request / Response types ───
@@ -35,7 +38,7 @@ pub struct UpdateBotRequest {
 }
 
 /// Generate a secure random bot token: `bot.<uuid>.<random64>`
-fn generate_bot_token() -> String {
+pub fn generate_bot_token() -> String {
     let random_part: String = rand::thread_rng()
         .sample_iter(&rand::distributions::Alphanumeric)
         .take(64)
@@ -75,6 +78,8 @@ pub async fn create_bot(
         permissions: Set(0),
         created_at: Set(now.clone()),
         server_id: Set("default".to_string()),
+        application_id: Set(None),
+        role_id: Set(None),
     };
 
     bot::Entity::insert(new_bot)
@@ -91,6 +96,8 @@ pub async fn create_bot(
         permissions: 0,
         created_at: now,
         server_id: "default".to_string(),
+        application_id: None,
+        role_id: None,
     };
 
     Ok((StatusCode::CREATED, Json(CreateBotResponse { bot, token })))
@@ -159,6 +166,10 @@ pub async fn update_bot(
         return Err((StatusCode::BAD_REQUEST, "Bot name must be 1-32 characters".into()));
     }
 
+    // Installed bots keep the permissions granted by the server admin
+    if req.permissions.is_some() && existing.application_id.is_some() {
+        return Err((StatusCode::FORBIDDEN, "Permissions of installed bots are managed by the server".into()));
+    }
     let new_perms = req.permissions.unwrap_or(existing.permissions);
 
     bot::Entity::update_many()
@@ -178,10 +189,12 @@ pub async fn update_bot(
         permissions: new_perms,
         created_at: existing.created_at,
         server_id: existing.server_id,
+        application_id: existing.application_id,
+        role_id: existing.role_id,
     }))
 }
 
-/// DELETE /api/bots/:bot_id — delete a bot (owner only)
+/// DELETE /api/bots/:bot_id — delete a bot (owner, or MANAGE_SERVER for installed bots)
 pub async fn delete_bot(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -196,11 +209,26 @@ pub async fn delete_bot(
         .ok_or((StatusCode::NOT_FOUND, "Bot not found".into()))?;
 
     if existing.owner_id != claims.sub {
-        return Err((StatusCode::FORBIDDEN, "Only the bot owner can delete it".into()));
+        // Admins of the bot's server may uninstall application bots from it
+        let can_uninstall = existing.application_id.is_some()
+            && user_has_server_permission(&state, &existing.server_id, &claims.sub, Permissions::MANAGE_SERVER)
+                .await
+                .map_err(|e| (e, "Permission check failed".to_string()))?;
+        if !can_uninstall {
+            return Err((StatusCode::FORBIDDEN, "Only the bot owner can delete it".into()));
+        }
     }
 
-    bot::Entity::delete_by_id(&bot_id)
-        .exec(&state.db)
+    // Drop the membership and managed role that came with the install
+    let txn = state
+        .db
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+    uninstall_bot(&txn, &existing)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+    txn.commit()
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
 
@@ -254,14 +282,29 @@ pub async fn bot_send_message(
     }
 
     // Verify channel exists
-    let channel_exists = channel::Entity::find_by_id(&req.channel_id)
+    let channel = channel::Entity::find_by_id(&req.channel_id)
         .one(&state.db)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+        .ok_or((StatusCode::NOT_FOUND, "Channel not found".into()))?;
+    if channel.channel_type == "forum" {
+        return Err((StatusCode::BAD_REQUEST, "Bots can't post in forum channels".into()));
+    }
 
-    if channel_exists.is_none() {
-        return Err((StatusCode::NOT_FOUND, "Channel not found".into()));
+    // Installed bots are scoped to their server and granted permissions
+    if bot.application_id.is_some() {
+        if channel.server_id != bot.server_id {
+            return Err((StatusCode::FORBIDDEN, "Bot is not installed in this server".into()));
+        }
+        let perms = Permissions::from_bits_truncate(bot.permissions);
+        if !perms.contains(Permissions::ADMINISTRATOR) && !perms.contains(Permissions::SEND_MESSAGES) {
+            return Err((StatusCode::FORBIDDEN, "SEND_MESSAGES permission required".into()));
+        }
     }
+    // Announcement channels take posts only from installed bots whose role can manage messages
+    check_can_post(&state, &bot.id, &channel.id)
+        .await
+        .map_err(|e| (StatusCode::FORBIDDEN, e))?;
 
     let msg_id = Uuid::new_v4().to_string();
     let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
@@ -280,6 +323,9 @@ pub async fn bot_send_message(
         reply_to: Set(None),
         pinned_at: Set(None),
         pinned_by: Set(None),
+        sticker_id: Set(None),
+        post_id: Set(None),
+        published_at: Set(None),
     };
 
     message::Entity::insert(new_msg)
@@ -299,10 +345,12 @@ pub async fn bot_send_message(
         is_bot: true,
         reply_to: None,
         replied_message: None,
+        attachments: Vec::new(),
+        sticker: None,
+        post_id: None,
     };
 
-    let tx = state.get_channel_tx(&req.channel_id);
-    let _ = tx.send(broadcast_msg);
+    state.publish_channel(&req.channel_id, broadcast_msg);
 
     Ok(Json(serde_json::json!({ "id": msg_id })))
 }
@@ -45,6 +45,7 @@ pub async fn list_channels(
                 server_id: ch.server_id,
                 category_id: ch.category_id,
                 plugin_url: ch.plugin_url,
+                remote_follows: ch.remote_follows,
             });
         }
     }
@@ -86,7 +87,7 @@ pub async fn create_channel(
 
     // Validate channel_type
     let channel_type = req.channel_type.trim().to_string();
-    if channel_type != "text" && channel_type != "voice" && channel_type != "plugin" {
+    if !matches!(channel_type.as_str(), "text" | "voice" | "plugin" | "forum" | "announcement") {
         return Err((StatusCode::BAD_REQUEST, "Invalid channel type".into()));
     }
 
@@ -147,6 +148,7 @@ pub async fn create_channel(
         server_id: Set(server_id.clone()),
         category_id: Set(category_id.clone()),
         plugin_url: Set(req.plugin_url.clone()),
+        remote_follows: Set(false),
     };
 
     channel::Entity::insert(new_channel)
@@ -168,6 +170,7 @@ pub async fn create_channel(
         server_id,
         category_id: category_id.clone(),
         plugin_url: req.plugin_url,
+        remote_follows: false,
     };
 
     Ok((StatusCode::CREATED, Json(ch)))
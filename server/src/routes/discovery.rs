@@ -0,0 +1,190 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use sea_orm::sea_query::LikeExpr;
+use sea_orm::*;
+
+use crate::entities::{channel, message, server, server_member, server_screening};
+use crate::models::{DiscoverableServer, DiscoveryQuery, DiscoverySort, ServerMember};
+use crate::routes::auth::extract_claims;
+use crate::routes::servers::add_member;
+use crate::state::AppState;
+
+const MAX_TAGS: usize = 5;
+const MAX_TAG_LENGTH: usize = 20;
+const DEFAULT_PAGE: u64 = 50;
+const MAX_PAGE: u64 = 100;
+/// Window `sort=activity` counts messages in
+const ACTIVITY_DAYS: i64 = 7;
+
+fn db_err(e: DbErr) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"))
+}
+
+/// Lowercase and dedupe discovery tags; None if any is invalid
+/// (2-20 letters, digits or dashes) or there are too many
+pub fn normalize_tags(tags: &[String]) -> Option<Vec<String>> {
+    let mut out: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        let valid = (2..=MAX_TAG_LENGTH).contains(&tag.len())
+            && tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !valid {
+            return None;
+        }
+        if !out.contains(&tag) {
+            out.push(tag);
+        }
+    }
+    (out.len() <= MAX_TAGS).then_some(out)
+}
+
+fn split_tags(tags: Option<&str>) -> Vec<String> {
+    tags.map(|t| t.split(',').map(str::to_string).collect()).unwrap_or_default()
+}
+
+/// GET /api/discovery — public servers of this instance
+pub async fn list_discoverable(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<DiscoveryQuery>,
+) -> Result<Json<Vec<DiscoverableServer>>, (StatusCode, String)> {
+    let claims = extract_claims(&state.jwt_secret, &headers)?;
+
+    let mut select = server::Entity::find().filter(server::Column::Discoverable.eq(true));
+    if let Some(q) = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        let escaped = q.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        let pattern = || LikeExpr::new(format!("%{escaped}%")).escape('\\');
+        select = select.filter(
+            Condition::any()
+                .add(server::Column::Name.like(pattern()))
+                .add(server::Column::Description.like(pattern())),
+        );
+    }
+    let mut servers = select.all(&state.db).await.map_err(db_err)?;
+    if let Some(tag) = query.tag.as_deref().map(|t| t.trim().to_lowercase()) {
+        servers.retain(|s| split_tags(s.tags.as_deref()).contains(&tag));
+    }
+    let ids: Vec<String> = servers.iter().map(|s| s.id.clone()).collect();
+
+    let member_counts: HashMap<String, i64> = server_member::Entity::find()
+        .select_only()
+        .column(server_member::Column::ServerId)
+        .column_as(server_member::Column::UserId.count(), "members")
+        .filter(server_member::Column::ServerId.is_in(ids.clone()))
+        .filter(server_member::Column::Pending.eq(false))
+        .group_by(server_member::Column::ServerId)
+        .into_tuple::<(String, i64)>()
+        .all(&state.db)
+        .await
+        .map_err(db_err)?
+        .into_iter()
+        .collect();
+
+    let since = (chrono::Utc::now() - chrono::Duration::days(ACTIVITY_DAYS))
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string();
+    let activity: HashMap<String, i64> = message::Entity::find()
+        .select_only()
+        .column(channel::Column::ServerId)
+        .column_as(message::Column::Id.count(), "messages")
+        .inner_join(channel::Entity)
+        .filter(channel::Column::ServerId.is_in(ids.clone()))
+        .filter(message::Column::CreatedAt.gte(since))
+        .filter(message::Column::DeletedAt.is_null())
+        .group_by(channel::Column::ServerId)
+        .into_tuple::<(String, i64)>()
+        .all(&state.db)
+        .await
+        .map_err(db_err)?
+        .into_iter()
+        .collect();
+
+    let joined: HashSet<String> = server_member::Entity::find()
+        .filter(server_member::Column::UserId.eq(&claims.sub))
+        .filter(server_member::Column::ServerId.is_in(ids.clone()))
+        .all(&state.db)
+        .await
+        .map_err(db_err)?
+        .into_iter()
+        .map(|m| m.server_id)
+        .collect();
+    let screened: HashSet<String> = server_screening::Entity::find()
+        .filter(server_screening::Column::ServerId.is_in(ids))
+        .filter(server_screening::Column::Enabled.eq(true))
+        .all(&state.db)
+        .await
+        .map_err(db_err)?
+        .into_iter()
+        .map(|s| s.server_id)
+        .collect();
+
+    let mut result: Vec<DiscoverableServer> = servers
+        .into_iter()
+        .map(|s| DiscoverableServer {
+            member_count: member_counts.get(&s.id).copied().unwrap_or(0),
+            recent_messages: activity.get(&s.id).copied().unwrap_or(0),
+            joined: joined.contains(&s.id),
+            screening: screened.contains(&s.id),
+            tags: split_tags(s.tags.as_deref()),
+            id: s.id,
+            name: s.name,
+            description: s.description,
+            icon_url: s.icon_url,
+        })
+        .collect();
+    match query.sort {
+        DiscoverySort::Members => result.sort_by(|a, b| {
+            b.member_count.cmp(&a.member_count).then(b.recent_messages.cmp(&a.recent_messages))
+        }),
+        DiscoverySort::Activity => result.sort_by(|a, b| {
+            b.recent_messages.cmp(&a.recent_messages).then(b.member_count.cmp(&a.member_count))
+        }),
+    }
+
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE).clamp(1, MAX_PAGE) as usize;
+    let offset = query.offset.unwrap_or(0) as usize;
+    Ok(Json(result.into_iter().skip(offset).take(limit).collect()))
+}
+
+/// POST /api/discovery/{server_id}/join — join a listed server without an invite
+pub async fn join_discoverable(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(server_id): Path<String>,
+) -> Result<Json<ServerMember>, (StatusCode, String)> {
+    let claims = extract_claims(&state.jwt_secret, &headers)?;
+
+    let listed = server::Entity::find_by_id(&server_id)
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .is_some_and(|s| s.discoverable);
+    if !listed {
+        return Err((StatusCode::NOT_FOUND, "Server not found in the directory".into()));
+    }
+
+    add_member(&state, &server_id, &claims.sub).await.map(Json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(list: &[&str]) -> Vec<String> {
+        list.iter().map(|t| String::from(*t)).collect()
+    }
+
+    #[test]
+    fn tags_are_normalized() {
+        assert_eq!(normalize_tags(&tags(&[" Gaming", "gaming", "retro-pc"])), Some(tags(&["gaming", "retro-pc"])));
+        assert_eq!(normalize_tags(&[]), Some(vec![]));
+        assert_eq!(normalize_tags(&tags(&["a"])), None);
+        assert_eq!(normalize_tags(&tags(&["no spaces"])), None);
+        assert_eq!(normalize_tags(&tags(&["a1", "b1", "c1", "d1", "e1", "f1"])), None);
+    }
+}
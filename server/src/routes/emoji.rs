@@ -1,18 +1,31 @@
 use axum::{
-    extract::{Multipart, Path, State},
-    http::{HeaderMap, StatusCode},
+    body::Bytes,
+    extract::{Multipart, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
+use futures::TryStreamExt;
 use sea_orm::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::blobs;
+use crate::emoji_pack::{self, PackEmoji};
 use crate::entities::{custom_emoji, upload};
-use crate::routes::auth;
+use crate::images::{self, ProcessedImage};
+use crate::models::Permissions;
+use crate::quotas;
+use crate::routes::{auth, roles, uploads};
 use crate::state::AppState;
 
 const MAX_EMOJI_SIZE: usize = 256 * 1024; // 256KB
-const ALLOWED_TYPES: &[&str] = &["image/png", "image/gif", "image/webp"];
+const MAX_ANIMATED_EMOJI_SIZE: usize = 512 * 1024; // 512KB
+/// Emoji larger than this on either side are rejected
+const MAX_EMOJI_SIDE: u32 = 256;
+/// Largest pack zip accepted by `import_emoji`
+pub const MAX_PACK_SIZE: usize = 64 * 1024 * 1024; // 64MB
+pub const ALLOWED_TYPES: &[&str] = &["image/png", "image/gif", "image/webp"];
 
 #[derive(Debug, Serialize)]
 pub struct EmojiResponse {
@@ -20,6 +33,19 @@ pub struct EmojiResponse {
     pub name: String,
     pub url: String,
     pub user_id: String,
+    pub animated: bool,
+}
+
+impl From<custom_emoji::Model> for EmojiResponse {
+    fn from(e: custom_emoji::Model) -> Self {
+        Self {
+            id: e.id,
+            name: e.name,
+            url: format!("/api/uploads/{}", e.upload_id),
+            user_id: e.user_id,
+            animated: e.animated,
+        }
+    }
 }
 
 /// List all custom emoji
@@ -32,20 +58,109 @@ pub async fn list_emoji(
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let result: Vec<EmojiResponse> = emojis
-        .into_iter()
-        .map(|e| EmojiResponse {
-            id: e.id,
-            name: e.name,
-            url: format!("/api/uploads/{}", e.upload_id),
-            user_id: e.user_id,
-        })
-        .collect();
+    Ok(Json(emojis.into_iter().map(EmojiResponse::from).collect()))
+}
 
-    Ok(Json(result))
+/// Emoji and sticker names: 1-32 letters, digits and `_`
+pub fn validate_name(kind: &str, name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > 32 {
+        return Err(format!("{kind} name must be 1-32 characters"));
+    }
+    if !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return Err(format!("{kind} name can only contain letters, numbers, and _"));
+    }
+    Ok(())
+}
+
+/// Verify and process an emoji or sticker image on the blocking pool. Static
+/// images may be at most `static_limit` bytes, animated ones `animated_limit`.
+pub async fn process_emoji_image(
+    data: Bytes,
+    declared_type: &str,
+    max_side: u32,
+    static_limit: usize,
+    animated_limit: usize,
+) -> Result<(ProcessedImage, bool), (StatusCode, String)> {
+    if data.len() > animated_limit.max(static_limit) {
+        return Err((StatusCode::BAD_REQUEST, format!("Image too large (max {}KB)", animated_limit / 1024)));
+    }
+    let content_type = crate::sniff::verify(declared_type, &data, ALLOWED_TYPES)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?
+        .to_string();
+
+    let (img, animated) = tokio::task::spawn_blocking(move || images::process_emoji(&data, &content_type, max_side))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Image processing failed: {e}")))?
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    if !animated && img.data.len() > static_limit {
+        return Err((StatusCode::BAD_REQUEST, format!("Image too large (max {}KB)", static_limit / 1024)));
+    }
+    Ok((img, animated))
+}
+
+/// Store one emoji for `user_id`, checking its name, image and the uploader's quota
+async fn insert_emoji(
+    state: &AppState,
+    user_id: &str,
+    name: String,
+    data: Bytes,
+    declared_type: &str,
+) -> Result<EmojiResponse, (StatusCode, String)> {
+    validate_name("Emoji", &name).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let existing = custom_emoji::Entity::find()
+        .filter(custom_emoji::Column::Name.eq(&name))
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+    if existing.is_some() {
+        return Err((StatusCode::CONFLICT, "Emoji name already taken".into()));
+    }
+
+    let (img, animated) =
+        process_emoji_image(data, declared_type, MAX_EMOJI_SIDE, MAX_EMOJI_SIZE, MAX_ANIMATED_EMOJI_SIZE).await?;
+
+    let policy = uploads::upload_policy(state, user_id, None).await?;
+    quotas::check(state, user_id, &policy, img.data.len() as u64).await?;
+
+    // Save file
+    let upload_id = Uuid::new_v4().to_string();
+    let ext = uploads::get_extension(&img.mime_type);
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+
+    let new_upload = upload::ActiveModel {
+        id: Set(upload_id.clone()),
+        user_id: Set(user_id.to_string()),
+        filename: Set(format!("{name}.{ext}")),
+        mime_type: Set(img.mime_type.clone()),
+        size: Set(img.data.len() as i64),
+        created_at: Set(now.clone()),
+        width: Set(Some(img.width as i32)),
+        height: Set(Some(img.height as i32)),
+        ..Default::default()
+    };
+
+    blobs::insert_upload(state, new_upload, img.data.into(), Vec::new()).await?;
+
+    let new_emoji = custom_emoji::Model {
+        id: Uuid::new_v4().to_string(),
+        name,
+        upload_id,
+        user_id: user_id.to_string(),
+        created_at: now,
+        animated,
+    };
+
+    custom_emoji::Entity::insert(new_emoji.clone().into_active_model().reset_all())
+        .exec(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+    Ok(new_emoji.into())
 }
 
-/// Upload a custom emoji (requires auth)
+/// Upload a custom emoji (requires auth). GIF and animated WebP emoji stay animated.
 pub async fn create_emoji(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -54,7 +169,7 @@ pub async fn create_emoji(
     let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
 
     let mut emoji_name: Option<String> = None;
-    let mut file_data: Option<(Vec<u8>, String)> = None;
+    let mut file_data: Option<(Bytes, String)> = None;
 
     while let Some(field) = multipart.next_field().await.map_err(|e| {
         (StatusCode::BAD_REQUEST, format!("Multipart error: {e}"))
@@ -68,90 +183,21 @@ pub async fn create_emoji(
             emoji_name = Some(text.trim().to_lowercase());
         } else if field_name == "file" || field_name == "image" {
             let content_type = field.content_type().unwrap_or("").to_string();
-            if !ALLOWED_TYPES.contains(&content_type.as_str()) {
+            if !ALLOWED_TYPES.contains(&crate::sniff::canonical(&content_type)) {
                 return Err((StatusCode::BAD_REQUEST, "Emoji must be PNG, GIF, or WebP".into()));
             }
             let data = field.bytes().await.map_err(|e| {
                 (StatusCode::BAD_REQUEST, format!("Read error: {e}"))
             })?;
-            if data.len() > MAX_EMOJI_SIZE {
-                return Err((StatusCode::BAD_REQUEST, "Emoji too large (max 256KB)".into()));
-            }
-            file_data = Some((data.to_vec(), content_type));
+            file_data = Some((data, content_type));
         }
     }
 
     let name = emoji_name.ok_or((StatusCode::BAD_REQUEST, "Missing emoji name".into()))?;
     let (data, content_type) = file_data.ok_or((StatusCode::BAD_REQUEST, "Missing emoji image".into()))?;
 
-    // Validate name
-    if name.is_empty() || name.len() > 32 {
-        return Err((StatusCode::BAD_REQUEST, "Emoji name must be 1-32 characters".into()));
-    }
-    if !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
-        return Err((StatusCode::BAD_REQUEST, "Emoji name can only contain letters, numbers, and _".into()));
-    }
-
-    // Check duplicate
-    let existing = custom_emoji::Entity::find()
-        .filter(custom_emoji::Column::Name.eq(&name))
-        .one(&state.db)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
-    if existing.is_some() {
-        return Err((StatusCode::CONFLICT, "Emoji name already taken".into()));
-    }
-
-    // Save file
-    let upload_id = Uuid::new_v4().to_string();
-    let ext = match content_type.as_str() {
-        "image/png" => "png",
-        "image/gif" => "gif",
-        "image/webp" => "webp",
-        _ => "png",
-    };
-
-    tokio::fs::write(format!("./uploads/{upload_id}.{ext}"), &data)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Write error: {e}")))?;
-
-    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
-
-    let new_upload = upload::ActiveModel {
-        id: Set(upload_id.clone()),
-        user_id: Set(claims.sub.clone()),
-        filename: Set(format!("{name}.{ext}")),
-        mime_type: Set(content_type.clone()),
-        size: Set(data.len() as i64),
-        created_at: Set(now.clone()),
-    };
-
-    upload::Entity::insert(new_upload)
-        .exec(&state.db)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
-
-    let emoji_id = Uuid::new_v4().to_string();
-
-    let new_emoji = custom_emoji::ActiveModel {
-        id: Set(emoji_id.clone()),
-        name: Set(name.clone()),
-        upload_id: Set(upload_id.clone()),
-        user_id: Set(claims.sub.clone()),
-        created_at: Set(now),
-    };
-
-    custom_emoji::Entity::insert(new_emoji)
-        .exec(&state.db)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
-
-    Ok((StatusCode::CREATED, Json(EmojiResponse {
-        id: emoji_id,
-        name,
-        url: format!("/api/uploads/{upload_id}"),
-        user_id: claims.sub,
-    })))
+    let emoji = insert_emoji(&state, &claims.sub, name, data, &content_type).await?;
+    Ok((StatusCode::CREATED, Json(emoji)))
 }
 
 /// Delete a custom emoji (owner only)
@@ -195,3 +241,119 @@ pub async fn serve_emoji_by_name(
     Ok(axum::response::Redirect::to(&format!("/api/uploads/{}", emoji.upload_id)))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    /// Comma-separated emoji names (default: all)
+    pub names: Option<String>,
+}
+
+/// Download emoji as a pack zip (requires auth)
+pub async fn export_emoji(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    auth::extract_claims(&state.jwt_secret, &headers)?;
+    let db_err = |e: DbErr| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"));
+
+    let mut find = custom_emoji::Entity::find().order_by_asc(custom_emoji::Column::Name);
+    if let Some(names) = &query.names {
+        let names: Vec<String> = names.split(',').map(|n| n.trim().to_lowercase()).collect();
+        find = find.filter(custom_emoji::Column::Name.is_in(names));
+    }
+    let emojis = find.limit(emoji_pack::MAX_PACK_EMOJI as u64).all(&state.db).await.map_err(db_err)?;
+
+    let mut pack = Vec::with_capacity(emojis.len());
+    for emoji in emojis {
+        let Some(row) = upload::Entity::find_by_id(&emoji.upload_id).one(&state.db).await.map_err(db_err)? else {
+            continue;
+        };
+        let data: Vec<Bytes> = state
+            .storage
+            .get_stream(&blobs::upload_key(&row), None)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Read error: {e}")))?
+            .try_collect()
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Read error: {e}")))?;
+        pack.push(PackEmoji {
+            name: emoji.name,
+            extension: uploads::get_extension(&row.mime_type).to_string(),
+            animated: emoji.animated,
+            data: data.concat(),
+        });
+    }
+
+    let zip = tokio::task::spawn_blocking(move || emoji_pack::write("emoji", &pack))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Export failed: {e}")))?
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (header::CONTENT_DISPOSITION, crate::sniff::attachment_disposition("emoji-pack.zip")),
+        ],
+        zip,
+    )
+        .into_response())
+}
+
+#[derive(Debug, Serialize)]
+pub struct SkippedEmoji {
+    pub name: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportResult {
+    pub imported: Vec<EmojiResponse>,
+    pub skipped: Vec<SkippedEmoji>,
+}
+
+/// Add every emoji in a pack zip (requires MANAGE_EMOJIS). Emoji whose name is
+/// taken or whose image is rejected are skipped and reported, not fatal.
+pub async fn import_emoji(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Result<Json<ImportResult>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    if !roles::user_has_permission(&state, &claims.sub, Permissions::MANAGE_EMOJIS)
+        .await
+        .map_err(|s| (s, "Permission check failed".to_string()))?
+    {
+        return Err((StatusCode::FORBIDDEN, "Importing emoji requires MANAGE_EMOJIS".into()));
+    }
+
+    let mut zip = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Multipart error: {e}")))?
+    {
+        if field.name() == Some("file") {
+            zip = Some(field.bytes().await.map_err(|e| (StatusCode::BAD_REQUEST, format!("Read error: {e}")))?);
+        }
+    }
+    let zip = zip.ok_or((StatusCode::BAD_REQUEST, "No file field found".to_string()))?;
+
+    let limit = MAX_ANIMATED_EMOJI_SIZE.max(MAX_EMOJI_SIZE) as u64;
+    let pack = tokio::task::spawn_blocking(move || emoji_pack::read(&zip, limit))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Import failed: {e}")))?
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let mut result = ImportResult { imported: Vec::new(), skipped: Vec::new() };
+    for emoji in pack {
+        let name = emoji.name.trim().to_lowercase();
+        // The pack's file names aren't trusted; the bytes decide the type
+        let declared = crate::sniff::sniff(&emoji.data).unwrap_or("application/octet-stream");
+        match insert_emoji(&state, &claims.sub, name.clone(), emoji.data.into(), declared).await {
+            Ok(imported) => result.imported.push(imported),
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e)) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+            Err((_, reason)) => result.skipped.push(SkippedEmoji { name, reason }),
+        }
+    }
+    Ok(Json(result))
+}
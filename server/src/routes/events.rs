@@ -0,0 +1,559 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use sea_orm::*;
+use uuid::Uuid;
+
+use crate::entities::{channel, event_rsvp, scheduled_event, server, server_member, user};
+use crate::models::{
+    CreateEventRequest, EventInfo, EventRsvpInfo, EventsQuery, Permissions, RsvpRequest, ScheduledEvent,
+    UpdateEventRequest, WsServerMessage,
+};
+use crate::routes::audit_logs::create_audit_log;
+use crate::routes::{auth, roles};
+use crate::state::AppState;
+
+const MAX_TITLE_LENGTH: usize = 100;
+const MAX_DESCRIPTION_LENGTH: usize = 1000;
+const MAX_LOCATION_LENGTH: usize = 100;
+/// How far ahead events can be scheduled
+const MAX_LEAD_DAYS: i64 = 365;
+/// Events without an end time are completed this long after they start
+const DEFAULT_DURATION_HOURS: i64 = 12;
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+fn db_err(e: DbErr) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"))
+}
+
+/// Accept RFC 3339 or the `YYYY-MM-DD HH:MM:SS` (UTC) format times are stored in
+fn parse_time(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s)
+        .map(|t| t.with_timezone(&Utc))
+        .or_else(|_| NaiveDateTime::parse_from_str(s, TIME_FORMAT).map(|t| t.and_utc()))
+        .ok()
+}
+
+fn format_time(t: DateTime<Utc>) -> String {
+    t.format(TIME_FORMAT).to_string()
+}
+
+/// Check an event's schedule and return it normalized to the stored format
+fn validate_schedule(
+    starts_at: &str,
+    ends_at: Option<&str>,
+    require_future: bool,
+) -> Result<(String, Option<String>), (StatusCode, String)> {
+    let now = Utc::now();
+    let start = parse_time(starts_at).ok_or((StatusCode::BAD_REQUEST, "Invalid starts_at".to_string()))?;
+    if require_future && start <= now {
+        return Err((StatusCode::BAD_REQUEST, "Events must start in the future".into()));
+    }
+    if start > now + Duration::days(MAX_LEAD_DAYS) {
+        return Err((StatusCode::BAD_REQUEST, format!("Events can be at most {MAX_LEAD_DAYS} days ahead")));
+    }
+    let end = match ends_at {
+        Some(s) => {
+            let end = parse_time(s).ok_or((StatusCode::BAD_REQUEST, "Invalid ends_at".to_string()))?;
+            if end <= start {
+                return Err((StatusCode::BAD_REQUEST, "Events must end after they start".into()));
+            }
+            Some(format_time(end))
+        }
+        None => None,
+    };
+    Ok((format_time(start), end))
+}
+
+/// An event takes place either in one of the server's voice channels or somewhere else
+async fn validate_place(
+    state: &AppState,
+    server_id: &str,
+    channel_id: Option<&str>,
+    location: Option<&str>,
+) -> Result<(), (StatusCode, String)> {
+    match (channel_id, location) {
+        (Some(channel_id), None) => {
+            let is_voice = channel::Entity::find_by_id(channel_id)
+                .one(&state.db)
+                .await
+                .map_err(db_err)?
+                .is_some_and(|c| c.server_id == server_id && c.channel_type == "voice");
+            if !is_voice {
+                return Err((StatusCode::BAD_REQUEST, "Not a voice channel of this server".into()));
+            }
+            Ok(())
+        }
+        (None, Some(location)) if !location.is_empty() && location.chars().count() <= MAX_LOCATION_LENGTH => Ok(()),
+        (None, Some(_)) => Err((
+            StatusCode::BAD_REQUEST,
+            format!("Locations must be 1-{MAX_LOCATION_LENGTH} characters"),
+        )),
+        _ => Err((StatusCode::BAD_REQUEST, "Give either a voice channel_id or a location".into())),
+    }
+}
+
+fn validate_text(title: &str, description: &str) -> Result<(), (StatusCode, String)> {
+    if title.is_empty() || title.chars().count() > MAX_TITLE_LENGTH {
+        return Err((StatusCode::BAD_REQUEST, format!("Titles must be 1-{MAX_TITLE_LENGTH} characters")));
+    }
+    if description.chars().count() > MAX_DESCRIPTION_LENGTH {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Descriptions are at most {MAX_DESCRIPTION_LENGTH} characters"),
+        ));
+    }
+    Ok(())
+}
+
+async fn require_member(state: &AppState, server_id: &str, user_id: &str) -> Result<(), (StatusCode, String)> {
+    server_member::Entity::find_by_id((server_id.to_string(), user_id.to_string()))
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .map(|_| ())
+        .ok_or((StatusCode::FORBIDDEN, "You are not a member of this server".to_string()))
+}
+
+/// Owner of the server or MANAGE_EVENTS
+async fn can_manage_events(state: &AppState, server_id: &str, user_id: &str) -> Result<bool, (StatusCode, String)> {
+    let srv = server::Entity::find_by_id(server_id)
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .ok_or((StatusCode::NOT_FOUND, "Server not found".to_string()))?;
+    Ok(srv.owner_id == user_id
+        || roles::user_has_server_permission(state, server_id, user_id, Permissions::MANAGE_EVENTS)
+            .await
+            .map_err(|s| (s, "Permission check failed".to_string()))?)
+}
+
+async fn find_event(
+    state: &AppState,
+    server_id: &str,
+    event_id: &str,
+) -> Result<scheduled_event::Model, (StatusCode, String)> {
+    scheduled_event::Entity::find_by_id(event_id)
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .filter(|e| e.server_id == server_id)
+        .ok_or((StatusCode::NOT_FOUND, "Event not found".to_string()))
+}
+
+/// GET /api/servers/{id}/events — upcoming and running events, soonest first
+pub async fn list_events(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(server_id): Path<String>,
+    Query(query): Query<EventsQuery>,
+) -> Result<Json<Vec<EventInfo>>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    require_member(&state, &server_id, &claims.sub).await?;
+
+    let mut select = scheduled_event::Entity::find()
+        .filter(scheduled_event::Column::ServerId.eq(&server_id))
+        .order_by_asc(scheduled_event::Column::StartsAt);
+    if !query.include_past {
+        select = select.filter(scheduled_event::Column::Status.is_in(["scheduled", "active"]));
+    }
+    let events = select.all(&state.db).await.map_err(db_err)?;
+
+    let rsvps = event_rsvp::Entity::find()
+        .filter(event_rsvp::Column::EventId.is_in(events.iter().map(|e| e.id.clone())))
+        .all(&state.db)
+        .await
+        .map_err(db_err)?;
+    let mut counts: HashMap<&str, (u64, u64)> = HashMap::new();
+    let mut mine: HashMap<&str, String> = HashMap::new();
+    for r in &rsvps {
+        let entry = counts.entry(r.event_id.as_str()).or_default();
+        if r.status == "going" {
+            entry.0 += 1;
+        } else {
+            entry.1 += 1;
+        }
+        if r.user_id == claims.sub {
+            mine.insert(r.event_id.as_str(), r.status.clone());
+        }
+    }
+
+    Ok(Json(
+        events
+            .iter()
+            .map(|e| {
+                let (going, interested) = counts.get(e.id.as_str()).copied().unwrap_or_default();
+                EventInfo {
+                    event: e.clone(),
+                    going,
+                    interested,
+                    rsvp: mine.get(e.id.as_str()).cloned(),
+                }
+            })
+            .collect(),
+    ))
+}
+
+/// POST /api/servers/{id}/events (owner or MANAGE_EVENTS)
+pub async fn create_event(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(server_id): Path<String>,
+    Json(req): Json<CreateEventRequest>,
+) -> Result<(StatusCode, Json<ScheduledEvent>), (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    if !can_manage_events(&state, &server_id, &claims.sub).await? {
+        return Err((StatusCode::FORBIDDEN, "Scheduling events requires MANAGE_EVENTS".into()));
+    }
+
+    let title = req.title.trim().to_string();
+    let description = req.description.trim().to_string();
+    validate_text(&title, &description)?;
+    let (starts_at, ends_at) = validate_schedule(&req.starts_at, req.ends_at.as_deref(), true)?;
+    let location = req.location.map(|l| l.trim().to_string());
+    validate_place(&state, &server_id, req.channel_id.as_deref(), location.as_deref()).await?;
+
+    let event = ScheduledEvent {
+        id: Uuid::new_v4().to_string(),
+        server_id: server_id.clone(),
+        creator_id: Some(claims.sub.clone()),
+        title,
+        description,
+        starts_at,
+        ends_at,
+        channel_id: req.channel_id,
+        location,
+        status: "scheduled".to_string(),
+        created_at: format_time(Utc::now()),
+    };
+    scheduled_event::Entity::insert(event.clone().into_active_model().reset_all())
+        .exec(&state.db)
+        .await
+        .map_err(db_err)?;
+
+    create_audit_log(
+        &state.db,
+        &claims.sub,
+        &claims.username,
+        "CREATE_EVENT",
+        Some(&event.id),
+        Some(&event.title),
+        Some(&format!("Starts {}", event.starts_at)),
+    )
+    .await;
+
+    state.broadcast_global(Some(&server_id), WsServerMessage::EventUpdate { event: event.clone() });
+    Ok((StatusCode::CREATED, Json(event)))
+}
+
+/// PATCH /api/servers/{id}/events/{event_id} — edit, start, end or cancel an event
+/// (its creator, the owner or MANAGE_EVENTS)
+pub async fn update_event(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((server_id, event_id)): Path<(String, String)>,
+    Json(req): Json<UpdateEventRequest>,
+) -> Result<Json<ScheduledEvent>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let event = find_event(&state, &server_id, &event_id).await?;
+    if event.creator_id.as_deref() != Some(claims.sub.as_str())
+        && !can_manage_events(&state, &server_id, &claims.sub).await?
+    {
+        return Err((StatusCode::FORBIDDEN, "Editing events requires MANAGE_EVENTS".into()));
+    }
+    if matches!(event.status.as_str(), "completed" | "cancelled") {
+        return Err((StatusCode::CONFLICT, "This event is over".into()));
+    }
+
+    let mut next = event.clone();
+    if let Some(title) = req.title {
+        next.title = title.trim().to_string();
+    }
+    if let Some(description) = req.description {
+        next.description = description.trim().to_string();
+    }
+    validate_text(&next.title, &next.description)?;
+
+    if req.starts_at.is_some() || req.ends_at.is_some() {
+        if event.status != "scheduled" {
+            return Err((StatusCode::CONFLICT, "Only events that haven't started can be rescheduled".into()));
+        }
+        let starts_at = req.starts_at.as_deref().unwrap_or(&event.starts_at);
+        let ends_at = req.ends_at.as_deref().or(event.ends_at.as_deref()).filter(|e| !e.is_empty());
+        (next.starts_at, next.ends_at) = validate_schedule(starts_at, ends_at, req.starts_at.is_some())?;
+    }
+
+    // Moving the event replaces where it was
+    if req.channel_id.is_some() || req.location.is_some() {
+        next.channel_id = req.channel_id.filter(|c| !c.is_empty());
+        next.location = req.location.map(|l| l.trim().to_string()).filter(|l| !l.is_empty());
+        validate_place(&state, &server_id, next.channel_id.as_deref(), next.location.as_deref()).await?;
+    }
+
+    let mut starting = false;
+    if let Some(status) = req.status.as_deref().filter(|s| *s != event.status) {
+        match (event.status.as_str(), status) {
+            ("scheduled", "active") => {
+                starting = true;
+                next.starts_at = format_time(Utc::now());
+            }
+            ("scheduled", "cancelled") | ("active", "completed") => {}
+            _ => return Err((StatusCode::BAD_REQUEST, format!("Cannot go from {} to {status}", event.status))),
+        }
+        next.status = status.to_string();
+    }
+
+    // Write only what changed, and only if nobody moved the event on since we read it
+    let changes = [
+        (scheduled_event::Column::Title, next.title != event.title, Value::from(next.title.clone())),
+        (scheduled_event::Column::Description, next.description != event.description, next.description.clone().into()),
+        (scheduled_event::Column::StartsAt, next.starts_at != event.starts_at, next.starts_at.clone().into()),
+        (scheduled_event::Column::EndsAt, next.ends_at != event.ends_at, next.ends_at.clone().into()),
+        (scheduled_event::Column::ChannelId, next.channel_id != event.channel_id, next.channel_id.clone().into()),
+        (scheduled_event::Column::Location, next.location != event.location, next.location.clone().into()),
+    ];
+    let txn = state.db.begin().await.map_err(db_err)?;
+    if changes.iter().any(|(_, changed, _)| *changed) {
+        let mut query = scheduled_event::Entity::update_many()
+            .filter(scheduled_event::Column::Id.eq(&next.id))
+            .filter(scheduled_event::Column::Status.eq(&event.status));
+        for (column, _, value) in changes.into_iter().filter(|(_, changed, _)| *changed) {
+            query = query.col_expr(column, sea_query::Expr::value(value));
+        }
+        if query.exec(&txn).await.map_err(db_err)?.rows_affected == 0 {
+            return Err((StatusCode::CONFLICT, "The event changed meanwhile, try again".into()));
+        }
+    }
+    if next.status != event.status && !transition(&txn, &next.id, &event.status, &next.status).await.map_err(db_err)? {
+        return Err((StatusCode::CONFLICT, "The event changed meanwhile, try again".into()));
+    }
+    txn.commit().await.map_err(db_err)?;
+
+    create_audit_log(
+        &state.db,
+        &claims.sub,
+        &claims.username,
+        if next.status == "cancelled" { "CANCEL_EVENT" } else { "UPDATE_EVENT" },
+        Some(&next.id),
+        Some(&next.title),
+        req.status.as_deref(),
+    )
+    .await;
+
+    state.broadcast_global(Some(&server_id), WsServerMessage::EventUpdate { event: next.clone() });
+    if starting {
+        notify_starting(&state, &next).await;
+    }
+    Ok(Json(next))
+}
+
+/// DELETE /api/servers/{id}/events/{event_id} (its creator, the owner or MANAGE_EVENTS)
+pub async fn delete_event(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((server_id, event_id)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let event = find_event(&state, &server_id, &event_id).await?;
+    if event.creator_id.as_deref() != Some(claims.sub.as_str())
+        && !can_manage_events(&state, &server_id, &claims.sub).await?
+    {
+        return Err((StatusCode::FORBIDDEN, "Deleting events requires MANAGE_EVENTS".into()));
+    }
+
+    scheduled_event::Entity::delete_by_id(&event_id)
+        .exec(&state.db)
+        .await
+        .map_err(db_err)?;
+
+    create_audit_log(
+        &state.db,
+        &claims.sub,
+        &claims.username,
+        "DELETE_EVENT",
+        Some(&event_id),
+        Some(&event.title),
+        None,
+    )
+    .await;
+
+    state.broadcast_global(Some(&server_id), WsServerMessage::EventDelete { server_id: server_id.clone(), event_id });
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// PUT /api/servers/{id}/events/{event_id}/rsvp — `going` or `interested`
+pub async fn set_rsvp(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((server_id, event_id)): Path<(String, String)>,
+    Json(req): Json<RsvpRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    require_member(&state, &server_id, &claims.sub).await?;
+    let event = find_event(&state, &server_id, &event_id).await?;
+    if !matches!(req.status.as_str(), "going" | "interested") {
+        return Err((StatusCode::BAD_REQUEST, "status must be going or interested".into()));
+    }
+    if matches!(event.status.as_str(), "completed" | "cancelled") {
+        return Err((StatusCode::CONFLICT, "This event is over".into()));
+    }
+
+    event_rsvp::Entity::insert(event_rsvp::ActiveModel {
+        event_id: Set(event_id),
+        user_id: Set(claims.sub),
+        status: Set(req.status),
+        created_at: Set(format_time(Utc::now())),
+    })
+    .on_conflict(
+        sea_query::OnConflict::columns([event_rsvp::Column::EventId, event_rsvp::Column::UserId])
+            .update_column(event_rsvp::Column::Status)
+            .to_owned(),
+    )
+    .exec(&state.db)
+    .await
+    .map_err(db_err)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// DELETE /api/servers/{id}/events/{event_id}/rsvp
+pub async fn delete_rsvp(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((server_id, event_id)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    find_event(&state, &server_id, &event_id).await?;
+    event_rsvp::Entity::delete_by_id((event_id, claims.sub))
+        .exec(&state.db)
+        .await
+        .map_err(db_err)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /api/servers/{id}/events/{event_id}/rsvps — who's coming
+pub async fn list_rsvps(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((server_id, event_id)): Path<(String, String)>,
+) -> Result<Json<Vec<EventRsvpInfo>>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    require_member(&state, &server_id, &claims.sub).await?;
+    find_event(&state, &server_id, &event_id).await?;
+
+    let rows = event_rsvp::Entity::find()
+        .filter(event_rsvp::Column::EventId.eq(&event_id))
+        .find_also_related(user::Entity)
+        .order_by_asc(event_rsvp::Column::CreatedAt)
+        .all(&state.db)
+        .await
+        .map_err(db_err)?;
+    Ok(Json(
+        rows.into_iter()
+            .filter_map(|(r, u)| {
+                u.map(|u| EventRsvpInfo {
+                    user_id: u.id,
+                    display_name: u.display_name,
+                    avatar_url: u.avatar_url,
+                    status: r.status,
+                })
+            })
+            .collect(),
+    ))
+}
+
+/// Tell everyone who RSVP'd that the event has begun
+async fn notify_starting(state: &AppState, event: &ScheduledEvent) {
+    let user_ids: Vec<String> = event_rsvp::Entity::find()
+        .filter(event_rsvp::Column::EventId.eq(&event.id))
+        .all(&state.db)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|r| r.user_id)
+        .collect();
+    state.send_to_users(user_ids, WsServerMessage::EventStarting { event: event.clone() });
+}
+
+/// Start events whose time has come and complete finished ones (call from a background task).
+/// Each transition is a conditional update, so with several instances only one announces it.
+pub async fn run_scheduler(state: &AppState) -> Result<(), DbErr> {
+    let now = Utc::now();
+    let now_str = format_time(now);
+
+    let due = scheduled_event::Entity::find()
+        .filter(scheduled_event::Column::Status.eq("scheduled"))
+        .filter(scheduled_event::Column::StartsAt.lte(&now_str))
+        .all(&state.db)
+        .await?;
+    for mut event in due {
+        if !transition(&state.db, &event.id, "scheduled", "active").await? {
+            continue;
+        }
+        event.status = "active".to_string();
+        state.broadcast_global(Some(&event.server_id), WsServerMessage::EventUpdate { event: event.clone() });
+        notify_starting(state, &event).await;
+    }
+
+    let running = scheduled_event::Entity::find()
+        .filter(scheduled_event::Column::Status.eq("active"))
+        .all(&state.db)
+        .await?;
+    for mut event in running {
+        let end = match event.ends_at.as_deref() {
+            Some(end) => parse_time(end),
+            None => parse_time(&event.starts_at).map(|s| s + Duration::hours(DEFAULT_DURATION_HOURS)),
+        };
+        if end.is_none_or(|end| end > now) || !transition(&state.db, &event.id, "active", "completed").await? {
+            continue;
+        }
+        event.status = "completed".to_string();
+        let server_id = event.server_id.clone();
+        state.broadcast_global(Some(&server_id), WsServerMessage::EventUpdate { event });
+    }
+    Ok(())
+}
+
+async fn transition<C: ConnectionTrait>(db: &C, event_id: &str, from: &str, to: &str) -> Result<bool, DbErr> {
+    let result = scheduled_event::Entity::update_many()
+        .col_expr(scheduled_event::Column::Status, sea_query::Expr::value(to))
+        .filter(scheduled_event::Column::Id.eq(event_id))
+        .filter(scheduled_event::Column::Status.eq(from))
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected == 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedules_are_normalized_and_checked() {
+        let start = Utc::now() + Duration::hours(1);
+        let (starts_at, ends_at) = validate_schedule(&start.to_rfc3339(), None, true).unwrap();
+        assert_eq!(starts_at, format_time(start));
+        assert_eq!(ends_at, None);
+
+        let end = format_time(start + Duration::hours(2));
+        assert_eq!(validate_schedule(&starts_at, Some(&end), true).unwrap().1, Some(end));
+
+        let past = format_time(Utc::now() - Duration::hours(1));
+        assert!(validate_schedule(&past, None, true).is_err());
+        assert!(validate_schedule(&starts_at, Some(&past), false).is_err());
+        assert!(validate_schedule("tomorrow", None, true).is_err());
+    }
+
+    #[tokio::test]
+    async fn managing_events_in_one_server_grants_nothing_in_another() {
+        let state = AppState::for_tests().await;
+        roles::seed_role_in_one_server(&state, "u", "a", "b", Permissions::MANAGE_EVENTS).await;
+
+        assert!(can_manage_events(&state, "a", "u").await.unwrap());
+        assert!(!can_manage_events(&state, "b", "u").await.unwrap());
+    }
+}
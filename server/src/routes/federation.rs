@@ -9,8 +9,9 @@ use sea_orm::prelude::Expr;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::entities::{channel, federated_channel, federation_peer, message};
+use crate::entities::{channel, channel_follow, federated_channel, federation_peer, message};
 use crate::models::{FederatedChannel, FederationPeer, Permissions, WsServerMessage};
+use crate::routes::announcements::MAX_FOLLOWERS;
 use crate::routes::auth;
 use crate::routes::roles::user_has_permission;
 use crate::state::AppState;
@@ -54,6 +55,24 @@ pub struct FederatedMessageRequest {
     pub server_name: String,
 }
 
+/// Follow an announcement channel of a peer into a local channel
+#[derive(Debug, Deserialize)]
+pub struct FollowRemoteRequest {
+    pub peer_id: String,
+    pub remote_channel_id: String,
+    pub local_channel_id: String,
+}
+
+/// Longest channel id a peer may give for its follower channel
+const MAX_REMOTE_ID_LENGTH: usize = 64;
+
+/// Sent between peers to start or stop following `channel_id`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PeerFollowRequest {
+    pub channel_id: String,
+    pub follower_channel_id: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct FederationStatus {
     pub peers: Vec<FederationPeer>,
@@ -70,6 +89,43 @@ fn generate_shared_secret() -> String {
     format!("fed_{}", secret)
 }
 
+/// URL of `path` on a peer
+pub fn peer_url(peer: &federation_peer::Model, path: &str) -> String {
+    let scheme = if peer.port == 443 { "https" } else { "http" };
+    format!("{}://{}:{}{}", scheme, peer.host, peer.port, path)
+}
+
+pub fn peer_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new())
+}
+
+/// The active peer whose shared secret is in `Authorization: Federation <secret>`
+async fn authenticate_peer(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<federation_peer::Model, (StatusCode, String)> {
+    let auth_header = headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .ok_or((StatusCode::UNAUTHORIZED, "Missing Authorization header".into()))?;
+
+    let secret = auth_header.strip_prefix("Federation ").ok_or((
+        StatusCode::UNAUTHORIZED,
+        "Use: Authorization: Federation <shared_secret>".into(),
+    ))?;
+
+    federation_peer::Entity::find()
+        .filter(federation_peer::Column::SharedSecret.eq(secret))
+        .filter(federation_peer::Column::Status.eq("active"))
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+        .ok_or((StatusCode::UNAUTHORIZED, "Invalid or inactive federation secret".into()))
+}
+
 // ─── Routes ───
 
 /// GET /api/federation — get federation status (peers + linked channels)
@@ -299,6 +355,7 @@ pub async fn link_channel(
         peer_id: Set(req.peer_id.clone()),
         remote_channel_id: Set(req.remote_channel_id.clone()),
         created_at: Set(now.clone()),
+        mode: Set("link".to_string()),
     };
 
     federated_channel::Entity::insert(new_link)
@@ -312,6 +369,7 @@ pub async fn link_channel(
         peer_id: req.peer_id,
         remote_channel_id: req.remote_channel_id,
         created_at: now,
+        mode: "link".to_string(),
     };
 
     Ok((StatusCode::CREATED, Json(link)))
@@ -331,40 +389,182 @@ pub async fn unlink_channel(
         return Err((StatusCode::FORBIDDEN, "MANAGE_SERVER required".into()));
     }
 
+    let link = federated_channel::Entity::find_by_id(&link_id)
+        .find_also_related(federation_peer::Entity)
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
     federated_channel::Entity::delete_by_id(&link_id)
         .exec(&state.db)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
 
+    // Let the publisher stop sending; it also drops followers that answer 404
+    if let Some((link, Some(peer))) = link.filter(|(l, _)| l.mode == "follow") {
+        let sent = peer_client()
+            .delete(peer_url(&peer, "/api/federation/follow"))
+            .header("Authorization", format!("Federation {}", peer.shared_secret))
+            .json(&PeerFollowRequest {
+                channel_id: link.remote_channel_id,
+                follower_channel_id: link.local_channel_id,
+            })
+            .send()
+            .await;
+        if let Err(e) = sent {
+            tracing::warn!("Failed to unfollow on peer {}: {e}", peer.name);
+        }
+    }
+
     Ok(StatusCode::NO_CONTENT)
 }
 
-/// POST /api/federation/message — receive a message from a federated server
-/// Authenticated via shared_secret in the Authorization header: `Federation <secret>`
-pub async fn receive_federated_message(
+/// POST /api/federation/follows — follow an announcement channel of a peer into a local channel
+pub async fn follow_remote_channel(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Json(req): Json<FederatedMessageRequest>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    // Authenticate via federation shared secret
-    let auth_header = headers
-        .get("authorization")
-        .and_then(|v| v.to_str().ok())
-        .ok_or((StatusCode::UNAUTHORIZED, "Missing Authorization header".into()))?;
+    Json(req): Json<FollowRemoteRequest>,
+) -> Result<(StatusCode, Json<FederatedChannel>), (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    if !user_has_permission(&state, &claims.sub, Permissions::MANAGE_SERVER)
+        .await
+        .map_err(|e| (e, "Permission check failed".to_string()))?
+    {
+        return Err((StatusCode::FORBIDDEN, "MANAGE_SERVER required".into()));
+    }
 
-    let secret = auth_header.strip_prefix("Federation ").ok_or((
-        StatusCode::UNAUTHORIZED,
-        "Use: Authorization: Federation <shared_secret>".into(),
-    ))?;
+    let peer = federation_peer::Entity::find_by_id(&req.peer_id)
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+        .filter(|p| p.status == "active")
+        .ok_or((StatusCode::NOT_FOUND, "Active federation peer not found".to_string()))?;
 
-    // Validate shared secret and find peer
-    let peer = federation_peer::Entity::find()
-        .filter(federation_peer::Column::SharedSecret.eq(secret))
-        .filter(federation_peer::Column::Status.eq("active"))
+    let is_text = channel::Entity::find_by_id(&req.local_channel_id)
         .one(&state.db)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
-        .ok_or((StatusCode::UNAUTHORIZED, "Invalid or inactive federation secret".into()))?;
+        .is_some_and(|c| c.channel_type == "text");
+    if !is_text {
+        return Err((StatusCode::BAD_REQUEST, "Announcements can only be followed into a text channel".into()));
+    }
+
+    // The peer checks that the channel is an announcement channel and records us as a follower
+    let response = peer_client()
+        .post(peer_url(&peer, "/api/federation/follow"))
+        .header("Authorization", format!("Federation {}", peer.shared_secret))
+        .json(&PeerFollowRequest {
+            channel_id: req.remote_channel_id.clone(),
+            follower_channel_id: req.local_channel_id.clone(),
+        })
+        .send()
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Peer unreachable: {e}")))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let reason = response.text().await.unwrap_or_default();
+        return Err((StatusCode::BAD_GATEWAY, format!("Peer refused the follow ({status}): {reason}")));
+    }
+
+    let link = FederatedChannel {
+        id: Uuid::new_v4().to_string(),
+        local_channel_id: req.local_channel_id,
+        peer_id: peer.id,
+        remote_channel_id: req.remote_channel_id,
+        created_at: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        mode: "follow".to_string(),
+    };
+    federated_channel::Entity::insert(link.clone().into_active_model().reset_all())
+        .exec(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+    Ok((StatusCode::CREATED, Json(link)))
+}
+
+/// POST /api/federation/follow — a peer starts following one of our announcement channels
+pub async fn receive_follow(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<PeerFollowRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let peer = authenticate_peer(&state, &headers).await?;
+
+    // Only channels whose managers opted in; others look the same as missing ones
+    let followable = channel::Entity::find_by_id(&req.channel_id)
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+        .is_some_and(|c| c.channel_type == "announcement" && c.remote_follows);
+    if !followable {
+        return Err((StatusCode::NOT_FOUND, "No announcement channel with this id".into()));
+    }
+    if req.follower_channel_id.is_empty() || req.follower_channel_id.len() > MAX_REMOTE_ID_LENGTH {
+        return Err((StatusCode::BAD_REQUEST, "Invalid follower channel id".into()));
+    }
+
+    let followers = channel_follow::Entity::find()
+        .filter(channel_follow::Column::SourceChannelId.eq(&req.channel_id))
+        .count(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+    if followers >= MAX_FOLLOWERS {
+        return Err((StatusCode::BAD_REQUEST, format!("Channels can have at most {MAX_FOLLOWERS} followers")));
+    }
+
+    channel_follow::Entity::insert(channel_follow::ActiveModel {
+        id: Set(Uuid::new_v4().to_string()),
+        source_channel_id: Set(req.channel_id),
+        target_channel_id: Set(None),
+        peer_id: Set(Some(peer.id)),
+        remote_channel_id: Set(Some(req.follower_channel_id)),
+        created_by: Set(None),
+        created_at: Set(chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()),
+    })
+    .on_conflict(
+        sea_query::OnConflict::columns([
+            channel_follow::Column::SourceChannelId,
+            channel_follow::Column::PeerId,
+            channel_follow::Column::RemoteChannelId,
+        ])
+        .do_nothing()
+        .to_owned(),
+    )
+    .do_nothing()
+    .exec(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// DELETE /api/federation/follow — a peer stops following one of our announcement channels
+pub async fn receive_unfollow(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<PeerFollowRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let peer = authenticate_peer(&state, &headers).await?;
+
+    channel_follow::Entity::delete_many()
+        .filter(channel_follow::Column::SourceChannelId.eq(&req.channel_id))
+        .filter(channel_follow::Column::PeerId.eq(&peer.id))
+        .filter(channel_follow::Column::RemoteChannelId.eq(&req.follower_channel_id))
+        .exec(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /api/federation/message — receive a message from a federated server
+/// Authenticated via shared_secret in the Authorization header: `Federation <secret>`
+pub async fn receive_federated_message(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<FederatedMessageRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let peer = authenticate_peer(&state, &headers).await?;
 
     // Find local channel linked to the remote channel_id from this peer
     let link = federated_channel::Entity::find()
@@ -415,10 +615,12 @@ pub async fn receive_federated_message(
         is_bot: false,
         reply_to: None,
         replied_message: None,
+        attachments: Vec::new(),
+        sticker: None,
+        post_id: None,
     };
 
-    let tx = state.get_channel_tx(&link.local_channel_id);
-    let _ = tx.send(broadcast_msg);
+    state.publish_channel(&link.local_channel_id, broadcast_msg);
 
     Ok(Json(serde_json::json!({ "id": msg_id })))
 }
@@ -0,0 +1,614 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use sea_orm::*;
+use uuid::Uuid;
+
+use crate::blobs;
+use crate::entities::{channel, forum_post, forum_post_tag, forum_tag, message, user};
+use crate::models::{
+    CreatePostRequest, ForumPost, ForumPostInfo, ForumTag, Permissions, PostSort, PostsQuery, UpdateForumTagsRequest,
+    UpdatePostRequest, WsServerMessage,
+};
+use crate::permissions::check_channel_permission;
+use crate::routes::{auth, members};
+use crate::state::AppState;
+
+const MAX_TITLE_LENGTH: usize = 100;
+const MAX_CONTENT_LENGTH: usize = 2000;
+const MAX_TAG_NAME_LENGTH: usize = 20;
+const MAX_FORUM_TAGS: usize = 20;
+const MAX_POST_TAGS: usize = 5;
+const DEFAULT_PAGE: u64 = 25;
+const MAX_PAGE: u64 = 100;
+
+fn db_err(e: DbErr) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"))
+}
+
+async fn require(
+    state: &AppState,
+    user_id: &str,
+    channel_id: &str,
+    perm: Permissions,
+) -> Result<(), (StatusCode, String)> {
+    let allowed = check_channel_permission(state, user_id, channel_id, perm)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Permission error: {e}")))?;
+    if !allowed {
+        return Err((StatusCode::FORBIDDEN, "Missing permissions in this forum".into()));
+    }
+    Ok(())
+}
+
+async fn can(state: &AppState, user_id: &str, channel_id: &str, perm: Permissions) -> bool {
+    check_channel_permission(state, user_id, channel_id, perm).await.unwrap_or(false)
+}
+
+async fn forum_channel(state: &AppState, channel_id: &str) -> Result<channel::Model, (StatusCode, String)> {
+    let ch = channel::Entity::find_by_id(channel_id)
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .ok_or((StatusCode::NOT_FOUND, "Channel not found".to_string()))?;
+    if ch.channel_type != "forum" {
+        return Err((StatusCode::BAD_REQUEST, "Not a forum channel".into()));
+    }
+    Ok(ch)
+}
+
+async fn find_post(state: &AppState, channel_id: &str, post_id: &str) -> Result<ForumPost, (StatusCode, String)> {
+    forum_post::Entity::find_by_id(post_id)
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .filter(|p| p.channel_id == channel_id)
+        .ok_or((StatusCode::NOT_FOUND, "Post not found".to_string()))
+}
+
+fn validate_title(title: &str) -> Result<(), (StatusCode, String)> {
+    if title.is_empty() || title.chars().count() > MAX_TITLE_LENGTH {
+        return Err((StatusCode::BAD_REQUEST, format!("Titles must be 1-{MAX_TITLE_LENGTH} characters")));
+    }
+    Ok(())
+}
+
+/// Dedupe `tag_ids` and check they're tags of this forum
+async fn validate_tag_ids(
+    state: &AppState,
+    channel_id: &str,
+    tag_ids: &[String],
+) -> Result<Vec<String>, (StatusCode, String)> {
+    let forum_tags: HashSet<String> = forum_tag::Entity::find()
+        .filter(forum_tag::Column::ChannelId.eq(channel_id))
+        .all(&state.db)
+        .await
+        .map_err(db_err)?
+        .into_iter()
+        .map(|t| t.id)
+        .collect();
+    check_tag_ids(tag_ids, &forum_tags)
+}
+
+fn check_tag_ids(tag_ids: &[String], forum_tags: &HashSet<String>) -> Result<Vec<String>, (StatusCode, String)> {
+    let mut ids: Vec<String> = Vec::new();
+    for id in tag_ids {
+        if !ids.contains(id) {
+            ids.push(id.clone());
+        }
+    }
+    if ids.len() > MAX_POST_TAGS {
+        return Err((StatusCode::BAD_REQUEST, format!("Posts can have at most {MAX_POST_TAGS} tags")));
+    }
+    if !ids.iter().all(|id| forum_tags.contains(id)) {
+        return Err((StatusCode::BAD_REQUEST, "Unknown tag".into()));
+    }
+    Ok(ids)
+}
+
+/// Attach tag ids and reply counts
+async fn with_details(state: &AppState, posts: Vec<ForumPost>) -> Result<Vec<ForumPostInfo>, DbErr> {
+    let ids: Vec<String> = posts.iter().map(|p| p.id.clone()).collect();
+
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+    for t in forum_post_tag::Entity::find()
+        .filter(forum_post_tag::Column::PostId.is_in(ids.clone()))
+        .all(&state.db)
+        .await?
+    {
+        tags.entry(t.post_id).or_default().push(t.tag_id);
+    }
+
+    // Replies are the messages after the opening one, which shares the post's timestamp
+    let mut replies = Condition::any();
+    for post in &posts {
+        replies = replies.add(
+            Condition::all()
+                .add(message::Column::PostId.eq(&post.id))
+                .add(message::Column::CreatedAt.gt(&post.created_at)),
+        );
+    }
+    let counts: HashMap<String, i64> = message::Entity::find()
+        .select_only()
+        .column(message::Column::PostId)
+        .column_as(message::Column::Id.count(), "messages")
+        .filter(message::Column::PostId.is_in(ids))
+        .filter(replies)
+        .filter(message::Column::DeletedAt.is_null())
+        .group_by(message::Column::PostId)
+        .into_tuple::<(String, i64)>()
+        .all(&state.db)
+        .await?
+        .into_iter()
+        .collect();
+
+    Ok(posts
+        .into_iter()
+        .map(|post| ForumPostInfo {
+            tag_ids: tags.remove(&post.id).unwrap_or_default(),
+            reply_count: counts.get(&post.id).copied().unwrap_or(0),
+            post,
+        })
+        .collect())
+}
+
+async fn post_details(state: &AppState, post: ForumPost) -> Result<ForumPostInfo, (StatusCode, String)> {
+    with_details(state, vec![post])
+        .await
+        .map_err(db_err)?
+        .pop()
+        .ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Post vanished".to_string()))
+}
+
+/// GET /api/channels/{channel_id}/posts — pinned first, then by `sort`
+pub async fn list_posts(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(channel_id): Path<String>,
+    Query(query): Query<PostsQuery>,
+) -> Result<Json<Vec<ForumPostInfo>>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    forum_channel(&state, &channel_id).await?;
+    require(&state, &claims.sub, &channel_id, Permissions::VIEW_CHANNELS).await?;
+
+    let mut select = forum_post::Entity::find()
+        .filter(forum_post::Column::ChannelId.eq(&channel_id))
+        .order_by_desc(forum_post::Column::Pinned);
+    select = match query.sort {
+        PostSort::Activity => select.order_by_desc(forum_post::Column::LastActivityAt),
+        PostSort::Created => select.order_by_desc(forum_post::Column::CreatedAt),
+    };
+    if let Some(tag_id) = &query.tag_id {
+        let tagged: Vec<String> = forum_post_tag::Entity::find()
+            .filter(forum_post_tag::Column::TagId.eq(tag_id))
+            .all(&state.db)
+            .await
+            .map_err(db_err)?
+            .into_iter()
+            .map(|t| t.post_id)
+            .collect();
+        select = select.filter(forum_post::Column::Id.is_in(tagged));
+    }
+    let posts = select
+        .order_by_desc(forum_post::Column::Id)
+        .limit(query.limit.unwrap_or(DEFAULT_PAGE).clamp(1, MAX_PAGE))
+        .offset(query.offset.unwrap_or(0))
+        .all(&state.db)
+        .await
+        .map_err(db_err)?;
+
+    with_details(&state, posts).await.map(Json).map_err(db_err)
+}
+
+/// GET /api/channels/{channel_id}/posts/{post_id}
+pub async fn get_post(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((channel_id, post_id)): Path<(String, String)>,
+) -> Result<Json<ForumPostInfo>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    require(&state, &claims.sub, &channel_id, Permissions::VIEW_CHANNELS).await?;
+    let post = find_post(&state, &channel_id, &post_id).await?;
+    post_details(&state, post).await.map(Json)
+}
+
+/// POST /api/channels/{channel_id}/posts — start a topic with its opening message
+pub async fn create_post(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(channel_id): Path<String>,
+    Json(req): Json<CreatePostRequest>,
+) -> Result<(StatusCode, Json<ForumPostInfo>), (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let forum = forum_channel(&state, &channel_id).await?;
+    require(&state, &claims.sub, &channel_id, Permissions::SEND_MESSAGES).await?;
+    if state.is_user_timed_out(&claims.sub).await {
+        return Err((StatusCode::FORBIDDEN, "You are currently timed out".into()));
+    }
+
+    let title = req.title.trim().to_string();
+    validate_title(&title)?;
+    let content = req.content.trim().to_string();
+    if content.is_empty() || content.len() > MAX_CONTENT_LENGTH {
+        return Err((StatusCode::BAD_REQUEST, format!("Posts must be 1-{MAX_CONTENT_LENGTH} characters")));
+    }
+    let tag_ids = validate_tag_ids(&state, &channel_id, &req.tag_ids).await?;
+
+    let author_name = members::server_nickname(&state, &forum.server_id, &claims.sub)
+        .await
+        .unwrap_or_else(|| claims.username.clone());
+    let avatar_url = user::Entity::find_by_id(&claims.sub)
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .and_then(|u| u.avatar_url);
+
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let post = ForumPost {
+        id: Uuid::new_v4().to_string(),
+        channel_id: channel_id.clone(),
+        author_id: Some(claims.sub.clone()),
+        title,
+        pinned: false,
+        locked: false,
+        created_at: now.clone(),
+        last_activity_at: now.clone(),
+    };
+    let msg_id = Uuid::new_v4().to_string();
+
+    let txn = state.db.begin().await.map_err(db_err)?;
+    forum_post::Entity::insert(post.clone().into_active_model().reset_all())
+        .exec(&txn)
+        .await
+        .map_err(db_err)?;
+    for tag_id in &tag_ids {
+        forum_post_tag::Entity::insert(forum_post_tag::ActiveModel {
+            post_id: Set(post.id.clone()),
+            tag_id: Set(tag_id.clone()),
+        })
+        .exec(&txn)
+        .await
+        .map_err(db_err)?;
+    }
+    message::Entity::insert(message::ActiveModel {
+        id: Set(msg_id.clone()),
+        channel_id: Set(channel_id.clone()),
+        user_id: Set(claims.sub.clone()),
+        user_name: Set(author_name.clone()),
+        avatar_url: Set(avatar_url.clone()),
+        content: Set(content.clone()),
+        created_at: Set(now.clone()),
+        post_id: Set(Some(post.id.clone())),
+        ..Default::default()
+    })
+    .exec(&txn)
+    .await
+    .map_err(db_err)?;
+    txn.commit().await.map_err(db_err)?;
+
+    let info = ForumPostInfo { post, tag_ids, reply_count: 0 };
+    state.publish_channel(&channel_id, WsServerMessage::ForumPostUpdate { post: info.clone() });
+    state.publish_channel(
+        &channel_id,
+        WsServerMessage::NewMessage {
+            id: msg_id,
+            channel_id: channel_id.clone(),
+            user_id: claims.sub,
+            user_name: author_name,
+            avatar_url,
+            content,
+            created_at: now,
+            is_bot: false,
+            reply_to: None,
+            replied_message: None,
+            attachments: Vec::new(),
+            sticker: None,
+            post_id: Some(info.post.id.clone()),
+        },
+    );
+    Ok((StatusCode::CREATED, Json(info)))
+}
+
+/// PATCH /api/channels/{channel_id}/posts/{post_id}
+pub async fn update_post(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((channel_id, post_id)): Path<(String, String)>,
+    Json(req): Json<UpdatePostRequest>,
+) -> Result<Json<ForumPostInfo>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let post = find_post(&state, &channel_id, &post_id).await?;
+    let moderator = can(&state, &claims.sub, &channel_id, Permissions::MANAGE_MESSAGES).await;
+    let is_author = post.author_id.as_deref() == Some(claims.sub.as_str());
+    if (req.pinned.is_some() || req.locked.is_some()) && !moderator {
+        return Err((StatusCode::FORBIDDEN, "Pinning and locking posts requires MANAGE_MESSAGES".into()));
+    }
+    if (req.title.is_some() || req.tag_ids.is_some()) && !is_author && !moderator {
+        return Err((StatusCode::FORBIDDEN, "Only the author can edit this post".into()));
+    }
+
+    let mut update: forum_post::ActiveModel = post.clone().into();
+    if let Some(title) = &req.title {
+        let title = title.trim().to_string();
+        validate_title(&title)?;
+        update.title = Set(title);
+    }
+    if let Some(pinned) = req.pinned {
+        update.pinned = Set(pinned);
+    }
+    if let Some(locked) = req.locked {
+        update.locked = Set(locked);
+    }
+    let tag_ids = match &req.tag_ids {
+        Some(ids) => Some(validate_tag_ids(&state, &channel_id, ids).await?),
+        None => None,
+    };
+
+    let txn = state.db.begin().await.map_err(db_err)?;
+    let post = update.update(&txn).await.map_err(db_err)?;
+    if let Some(tag_ids) = tag_ids {
+        forum_post_tag::Entity::delete_many()
+            .filter(forum_post_tag::Column::PostId.eq(&post_id))
+            .exec(&txn)
+            .await
+            .map_err(db_err)?;
+        for tag_id in tag_ids {
+            forum_post_tag::Entity::insert(forum_post_tag::ActiveModel {
+                post_id: Set(post_id.clone()),
+                tag_id: Set(tag_id),
+            })
+            .exec(&txn)
+            .await
+            .map_err(db_err)?;
+        }
+    }
+    txn.commit().await.map_err(db_err)?;
+
+    let info = post_details(&state, post).await?;
+    state.publish_channel(&channel_id, WsServerMessage::ForumPostUpdate { post: info.clone() });
+    Ok(Json(info))
+}
+
+/// DELETE /api/channels/{channel_id}/posts/{post_id} — the post and all its replies
+pub async fn delete_post(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((channel_id, post_id)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let post = find_post(&state, &channel_id, &post_id).await?;
+    if post.author_id.as_deref() != Some(claims.sub.as_str())
+        && !can(&state, &claims.sub, &channel_id, Permissions::MANAGE_MESSAGES).await
+    {
+        return Err((StatusCode::FORBIDDEN, "Not authorized to delete this post".into()));
+    }
+
+    let replies = message::Entity::find()
+        .filter(message::Column::PostId.eq(&post_id))
+        .filter(message::Column::DeletedAt.is_null())
+        .all(&state.db)
+        .await
+        .map_err(db_err)?;
+    for reply in replies {
+        let mut active: message::ActiveModel = reply.into();
+        active.deleted_at = Set(Some(chrono::Utc::now()));
+        let deleted = active.update(&state.db).await.map_err(db_err)?;
+        if let Err(e) = blobs::release_message_uploads(&state, &deleted).await {
+            tracing::warn!("Failed to release uploads of message {}: {e}", deleted.id);
+        }
+    }
+    forum_post::Entity::delete_by_id(&post_id)
+        .exec(&state.db)
+        .await
+        .map_err(db_err)?;
+
+    state.publish_channel(&channel_id, WsServerMessage::ForumPostDelete { channel_id: channel_id.clone(), post_id });
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /api/channels/{channel_id}/tags
+pub async fn list_tags(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(channel_id): Path<String>,
+) -> Result<Json<Vec<ForumTag>>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    forum_channel(&state, &channel_id).await?;
+    require(&state, &claims.sub, &channel_id, Permissions::VIEW_CHANNELS).await?;
+
+    forum_tag::Entity::find()
+        .filter(forum_tag::Column::ChannelId.eq(&channel_id))
+        .order_by_asc(forum_tag::Column::Position)
+        .all(&state.db)
+        .await
+        .map(Json)
+        .map_err(db_err)
+}
+
+/// PUT /api/channels/{channel_id}/tags — replace the forum's tags, in order (MANAGE_CHANNELS)
+pub async fn update_tags(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(channel_id): Path<String>,
+    Json(req): Json<UpdateForumTagsRequest>,
+) -> Result<Json<Vec<ForumTag>>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    forum_channel(&state, &channel_id).await?;
+    require(&state, &claims.sub, &channel_id, Permissions::MANAGE_CHANNELS).await?;
+
+    if req.tags.len() > MAX_FORUM_TAGS {
+        return Err((StatusCode::BAD_REQUEST, format!("Forums can have at most {MAX_FORUM_TAGS} tags")));
+    }
+    let existing: HashSet<String> = forum_tag::Entity::find()
+        .filter(forum_tag::Column::ChannelId.eq(&channel_id))
+        .all(&state.db)
+        .await
+        .map_err(db_err)?
+        .into_iter()
+        .map(|t| t.id)
+        .collect();
+
+    let mut names = HashSet::new();
+    let mut tags = Vec::new();
+    for (position, input) in req.tags.into_iter().enumerate() {
+        let name = input.name.trim().to_string();
+        if name.is_empty() || name.chars().count() > MAX_TAG_NAME_LENGTH {
+            return Err((StatusCode::BAD_REQUEST, format!("Tag names must be 1-{MAX_TAG_NAME_LENGTH} characters")));
+        }
+        if !names.insert(name.to_lowercase()) {
+            return Err((StatusCode::BAD_REQUEST, format!("Duplicate tag {name}")));
+        }
+        let id = match input.id {
+            Some(id) if existing.contains(&id) => id,
+            Some(_) => return Err((StatusCode::BAD_REQUEST, "Unknown tag".into())),
+            None => Uuid::new_v4().to_string(),
+        };
+        tags.push(ForumTag {
+            id,
+            channel_id: channel_id.clone(),
+            name,
+            position: position as i64,
+        });
+    }
+
+    // Removing a tag takes it off every post
+    let txn = state.db.begin().await.map_err(db_err)?;
+    forum_tag::Entity::delete_many()
+        .filter(forum_tag::Column::ChannelId.eq(&channel_id))
+        .filter(forum_tag::Column::Id.is_not_in(tags.iter().map(|t| t.id.clone())))
+        .exec(&txn)
+        .await
+        .map_err(db_err)?;
+    for tag in &tags {
+        let active = tag.clone().into_active_model().reset_all();
+        if existing.contains(&tag.id) {
+            active.update(&txn).await.map_err(db_err)?;
+        } else {
+            forum_tag::Entity::insert(active).exec(&txn).await.map_err(db_err)?;
+        }
+    }
+    txn.commit().await.map_err(db_err)?;
+
+    state.publish_channel(
+        &channel_id,
+        WsServerMessage::ForumTagsUpdate { channel_id: channel_id.clone(), tags: tags.clone() },
+    );
+    Ok(Json(tags))
+}
+
+/// Check where a new message may go: forum channels need an open post (locked ones
+/// still take replies from moderators) and other channels can't have one
+pub async fn check_reply(
+    state: &AppState,
+    user_id: &str,
+    channel_id: &str,
+    post_id: Option<&str>,
+) -> Result<(), String> {
+    let is_forum = channel::Entity::find_by_id(channel_id)
+        .one(&state.db)
+        .await
+        .map_err(|e| format!("DB error: {e}"))?
+        .is_some_and(|c| c.channel_type == "forum");
+    let post = match post_id {
+        Some(post_id) if is_forum => forum_post::Entity::find_by_id(post_id)
+            .one(&state.db)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?,
+        _ => None,
+    };
+    let moderator = match &post {
+        Some(post) if post.locked => can(state, user_id, channel_id, Permissions::MANAGE_MESSAGES).await,
+        _ => false,
+    };
+    reply_allowed(channel_id, is_forum, post_id.is_some(), post.as_ref(), moderator)
+}
+
+/// `check_reply` once the channel, the post (if found) and the author's rights are known
+fn reply_allowed(
+    channel_id: &str,
+    is_forum: bool,
+    has_post_id: bool,
+    post: Option<&ForumPost>,
+    moderator: bool,
+) -> Result<(), String> {
+    if !has_post_id {
+        return match is_forum {
+            true => Err("Messages in forum channels must reply to a post".to_string()),
+            false => Ok(()),
+        };
+    }
+    if !is_forum {
+        return Err("Only forum channels have posts".to_string());
+    }
+    let post = post
+        .filter(|p| p.channel_id == channel_id)
+        .ok_or_else(|| "Post not found".to_string())?;
+    if post.locked && !moderator {
+        return Err("This post is locked".to_string());
+    }
+    Ok(())
+}
+
+/// Bump a post to the top of the activity sort after a reply
+pub async fn touch_post(state: &AppState, post_id: &str, at: &str) {
+    let bumped = forum_post::Entity::update_many()
+        .col_expr(forum_post::Column::LastActivityAt, sea_query::Expr::value(at))
+        .filter(forum_post::Column::Id.eq(post_id))
+        .exec(&state.db)
+        .await;
+    if let Err(e) = bumped {
+        tracing::warn!("Failed to bump forum post {post_id}: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(channel_id: &str, locked: bool) -> ForumPost {
+        ForumPost {
+            id: "post".into(),
+            channel_id: channel_id.into(),
+            author_id: None,
+            title: "Hello".into(),
+            pinned: false,
+            locked,
+            created_at: "2026-01-01 00:00:00".into(),
+            last_activity_at: "2026-01-01 00:00:00".into(),
+        }
+    }
+
+    #[test]
+    fn replies_need_an_open_post_of_the_forum() {
+        let open = post("forum", false);
+        assert!(reply_allowed("forum", true, true, Some(&open), false).is_ok());
+        assert!(reply_allowed("forum", true, false, None, false).is_err());
+        assert!(reply_allowed("text", false, false, None, false).is_ok());
+        assert!(reply_allowed("text", false, true, None, false).is_err());
+
+        // Missing, or belonging to another forum
+        assert_eq!(reply_allowed("forum", true, true, None, false), Err("Post not found".into()));
+        let elsewhere = post("other-forum", false);
+        assert_eq!(reply_allowed("forum", true, true, Some(&elsewhere), true), Err("Post not found".into()));
+
+        let locked = post("forum", true);
+        assert_eq!(reply_allowed("forum", true, true, Some(&locked), false), Err("This post is locked".into()));
+        assert!(reply_allowed("forum", true, true, Some(&locked), true).is_ok());
+    }
+
+    #[test]
+    fn tags_must_belong_to_the_forum() {
+        let forum_tags: HashSet<String> = ["a".to_string(), "b".to_string()].into();
+        let ids = check_tag_ids(&["a".into(), "b".into(), "a".into()], &forum_tags).unwrap();
+        assert_eq!(ids, vec!["a".to_string(), "b".to_string()]);
+        // A tag of another forum is unknown here
+        assert!(check_tag_ids(&["a".into(), "other-forum-tag".into()], &forum_tags).is_err());
+        let too_many: Vec<String> = (0..=MAX_POST_TAGS).map(|i| i.to_string()).collect();
+        let all: HashSet<String> = too_many.iter().cloned().collect();
+        assert!(check_tag_ids(&too_many, &all).is_err());
+    }
+}
@@ -1,29 +1,100 @@
 use axum::{extract::{State, Path}, Json, response::IntoResponse, http::{HeaderMap, StatusCode}};
 use sea_orm::*;
+use sea_orm::prelude::Expr;
 use serde::Deserialize;
 use uuid::Uuid;
-use crate::entities::{invite_code, server};
-use crate::models::{CreateInviteRequest, InviteResponse, JoinRequest, JoinResponse};
+use crate::entities::{ban, channel, invite_code, role, server, server_member, user, user_role};
+use crate::models::{
+    AcceptInviteResponse, CreateInviteRequest, InviteDetails, InviteResponse, JoinRequest, JoinResponse,
+    Permissions, SetVanityRequest,
+};
 use crate::state::AppState;
 use crate::token;
+use crate::routes::{auth, onboarding, roles};
 use crate::routes::audit_logs::create_audit_log;
 use crate::routes::servers::extract_server_id;
 
+const MAX_INVITE_AGE_SECS: i64 = 30 * 24 * 3600;
+
+fn now_string() -> String {
+    chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Past its expiry, or out of uses
+fn is_exhausted(invite: &invite_code::Model) -> bool {
+    invite.expires_at.as_ref().is_some_and(|t| *t <= now_string())
+        || invite.max_uses.is_some_and(|max| invite.uses >= max)
+}
+
+fn connection_token(state: &AppState, code: &str) -> String {
+    token::encode_token(&crate::models::ConnectionToken {
+        host: state.external_host.clone(),
+        port: state.external_port,
+        invite_code: code.to_string(),
+    })
+}
+
+/// Create an invite to the X-Server-Id server (requires CREATE_INVITE)
 pub async fn create_invite(
     State(state): State<AppState>,
     headers: HeaderMap,
     Json(req): Json<CreateInviteRequest>,
-) -> Result<(StatusCode, Json<InviteResponse>), StatusCode> {
+) -> Result<(StatusCode, Json<InviteResponse>), (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
     let server_id = extract_server_id(&headers);
-    let code = token::generate_invite_code();
-    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let db_err = |e: DbErr| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"));
 
+    let is_member = server_member::Entity::find_by_id((server_id.clone(), claims.sub.clone()))
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .is_some();
+    if !is_member
+        || !roles::user_has_server_permission(&state, &server_id, &claims.sub, Permissions::CREATE_INVITE)
+            .await
+            .map_err(|s| (s, "Permission check failed".to_string()))?
+    {
+        return Err((StatusCode::FORBIDDEN, "Creating invites requires CREATE_INVITE".into()));
+    }
+
+    if req.max_uses.is_some_and(|m| m < 1) {
+        return Err((StatusCode::BAD_REQUEST, "max_uses must be at least 1".into()));
+    }
+    let expires_at = match req.max_age_secs.filter(|&s| s != 0) {
+        Some(secs) if !(1..=MAX_INVITE_AGE_SECS).contains(&secs) => {
+            return Err((StatusCode::BAD_REQUEST, format!("max_age_secs must be 0-{MAX_INVITE_AGE_SECS}")));
+        }
+        Some(secs) => Some(
+            (chrono::Utc::now() + chrono::Duration::seconds(secs))
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+        ),
+        None => None,
+    };
+    if let Some(channel_id) = &req.channel_id {
+        let in_server = channel::Entity::find_by_id(channel_id)
+            .one(&state.db)
+            .await
+            .map_err(db_err)?
+            .is_some_and(|c| c.server_id == server_id);
+        if !in_server {
+            return Err((StatusCode::BAD_REQUEST, "Channel not found in this server".into()));
+        }
+    }
+
+    let code = token::generate_invite_code();
     let new_invite = invite_code::ActiveModel {
         code: Set(code.clone()),
-        created_at: Set(now),
+        created_at: Set(now_string()),
         uses: Set(0),
         max_uses: Set(req.max_uses),
-        server_id: Set(server_id),
+        server_id: Set(server_id.clone()),
+        expires_at: Set(expires_at.clone()),
+        created_by: Set(Some(claims.sub.clone())),
+        channel_id: Set(req.channel_id),
+        temporary: Set(req.temporary),
+        vanity: Set(false),
+        joins: Set(0),
     };
 
     invite_code::Entity::insert(new_invite)
@@ -31,25 +102,270 @@ pub async fn create_invite(
         .await
         .map_err(|e| {
             tracing::error!("Failed to insert invite: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"))
         })?;
 
-    let conn_token = crate::models::ConnectionToken {
-        host: state.external_host.clone(),
-        port: state.external_port,
-        invite_code: code.clone(),
-    };
-    let encoded = token::encode_token(&conn_token);
+    create_audit_log(
+        &state.db,
+        &claims.sub,
+        &claims.username,
+        "CREATE_INVITE",
+        Some(&server_id),
+        Some(&code),
+        None,
+    )
+    .await;
 
     Ok((
         StatusCode::CREATED,
         Json(InviteResponse {
+            token: connection_token(&state, &code),
             code,
-            token: encoded,
+            expires_at,
         }),
     ))
 }
 
+/// Join the invite's server as the authenticated user
+pub async fn accept_invite(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(code): Path<String>,
+) -> Result<Json<AcceptInviteResponse>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let db_err = |e: DbErr| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"));
+
+    let invite = invite_code::Entity::find_by_id(&code)
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .ok_or((StatusCode::NOT_FOUND, "Invite not found".to_string()))?;
+    let server = server::Entity::find_by_id(&invite.server_id)
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .ok_or((StatusCode::NOT_FOUND, "Invite not found".to_string()))?;
+
+    // Already a member: nothing to do, and a temporary invite doesn't downgrade them
+    if let Some(member) = server_member::Entity::find_by_id((server.id.clone(), claims.sub.clone()))
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+    {
+        return Ok(Json(AcceptInviteResponse {
+            server,
+            channel_id: invite.channel_id,
+            temporary: member.temporary,
+        }));
+    }
+
+    if is_exhausted(&invite) {
+        return Err((StatusCode::GONE, "This invite has expired".into()));
+    }
+    let banned = ban::Entity::find_by_id(&claims.sub)
+        .filter(ban::Column::ServerId.eq(&server.id))
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .is_some();
+    if banned {
+        return Err((StatusCode::FORBIDDEN, "You are banned from this server".into()));
+    }
+
+    let pending = onboarding::screening_required(&state.db, &server.id).await.map_err(db_err)?;
+    let txn = state.db.begin().await.map_err(db_err)?;
+    server_member::Entity::insert(server_member::ActiveModel {
+        server_id: Set(server.id.clone()),
+        user_id: Set(claims.sub.clone()),
+        joined_at: Set(now_string()),
+        temporary: Set(invite.temporary),
+        nickname: Set(None),
+        pending: Set(pending),
+        screened_at: Set(None),
+        screening_answers: Set(None),
+    })
+    .exec(&txn)
+    .await
+    .map_err(db_err)?;
+    // Claim a use only while the invite is still valid, so concurrent joins can't overshoot max_uses
+    let claimed = invite_code::Entity::update_many()
+        .col_expr(invite_code::Column::Uses, Expr::col(invite_code::Column::Uses).add(1))
+        .col_expr(invite_code::Column::Joins, Expr::col(invite_code::Column::Joins).add(1))
+        .filter(invite_code::Column::Code.eq(&code))
+        .filter(
+            Condition::any()
+                .add(invite_code::Column::MaxUses.is_null())
+                .add(Expr::col(invite_code::Column::Uses).lt(Expr::col(invite_code::Column::MaxUses))),
+        )
+        .filter(
+            Condition::any()
+                .add(invite_code::Column::ExpiresAt.is_null())
+                .add(invite_code::Column::ExpiresAt.gt(now_string())),
+        )
+        .exec(&txn)
+        .await
+        .map_err(db_err)?;
+    if claimed.rows_affected == 0 {
+        txn.rollback().await.map_err(db_err)?;
+        return Err((StatusCode::GONE, "This invite has expired".into()));
+    }
+    txn.commit().await.map_err(db_err)?;
+    state.membership_changed(&claims.sub, &server.id, true);
+
+    if !pending {
+        onboarding::send_welcome(&state, &server.id, &claims.sub).await;
+    }
+
+    Ok(Json(AcceptInviteResponse {
+        server,
+        channel_id: invite.channel_id,
+        temporary: invite.temporary,
+    }))
+}
+
+/// Set or remove the server's vanity invite code (owner or MANAGE_SERVER)
+pub async fn set_vanity_code(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(server_id): Path<String>,
+    Json(req): Json<SetVanityRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let db_err = |e: DbErr| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"));
+
+    let srv = server::Entity::find_by_id(&server_id)
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .ok_or((StatusCode::NOT_FOUND, "Server not found".to_string()))?;
+    if srv.owner_id != claims.sub
+        && !roles::user_has_server_permission(&state, &server_id, &claims.sub, Permissions::MANAGE_SERVER)
+            .await
+            .map_err(|s| (s, "Permission check failed".to_string()))?
+    {
+        return Err((StatusCode::FORBIDDEN, "Setting a vanity code requires MANAGE_SERVER".into()));
+    }
+
+    let code = req.code.map(|c| c.trim().to_lowercase());
+    if code.as_deref().is_some_and(|c| !token::is_valid_vanity_code(c)) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Vanity codes are 3-32 lowercase letters, digits and dashes".into(),
+        ));
+    }
+
+    let txn = state.db.begin().await.map_err(db_err)?;
+    invite_code::Entity::delete_many()
+        .filter(invite_code::Column::ServerId.eq(&server_id))
+        .filter(invite_code::Column::Vanity.eq(true))
+        .exec(&txn)
+        .await
+        .map_err(db_err)?;
+    if let Some(code) = &code {
+        let taken = invite_code::Entity::find_by_id(code)
+            .one(&txn)
+            .await
+            .map_err(db_err)?
+            .is_some();
+        if taken {
+            return Err((StatusCode::CONFLICT, "That code is already taken".into()));
+        }
+        invite_code::Entity::insert(invite_code::ActiveModel {
+            code: Set(code.clone()),
+            created_at: Set(now_string()),
+            uses: Set(0),
+            max_uses: Set(None),
+            server_id: Set(server_id.clone()),
+            expires_at: Set(None),
+            created_by: Set(Some(claims.sub.clone())),
+            channel_id: Set(None),
+            temporary: Set(false),
+            vanity: Set(true),
+            joins: Set(0),
+        })
+        .exec(&txn)
+        .await
+        .map_err(db_err)?;
+    }
+    txn.commit().await.map_err(db_err)?;
+
+    create_audit_log(
+        &state.db,
+        &claims.sub,
+        &claims.username,
+        "UPDATE_VANITY_CODE",
+        Some(&server_id),
+        Some(&srv.name),
+        code.as_deref(),
+    )
+    .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Remove `user_id` from servers they joined through a temporary invite and
+/// still hold no role in. Called once their last connection is gone.
+pub async fn drop_temporary_memberships(state: &AppState, user_id: &str) -> Result<(), DbErr> {
+    let memberships = server_member::Entity::find()
+        .filter(server_member::Column::UserId.eq(user_id))
+        .filter(server_member::Column::Temporary.eq(true))
+        .all(&state.db)
+        .await?;
+    for m in memberships {
+        let has_role = user_role::Entity::find()
+            .inner_join(role::Entity)
+            .filter(user_role::Column::UserId.eq(user_id))
+            .filter(role::Column::ServerId.eq(&m.server_id))
+            .count(&state.db)
+            .await?
+            > 0;
+        if has_role {
+            // Given a role: they're a regular member from now on
+            let mut update: server_member::ActiveModel = m.into();
+            update.temporary = Set(false);
+            update.update(&state.db).await?;
+        } else {
+            server_member::Entity::delete_many()
+                .filter(server_member::Column::ServerId.eq(&m.server_id))
+                .filter(server_member::Column::UserId.eq(user_id))
+                .exec(&state.db)
+                .await?;
+            state.membership_changed(user_id, &m.server_id, false);
+        }
+    }
+    Ok(())
+}
+
+/// Joined through a temporary invite but never connected: left alone for this long first
+const TEMPORARY_JOIN_GRACE_SECS: i64 = 600;
+
+/// Drop temporary memberships of users with no connection on any instance, for
+/// those whose disconnect was never seen (e.g. the instance holding it went down).
+/// Returns how many users were swept.
+pub async fn sweep_temporary_members(state: &AppState) -> Result<usize, DbErr> {
+    let cutoff = (chrono::Utc::now() - chrono::Duration::seconds(TEMPORARY_JOIN_GRACE_SECS))
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string();
+    let user_ids: Vec<String> = server_member::Entity::find()
+        .select_only()
+        .column(server_member::Column::UserId)
+        .distinct()
+        .filter(server_member::Column::Temporary.eq(true))
+        .filter(server_member::Column::JoinedAt.lt(cutoff))
+        .into_tuple()
+        .all(&state.db)
+        .await?;
+    let mut swept = 0;
+    for user_id in user_ids {
+        if state.presence.is_connected(&user_id) {
+            continue;
+        }
+        drop_temporary_memberships(state, &user_id).await?;
+        swept += 1;
+    }
+    Ok(swept)
+}
+
 pub async fn join_server(
     State(state): State<AppState>,
     Json(req): Json<JoinRequest>,
@@ -63,10 +379,8 @@ pub async fn join_server(
         })?
         .ok_or(StatusCode::NOT_FOUND)?;
 
-    if let Some(max) = invite.max_uses {
-        if invite.uses >= max {
-            return Err(StatusCode::GONE);
-        }
+    if is_exhausted(&invite) {
+        return Err(StatusCode::GONE);
     }
 
     // Increment uses
@@ -116,17 +430,26 @@ pub async fn join_direct(
 pub async fn list_invites(
     State(state): State<AppState>,
     headers: HeaderMap,
-) -> Json<Vec<crate::models::InviteCode>> {
+) -> Json<Vec<InviteDetails>> {
     let server_id = extract_server_id(&headers);
 
     let invites = invite_code::Entity::find()
         .filter(invite_code::Column::ServerId.eq(&server_id))
         .order_by_desc(invite_code::Column::CreatedAt)
+        .find_also_related(user::Entity)
         .all(&state.db)
         .await
         .unwrap_or_default();
 
-    Json(invites)
+    Json(
+        invites
+            .into_iter()
+            .map(|(invite, creator)| InviteDetails {
+                invite,
+                creator_name: creator.map(|u| u.display_name),
+            })
+            .collect(),
+    )
 }
 
 pub async fn delete_invite(
@@ -1,12 +1,12 @@
 use axum::{extract::{State, Path}, Json, http::{HeaderMap, StatusCode}};
 use sea_orm::{EntityTrait, QueryFilter, ColumnTrait, QueryOrder, Set, ActiveModelTrait};
-use crate::{entities::{ban, server_member, user}, models::TimeoutRequest, routes::auth::UserInfo};
-use crate::models::{Ban, BanRequest, Permissions};
+use crate::{entities::{ban, server, server_member, user}, models::TimeoutRequest, routes::auth::UserInfo};
+use crate::models::{Ban, BanRequest, Permissions, SetNicknameRequest, WsServerMessage};
 
 use crate::state::AppState;
 use crate::routes::audit_logs::create_audit_log;
 use crate::routes::auth::extract_claims;
-use crate::routes::roles::user_has_permission;
+use crate::routes::roles::{server_permissions, user_has_permission};
 use crate::routes::servers::extract_server_id;
 
 pub async fn list_bans(
@@ -73,6 +73,7 @@ pub async fn ban_member(
         .filter(server_member::Column::UserId.eq(&user_id))
         .exec(&state.db)
         .await;
+    state.membership_changed(&user_id, &server_id, false);
 
     create_audit_log(
         &state.db,
@@ -210,4 +211,85 @@ pub async fn timeout_member(
     ).await;
 
     Ok(StatusCode::OK)
-}
\ No newline at end of file
+}
+const MAX_NICKNAME_LENGTH: usize = 32;
+
+/// The name `user_id` goes by in `server_id`: their nickname, if they set one there
+pub async fn server_nickname(state: &AppState, server_id: &str, user_id: &str) -> Option<String> {
+    server_member::Entity::find_by_id((server_id.to_string(), user_id.to_string()))
+        .one(&state.db)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|m| m.nickname)
+}
+
+/// Set a member's nickname in a server: your own with CHANGE_NICKNAME,
+/// anyone else's with MANAGE_NICKNAMES. Only the owner can rename the owner.
+pub async fn set_nickname(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((server_id, user_id)): Path<(String, String)>,
+    Json(payload): Json<SetNicknameRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let claims = extract_claims(&state.jwt_secret, &headers)?;
+    let db_err = |e: sea_orm::DbErr| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"));
+
+    let srv = server::Entity::find_by_id(&server_id)
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .ok_or((StatusCode::NOT_FOUND, "Server not found".to_string()))?;
+    let member = server_member::Entity::find_by_id((server_id.clone(), user_id.clone()))
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .ok_or((StatusCode::NOT_FOUND, "Member not found".to_string()))?;
+
+    // Only the owner may rename the owner; everyone else needs the permission in this server
+    let is_self = user_id == claims.sub;
+    let perms = server_permissions(&state, &server_id, &claims.sub)
+        .await
+        .map_err(|s| (s, "Permission check failed".to_string()))?;
+    let allowed = srv.owner_id == claims.sub
+        || (is_self && perms.contains(Permissions::CHANGE_NICKNAME))
+        || (user_id != srv.owner_id && perms.contains(Permissions::MANAGE_NICKNAMES));
+    if !allowed {
+        return Err((StatusCode::FORBIDDEN, "You cannot change this nickname".into()));
+    }
+
+    let nickname = payload
+        .nickname
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty());
+    if nickname.as_ref().is_some_and(|n| n.chars().count() > MAX_NICKNAME_LENGTH) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Nicknames are at most {MAX_NICKNAME_LENGTH} characters"),
+        ));
+    }
+
+    let mut active: server_member::ActiveModel = member.into();
+    active.nickname = Set(nickname.clone());
+    active.update(&state.db).await.map_err(db_err)?;
+
+    if !is_self {
+        create_audit_log(
+            &state.db,
+            &claims.sub,
+            &claims.username,
+            "CHANGE_NICKNAME",
+            Some(&user_id),
+            nickname.as_deref(),
+            Some(&server_id),
+        )
+        .await;
+    }
+
+    state.broadcast_global(
+        Some(&server_id),
+        WsServerMessage::MemberUpdate { server_id: server_id.clone(), user_id, nickname },
+    );
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -6,12 +6,14 @@ use axum::{
 use sea_orm::{ActiveModelTrait, ActiveValue::Set, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
 use std::collections::HashMap;
 
-use crate::entities::{message, reaction};
+use crate::attachments;
+use crate::blobs;
+use crate::entities::{channel, message, reaction};
 use crate::models::{
     Message, MessageEdit, MessageWithReply, MessagesQuery, Permissions, ReactionGroup,
     RepliedMessage, WsServerMessage,
 };
-use crate::routes::auth;
+use crate::routes::{auth, stickers};
 use crate::permissions::check_channel_permission;
 use crate::state::AppState;
 
@@ -38,6 +40,21 @@ pub async fn get_messages(
         .order_by_desc(message::Column::CreatedAt)
         .limit(limit);
 
+    // Forum channels are read one post at a time
+    match &query.post_id {
+        Some(post_id) => q = q.filter(message::Column::PostId.eq(post_id)),
+        None => {
+            let is_forum = channel::Entity::find_by_id(&channel_id)
+                .one(&state.db)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+                .is_some_and(|c| c.channel_type == "forum");
+            if is_forum {
+                return Err((StatusCode::BAD_REQUEST, "post_id is required in forum channels".into()));
+            }
+        }
+    }
+
     if let Some(before) = &query.before {
         q = q.filter(message::Column::CreatedAt.lt(before));
     }
@@ -91,6 +108,13 @@ pub async fn get_messages(
             .push(r.user_id);
     }
 
+    let mut attachment_map = attachments::for_messages(&state, &message_ids)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+    let sticker_map = stickers::for_messages(&state, &messages)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
     let result: Vec<MessageWithReply> = messages
         .into_iter()
         .map(|msg| {
@@ -121,10 +145,14 @@ pub async fn get_messages(
                 })
                 .collect();
 
+            let sticker = msg.sticker_id.as_ref().and_then(|id| sticker_map.get(id).cloned());
+
             MessageWithReply {
                 message: msg,
                 replied_message,
                 reactions,
+                attachments: attachment_map.remove(&msg_id).unwrap_or_default(),
+                sticker,
             }
         })
         .collect();
@@ -164,8 +192,7 @@ pub async fn edit_message(
     })?;
 
     // Broadcast edit to all subscribers
-    let tx = state.get_channel_tx(&channel_id);
-    let _ = tx.send(WsServerMessage::MessageEdited {
+    state.publish_channel(&channel_id, WsServerMessage::MessageEdited {
         id: message_id,
         content: payload.content,
         edited_at: chrono::Utc::now(),
@@ -200,18 +227,21 @@ pub async fn delete_message(
     let mut active_message: message::ActiveModel = message.into();
     active_message.deleted_at = Set(Some(chrono::Utc::now()));
 
-    active_message.update(&state.db).await.map_err(|e| {
+    let deleted = active_message.update(&state.db).await.map_err(|e| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
             format!("Failed to delete message: {e}"),
         )
     })?;
 
+    if let Err(e) = blobs::release_message_uploads(&state, &deleted).await {
+        tracing::warn!("Failed to release uploads of message {}: {e}", deleted.id);
+    }
+
     // Broadcast deletion to all subscribers
-    let tx = state.get_channel_tx(&channel_id);
-    let _ = tx.send(WsServerMessage::MessageDeleted {
+    state.publish_channel(&channel_id, WsServerMessage::MessageDeleted {
         id: message_id,
-        channel_id,
+        channel_id: channel_id.clone(),
     });
 
     Ok(StatusCode::NO_CONTENT)
@@ -253,9 +283,8 @@ pub async fn pin_message(
         )
     })?;
 
-    let tx = state.get_channel_tx(&channel_id);
-    let _ = tx.send(WsServerMessage::MessagePinned {
-        channel_id,
+    state.publish_channel(&channel_id, WsServerMessage::MessagePinned {
+        channel_id: channel_id.clone(),
         message_id,
         pinned: true,
         pinned_at: Some(pinned_at),
@@ -299,9 +328,8 @@ pub async fn unpin_message(
         )
     })?;
 
-    let tx = state.get_channel_tx(&channel_id);
-    let _ = tx.send(WsServerMessage::MessagePinned {
-        channel_id,
+    state.publish_channel(&channel_id, WsServerMessage::MessagePinned {
+        channel_id: channel_id.clone(),
         message_id,
         pinned: false,
         pinned_at: None,
@@ -374,6 +402,13 @@ pub async fn get_pinned_messages(
             .push(r.user_id);
     }
 
+    let mut attachment_map = attachments::for_messages(&state, &message_ids)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+    let sticker_map = stickers::for_messages(&state, &messages)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
     let result: Vec<MessageWithReply> = messages
         .into_iter()
         .map(|msg| {
@@ -404,10 +439,14 @@ pub async fn get_pinned_messages(
                 })
                 .collect();
 
+            let sticker = msg.sticker_id.as_ref().and_then(|id| sticker_map.get(id).cloned());
+
             MessageWithReply {
                 message: msg,
                 replied_message,
                 reactions,
+                attachments: attachment_map.remove(&msg_id).unwrap_or_default(),
+                sticker,
             }
         })
         .collect();
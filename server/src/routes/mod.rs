@@ -1,17 +1,29 @@
+pub mod announcements;
+pub mod applications;
 pub mod auth;
 pub mod bots;
 pub mod channels;
+pub mod discovery;
 pub mod emoji;
+pub mod events;
+pub mod forums;
 pub mod invite;
 pub mod messages;
+pub mod onboarding;
+pub mod oauth2;
+pub mod presence;
+pub mod quotas;
 pub mod reactions;
 pub mod roles;
 pub mod server_info;
 pub mod servers;
 pub mod uploads;
+pub mod upload_sessions;
 pub mod audit_logs;
 pub mod members;
 pub mod stats;
+pub mod stickers;
+pub mod templates;
 pub mod webhooks;
 pub mod encryption;
 pub mod federation;
@@ -0,0 +1,438 @@
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Form, Json,
+};
+use rand::Rng;
+use sea_orm::*;
+use sea_orm::prelude::Expr;
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
+
+use crate::entities::{application, bot, oauth_code, role, server, server_member, user_role};
+use crate::models::{Bot, Permissions};
+use crate::routes::audit_logs::create_audit_log;
+use crate::routes::auth;
+use crate::routes::bots::generate_bot_token;
+use crate::routes::roles::server_permissions;
+use crate::state::AppState;
+
+/// Authorization codes are single-use and expire quickly
+const CODE_TTL_MINUTES: i64 = 10;
+
+// ─── Request / Response types ───
+
+#[derive(Debug, Deserialize)]
+pub struct AuthorizeQuery {
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub permissions: Option<i64>,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PermissionInfo {
+    pub bits: i64,
+    pub names: Vec<String>,
+}
+
+impl From<Permissions> for PermissionInfo {
+    fn from(perms: Permissions) -> Self {
+        Self {
+            bits: perms.bits(),
+            names: perms.iter_names().map(|(name, _)| name.to_string()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuthorizeInfo {
+    pub application: crate::routes::applications::ApplicationResponse,
+    pub permissions: PermissionInfo,
+    pub redirect_uri: String,
+    pub scope: String,
+    /// Echoed back so the consent screen can pass it on when approving
+    pub state: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApproveRequest {
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub server_id: String,
+    pub permissions: Option<i64>,
+    pub state: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApproveResponse {
+    pub code: String,
+    /// Where the admin's browser should be sent, with `code` and `state` appended
+    pub redirect_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    pub code: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_uri: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub scope: &'static str,
+    pub permissions: i64,
+    pub server_id: String,
+    pub bot: Bot,
+}
+
+fn generate_code() -> String {
+    rand::thread_rng()
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect()
+}
+
+/// Resolve the application and make sure the redirect URI is one it registered
+async fn find_application_for_redirect(
+    state: &AppState,
+    client_id: &str,
+    redirect_uri: &str,
+) -> Result<application::Model, (StatusCode, String)> {
+    let app = application::Entity::find_by_id(client_id)
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+        .ok_or((StatusCode::NOT_FOUND, "Unknown client_id".into()))?;
+
+    if !app.redirect_uri_list().contains(&redirect_uri) {
+        return Err((StatusCode::BAD_REQUEST, "redirect_uri is not registered for this application".into()));
+    }
+    Ok(app)
+}
+
+/// Requested permissions default to the application's and may never exceed them
+fn resolve_permissions(app: &application::Model, requested: Option<i64>) -> Result<Permissions, (StatusCode, String)> {
+    let allowed = Permissions::from_bits_truncate(app.permissions);
+    let requested = requested.map(Permissions::from_bits_truncate).unwrap_or(allowed);
+    if !allowed.contains(requested) {
+        return Err((StatusCode::BAD_REQUEST, "Requested permissions exceed what the application asks for".into()));
+    }
+    Ok(requested)
+}
+
+// ─── Routes ───
+
+/// GET /api/oauth2/authorize — describe an install request for the consent screen
+pub async fn get_authorize(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<AuthorizeQuery>,
+) -> Result<Json<AuthorizeInfo>, (StatusCode, String)> {
+    auth::extract_claims(&state.jwt_secret, &headers)?;
+
+    let scope = query.scope.unwrap_or_else(|| "bot".to_string());
+    if scope != "bot" {
+        return Err((StatusCode::BAD_REQUEST, "Only the 'bot' scope is supported".into()));
+    }
+
+    let app = find_application_for_redirect(&state, &query.client_id, &query.redirect_uri).await?;
+    let permissions = resolve_permissions(&app, query.permissions)?;
+
+    Ok(Json(AuthorizeInfo {
+        application: app.into(),
+        permissions: permissions.into(),
+        redirect_uri: query.redirect_uri,
+        scope,
+        state: query.state,
+    }))
+}
+
+/// POST /api/oauth2/authorize — a server admin approves the install and receives a code
+pub async fn approve_authorize(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<ApproveRequest>,
+) -> Result<Json<ApproveResponse>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+
+    let app = find_application_for_redirect(&state, &req.client_id, &req.redirect_uri).await?;
+    let permissions = resolve_permissions(&app, req.permissions)?;
+
+    let srv = server::Entity::find_by_id(&req.server_id)
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+        .ok_or((StatusCode::NOT_FOUND, "Server not found".into()))?;
+
+    let installer = server_permissions(&state, &srv.id, &claims.sub)
+        .await
+        .map_err(|e| (e, "Permission check failed".to_string()))?;
+    if !installer.contains(Permissions::MANAGE_SERVER) {
+        return Err((StatusCode::FORBIDDEN, "MANAGE_SERVER permission required to install bots".into()));
+    }
+    // The bot's role can't hold more than its installer (the owner holds everything)
+    if !installer.contains(permissions) {
+        return Err((StatusCode::FORBIDDEN, "You can't grant a bot permissions you don't have".into()));
+    }
+
+    let code = generate_code();
+    let now = chrono::Utc::now();
+    let expires_at = now + chrono::Duration::minutes(CODE_TTL_MINUTES);
+
+    let new_code = oauth_code::ActiveModel {
+        code: Set(code.clone()),
+        application_id: Set(app.id.clone()),
+        server_id: Set(srv.id.clone()),
+        user_id: Set(claims.sub.clone()),
+        redirect_uri: Set(req.redirect_uri.clone()),
+        permissions: Set(permissions.bits()),
+        expires_at: Set(expires_at.format("%Y-%m-%d %H:%M:%S").to_string()),
+        created_at: Set(now.format("%Y-%m-%d %H:%M:%S").to_string()),
+    };
+
+    oauth_code::Entity::insert(new_code)
+        .exec(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+    let mut redirect_url = reqwest::Url::parse(&req.redirect_uri)
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid redirect_uri".into()))?;
+    {
+        let mut pairs = redirect_url.query_pairs_mut();
+        pairs.append_pair("code", &code);
+        if let Some(s) = &req.state {
+            pairs.append_pair("state", s);
+        }
+    }
+
+    Ok(Json(ApproveResponse {
+        code,
+        redirect_url: redirect_url.to_string(),
+    }))
+}
+
+/// POST /api/oauth2/token — exchange an authorization code for an installed bot token
+pub async fn exchange_token(
+    State(state): State<AppState>,
+    Form(req): Form<TokenRequest>,
+) -> Result<Json<TokenResponse>, (StatusCode, String)> {
+    if req.grant_type != "authorization_code" {
+        return Err((StatusCode::BAD_REQUEST, "Unsupported grant_type".into()));
+    }
+
+    let app = application::Entity::find_by_id(&req.client_id)
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+        .ok_or((StatusCode::UNAUTHORIZED, "Invalid client credentials".into()))?;
+
+    if !bool::from(app.client_secret.as_bytes().ct_eq(req.client_secret.as_bytes())) {
+        return Err((StatusCode::UNAUTHORIZED, "Invalid client credentials".into()));
+    }
+
+    let code = oauth_code::Entity::find_by_id(&req.code)
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+        .ok_or((StatusCode::BAD_REQUEST, "Invalid or expired code".into()))?;
+
+    // Codes are single-use regardless of the outcome below; only the exchange that
+    // deletes it may go on
+    let claimed = oauth_code::Entity::delete_by_id(&code.code)
+        .exec(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+    if claimed.rows_affected != 1 {
+        return Err((StatusCode::BAD_REQUEST, "Invalid or expired code".into()));
+    }
+
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    if code.application_id != app.id || code.redirect_uri != req.redirect_uri || code.expires_at < now {
+        return Err((StatusCode::BAD_REQUEST, "Invalid or expired code".into()));
+    }
+
+    let token = generate_bot_token();
+
+    let existing = bot::Entity::find()
+        .filter(bot::Column::ApplicationId.eq(&app.id))
+        .filter(bot::Column::ServerId.eq(&code.server_id))
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+    let installed = match existing {
+        // Re-install: refresh permissions and rotate the token
+        Some(b) => {
+            bot::Entity::update_many()
+                .col_expr(bot::Column::Token, Expr::value(&token))
+                .col_expr(bot::Column::Permissions, Expr::value(code.permissions))
+                .filter(bot::Column::Id.eq(&b.id))
+                .exec(&state.db)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+            if let Some(role_id) = &b.role_id {
+                role::Entity::update_many()
+                    .col_expr(role::Column::Permissions, Expr::value(code.permissions))
+                    .filter(role::Column::Id.eq(role_id))
+                    .exec(&state.db)
+                    .await
+                    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+            }
+
+            Bot {
+                token: String::new(),
+                permissions: code.permissions,
+                ..b
+            }
+        }
+        None => install_bot(&state, &app, &code, &token, &now).await?,
+    };
+
+    create_audit_log(
+        &state.db,
+        &code.user_id,
+        "Administrator",
+        "INSTALL_BOT",
+        Some(&installed.id),
+        Some(&installed.name),
+        Some(&format!("application={} permissions={}", app.id, code.permissions)),
+    )
+    .await;
+
+    Ok(Json(TokenResponse {
+        access_token: token,
+        token_type: "Bot",
+        scope: "bot",
+        permissions: code.permissions,
+        server_id: code.server_id,
+        bot: installed,
+    }))
+}
+
+/// Create the bot row, its membership and its managed role for a first-time install
+async fn install_bot(
+    state: &AppState,
+    app: &application::Model,
+    code: &oauth_code::Model,
+    token: &str,
+    now: &str,
+) -> Result<Bot, (StatusCode, String)> {
+    let db_err = |e: DbErr| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"));
+    let bot_id = Uuid::new_v4().to_string();
+    let role_id = Uuid::new_v4().to_string();
+
+    let max_position: Option<i64> = role::Entity::find()
+        .filter(role::Column::ServerId.eq(&code.server_id))
+        .select_only()
+        .column_as(role::Column::Position.max(), "position")
+        .into_tuple()
+        .one(&state.db)
+        .await
+        .ok()
+        .flatten();
+
+    let new_role = role::ActiveModel {
+        id: Set(role_id.clone()),
+        name: Set(app.name.clone()),
+        color: Set(None),
+        position: Set(max_position.unwrap_or(0) + 1),
+        permissions: Set(code.permissions),
+        created_at: Set(now.to_string()),
+        server_id: Set(code.server_id.clone()),
+        managed: Set(true),
+        max_upload_size: Set(None),
+        storage_quota: Set(None),
+    };
+
+    let new_bot = bot::ActiveModel {
+        id: Set(bot_id.clone()),
+        name: Set(app.name.clone()),
+        avatar_url: Set(app.avatar_url.clone()),
+        owner_id: Set(app.owner_id.clone()),
+        token: Set(token.to_string()),
+        permissions: Set(code.permissions),
+        created_at: Set(now.to_string()),
+        server_id: Set(code.server_id.clone()),
+        application_id: Set(Some(app.id.clone())),
+        role_id: Set(Some(role_id.clone())),
+    };
+
+    let membership = server_member::ActiveModel {
+        server_id: Set(code.server_id.clone()),
+        user_id: Set(bot_id.clone()),
+        joined_at: Set(now.to_string()),
+        ..Default::default()
+    };
+
+    let assignment = user_role::ActiveModel {
+        user_id: Set(bot_id.clone()),
+        role_id: Set(role_id.clone()),
+        assigned_at: Set(now.to_string()),
+    };
+
+    let txn = state.db.begin().await.map_err(db_err)?;
+    role::Entity::insert(new_role).exec(&txn).await.map_err(db_err)?;
+    bot::Entity::insert(new_bot).exec(&txn).await.map_err(db_err)?;
+    server_member::Entity::insert(membership).exec(&txn).await.map_err(db_err)?;
+    user_role::Entity::insert(assignment).exec(&txn).await.map_err(db_err)?;
+    txn.commit().await.map_err(db_err)?;
+
+    Ok(Bot {
+        id: bot_id,
+        name: app.name.clone(),
+        avatar_url: app.avatar_url.clone(),
+        owner_id: app.owner_id.clone(),
+        token: String::new(),
+        permissions: code.permissions,
+        created_at: now.to_string(),
+        server_id: code.server_id.clone(),
+        application_id: Some(app.id.clone()),
+        role_id: Some(role_id),
+    })
+}
+
+/// Remove an installed bot along with its membership and managed role
+pub async fn uninstall_bot<C: ConnectionTrait>(db: &C, installed: &bot::Model) -> Result<(), DbErr> {
+    bot::Entity::delete_by_id(&installed.id).exec(db).await?;
+    server_member::Entity::delete_by_id((installed.server_id.clone(), installed.id.clone()))
+        .exec(db)
+        .await?;
+    user_role::Entity::delete_many()
+        .filter(user_role::Column::UserId.eq(&installed.id))
+        .exec(db)
+        .await?;
+    if let Some(role_id) = &installed.role_id {
+        role::Entity::delete_by_id(role_id).exec(db).await?;
+    }
+    Ok(())
+}
+
+/// Remove every installed bot of an application
+pub async fn uninstall_application_bots(
+    state: &AppState,
+    app_id: &str,
+) -> Result<(), (StatusCode, String)> {
+    let db_err = |e: DbErr| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"));
+    let bots = bot::Entity::find()
+        .filter(bot::Column::ApplicationId.eq(app_id))
+        .all(&state.db)
+        .await
+        .map_err(db_err)?;
+
+    let txn = state.db.begin().await.map_err(db_err)?;
+    for installed in &bots {
+        uninstall_bot(&txn, installed).await.map_err(db_err)?;
+    }
+    txn.commit().await.map_err(db_err)?;
+
+    Ok(())
+}
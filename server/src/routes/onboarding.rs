@@ -0,0 +1,430 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use sea_orm::*;
+use uuid::Uuid;
+
+use crate::entities::{message, server, server_member, server_screening, user};
+use crate::models::{
+    OnboardingStatus, OnboardingStatusResponse, PendingMember, Permissions, ScreeningAnswer,
+    ScreeningInfo, ScreeningQuestion, ScreeningSettings, ScreeningSubmission, WsServerMessage,
+};
+use crate::routes::audit_logs::create_audit_log;
+use crate::routes::{auth, roles};
+use crate::state::AppState;
+
+const MAX_RULES_LENGTH: usize = 4000;
+const MAX_QUESTIONS: usize = 5;
+const MAX_QUESTION_LENGTH: usize = 300;
+const MAX_ANSWER_LENGTH: usize = 1000;
+const DEFAULT_WELCOME: &str = "Welcome to {server}, {user}!";
+
+fn db_err(e: DbErr) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"))
+}
+
+fn status_of(member: &server_member::Model) -> OnboardingStatus {
+    match (member.pending, &member.screened_at) {
+        (false, _) => OnboardingStatus::Member,
+        (true, None) => OnboardingStatus::Screening,
+        (true, Some(_)) => OnboardingStatus::AwaitingApproval,
+    }
+}
+
+async fn load_settings<C: ConnectionTrait>(db: &C, server_id: &str) -> Result<ScreeningSettings, DbErr> {
+    let Some(row) = server_screening::Entity::find_by_id(server_id).one(db).await? else {
+        return Ok(ScreeningSettings::default());
+    };
+    Ok(ScreeningSettings {
+        enabled: row.enabled,
+        rules: row.rules,
+        questions: serde_json::from_str(&row.questions).unwrap_or_default(),
+        manual_approval: row.manual_approval,
+    })
+}
+
+/// Whether members joining `server_id` start out pending
+pub async fn screening_required<C: ConnectionTrait>(db: &C, server_id: &str) -> Result<bool, DbErr> {
+    Ok(server_screening::Entity::find_by_id(server_id)
+        .one(db)
+        .await?
+        .is_some_and(|s| s.enabled))
+}
+
+/// Whether `user_id` is a member of `server_id` that hasn't finished onboarding
+pub async fn is_pending(state: &AppState, server_id: &str, user_id: &str) -> Result<bool, DbErr> {
+    Ok(server_member::Entity::find_by_id((server_id.to_string(), user_id.to_string()))
+        .one(&state.db)
+        .await?
+        .is_some_and(|m| m.pending))
+}
+
+/// Post the server's welcome message for a new member in its system channel, if it has one
+pub async fn send_welcome(state: &AppState, server_id: &str, user_id: &str) {
+    let Ok(Some(srv)) = server::Entity::find_by_id(server_id).one(&state.db).await else {
+        return;
+    };
+    let Some(channel_id) = srv.system_channel_id.clone() else {
+        return;
+    };
+    let Ok(Some(member)) = user::Entity::find_by_id(user_id).one(&state.db).await else {
+        return;
+    };
+
+    let content = srv
+        .welcome_message
+        .as_deref()
+        .unwrap_or(DEFAULT_WELCOME)
+        .replace("{user}", &member.display_name)
+        .replace("{server}", &srv.name);
+    let msg_id = Uuid::new_v4().to_string();
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let author_id = format!("system:{server_id}");
+    let author_name = srv.name.clone();
+
+    let new_msg = message::ActiveModel {
+        id: Set(msg_id.clone()),
+        channel_id: Set(channel_id.clone()),
+        user_id: Set(author_id.clone()),
+        user_name: Set(author_name.clone()),
+        avatar_url: Set(srv.icon_url.clone()),
+        content: Set(content.clone()),
+        created_at: Set(now.clone()),
+        ..Default::default()
+    };
+    if let Err(e) = message::Entity::insert(new_msg).exec(&state.db).await {
+        tracing::error!("Failed to post welcome message: {e}");
+        return;
+    }
+
+    state.publish_channel(
+        &channel_id,
+        WsServerMessage::NewMessage {
+            id: msg_id,
+            channel_id: channel_id.clone(),
+            user_id: author_id,
+            user_name: author_name,
+            avatar_url: srv.icon_url,
+            content,
+            created_at: now,
+            is_bot: true,
+            reply_to: None,
+            replied_message: None,
+            attachments: Vec::new(),
+            sticker: None,
+            post_id: None,
+        },
+    );
+}
+
+/// The server, if `user_id` owns it or has `perm`
+async fn moderated_server(
+    state: &AppState,
+    user_id: &str,
+    server_id: &str,
+    perm: Permissions,
+) -> Result<server::Model, (StatusCode, String)> {
+    let srv = server::Entity::find_by_id(server_id)
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .ok_or((StatusCode::NOT_FOUND, "Server not found".to_string()))?;
+    if srv.owner_id != user_id
+        && !roles::user_has_server_permission(state, &srv.id, user_id, perm)
+            .await
+            .map_err(|s| (s, "Permission check failed".to_string()))?
+    {
+        return Err((StatusCode::FORBIDDEN, "Missing permission".into()));
+    }
+    Ok(srv)
+}
+
+/// GET /api/servers/{id}/screening — the rules and questions, and where the caller stands
+pub async fn get_screening(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(server_id): Path<String>,
+) -> Result<Json<ScreeningInfo>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let settings = load_settings(&state.db, &server_id).await.map_err(db_err)?;
+    let status = server_member::Entity::find_by_id((server_id, claims.sub))
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .map(|m| status_of(&m));
+    Ok(Json(ScreeningInfo { settings, status }))
+}
+
+/// PUT /api/servers/{id}/screening — configure screening (owner or MANAGE_SERVER).
+/// Turning it off lets everyone still pending in.
+pub async fn update_screening(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(server_id): Path<String>,
+    Json(req): Json<ScreeningSettings>,
+) -> Result<Json<ScreeningSettings>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let srv = moderated_server(&state, &claims.sub, &server_id, Permissions::MANAGE_SERVER).await?;
+
+    let rules = req.rules.trim().to_string();
+    if rules.chars().count() > MAX_RULES_LENGTH {
+        return Err((StatusCode::BAD_REQUEST, format!("Rules are at most {MAX_RULES_LENGTH} characters")));
+    }
+    if req.enabled && rules.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Screening needs rules to accept".into()));
+    }
+    if req.questions.len() > MAX_QUESTIONS {
+        return Err((StatusCode::BAD_REQUEST, format!("At most {MAX_QUESTIONS} questions")));
+    }
+    let questions: Vec<ScreeningQuestion> = req
+        .questions
+        .into_iter()
+        .map(|q| ScreeningQuestion { question: q.question.trim().to_string(), required: q.required })
+        .collect();
+    if questions
+        .iter()
+        .any(|q| q.question.is_empty() || q.question.chars().count() > MAX_QUESTION_LENGTH)
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Questions must be 1-{MAX_QUESTION_LENGTH} characters"),
+        ));
+    }
+
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let row = server_screening::ActiveModel {
+        server_id: Set(server_id.clone()),
+        enabled: Set(req.enabled),
+        rules: Set(rules.clone()),
+        questions: Set(serde_json::to_string(&questions).unwrap_or_else(|_| "[]".into())),
+        manual_approval: Set(req.manual_approval),
+        updated_at: Set(now),
+    };
+
+    let txn = state.db.begin().await.map_err(db_err)?;
+    server_screening::Entity::insert(row)
+        .on_conflict(
+            sea_query::OnConflict::column(server_screening::Column::ServerId)
+                .update_columns([
+                    server_screening::Column::Enabled,
+                    server_screening::Column::Rules,
+                    server_screening::Column::Questions,
+                    server_screening::Column::ManualApproval,
+                    server_screening::Column::UpdatedAt,
+                ])
+                .to_owned(),
+        )
+        .exec(&txn)
+        .await
+        .map_err(db_err)?;
+    if !req.enabled {
+        server_member::Entity::update_many()
+            .col_expr(server_member::Column::Pending, sea_query::Expr::value(false))
+            .filter(server_member::Column::ServerId.eq(&server_id))
+            .filter(server_member::Column::Pending.eq(true))
+            .exec(&txn)
+            .await
+            .map_err(db_err)?;
+    }
+    txn.commit().await.map_err(db_err)?;
+
+    create_audit_log(
+        &state.db,
+        &claims.sub,
+        &claims.username,
+        "UPDATE_SCREENING",
+        Some(&server_id),
+        Some(&srv.name),
+        Some(if req.enabled { "enabled" } else { "disabled" }),
+    )
+    .await;
+
+    Ok(Json(ScreeningSettings {
+        enabled: req.enabled,
+        rules,
+        questions,
+        manual_approval: req.manual_approval,
+    }))
+}
+
+/// POST /api/servers/{id}/screening — accept the rules and answer the questions
+pub async fn submit_screening(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(server_id): Path<String>,
+    Json(req): Json<ScreeningSubmission>,
+) -> Result<Json<OnboardingStatusResponse>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let member = server_member::Entity::find_by_id((server_id.clone(), claims.sub.clone()))
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .ok_or((StatusCode::NOT_FOUND, "You are not a member of this server".to_string()))?;
+    match status_of(&member) {
+        OnboardingStatus::Screening => {}
+        status => return Ok(Json(OnboardingStatusResponse { status })),
+    }
+
+    let settings = load_settings(&state.db, &server_id).await.map_err(db_err)?;
+    if !req.accept_rules {
+        return Err((StatusCode::BAD_REQUEST, "You must accept the rules".into()));
+    }
+    if req.answers.len() > settings.questions.len() {
+        return Err((StatusCode::BAD_REQUEST, "Too many answers".into()));
+    }
+    let mut answers = Vec::with_capacity(settings.questions.len());
+    for (i, q) in settings.questions.iter().enumerate() {
+        let answer = req.answers.get(i).map(|a| a.trim()).unwrap_or_default();
+        if q.required && answer.is_empty() {
+            return Err((StatusCode::BAD_REQUEST, format!("\"{}\" needs an answer", q.question)));
+        }
+        if answer.chars().count() > MAX_ANSWER_LENGTH {
+            return Err((StatusCode::BAD_REQUEST, format!("Answers are at most {MAX_ANSWER_LENGTH} characters")));
+        }
+        answers.push(ScreeningAnswer { question: q.question.clone(), answer: answer.to_string() });
+    }
+
+    let admitted = !settings.manual_approval;
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let mut update: server_member::ActiveModel = member.into();
+    update.screened_at = Set(Some(now));
+    update.screening_answers = Set(serde_json::to_string(&answers).ok());
+    update.pending = Set(!admitted);
+    update.update(&state.db).await.map_err(db_err)?;
+
+    if admitted {
+        send_welcome(&state, &server_id, &claims.sub).await;
+        Ok(Json(OnboardingStatusResponse { status: OnboardingStatus::Member }))
+    } else {
+        Ok(Json(OnboardingStatusResponse { status: OnboardingStatus::AwaitingApproval }))
+    }
+}
+
+/// GET /api/servers/{id}/pending-members — members who haven't finished onboarding (KICK_MEMBERS)
+pub async fn list_pending_members(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(server_id): Path<String>,
+) -> Result<Json<Vec<PendingMember>>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    moderated_server(&state, &claims.sub, &server_id, Permissions::KICK_MEMBERS).await?;
+
+    let rows = server_member::Entity::find()
+        .filter(server_member::Column::ServerId.eq(&server_id))
+        .filter(server_member::Column::Pending.eq(true))
+        .find_also_related(user::Entity)
+        .order_by_asc(server_member::Column::JoinedAt)
+        .all(&state.db)
+        .await
+        .map_err(db_err)?;
+
+    Ok(Json(
+        rows.into_iter()
+            .filter_map(|(m, u)| {
+                let u = u?;
+                Some(PendingMember {
+                    user_id: u.id,
+                    display_name: u.display_name,
+                    avatar_url: u.avatar_url,
+                    joined_at: m.joined_at,
+                    screened_at: m.screened_at,
+                    answers: m
+                        .screening_answers
+                        .and_then(|a| serde_json::from_str(&a).ok())
+                        .unwrap_or_default(),
+                })
+            })
+            .collect(),
+    ))
+}
+
+async fn find_pending(
+    state: &AppState,
+    server_id: &str,
+    user_id: &str,
+) -> Result<server_member::Model, (StatusCode, String)> {
+    server_member::Entity::find_by_id((server_id.to_string(), user_id.to_string()))
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .filter(|m| m.pending)
+        .ok_or((StatusCode::NOT_FOUND, "No pending member with that id".to_string()))
+}
+
+/// POST /api/servers/{id}/pending-members/{user_id}/approve (KICK_MEMBERS)
+pub async fn approve_member(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((server_id, user_id)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let srv = moderated_server(&state, &claims.sub, &server_id, Permissions::KICK_MEMBERS).await?;
+    let member = find_pending(&state, &server_id, &user_id).await?;
+
+    let mut update: server_member::ActiveModel = member.into();
+    update.pending = Set(false);
+    update.update(&state.db).await.map_err(db_err)?;
+
+    create_audit_log(
+        &state.db,
+        &claims.sub,
+        &claims.username,
+        "APPROVE_MEMBER",
+        Some(&user_id),
+        None,
+        Some(&srv.name),
+    )
+    .await;
+
+    send_welcome(&state, &server_id, &user_id).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// DELETE /api/servers/{id}/pending-members/{user_id} — turn a pending member away (KICK_MEMBERS)
+pub async fn reject_member(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((server_id, user_id)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let srv = moderated_server(&state, &claims.sub, &server_id, Permissions::KICK_MEMBERS).await?;
+    find_pending(&state, &server_id, &user_id).await?;
+
+    server_member::Entity::delete_many()
+        .filter(server_member::Column::ServerId.eq(&server_id))
+        .filter(server_member::Column::UserId.eq(&user_id))
+        .exec(&state.db)
+        .await
+        .map_err(db_err)?;
+    state.membership_changed(&user_id, &server_id, false);
+
+    create_audit_log(
+        &state.db,
+        &claims.sub,
+        &claims.username,
+        "REJECT_MEMBER",
+        Some(&user_id),
+        None,
+        Some(&srv.name),
+    )
+    .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn moderating_one_server_grants_nothing_in_another() {
+        let state = AppState::for_tests().await;
+        roles::seed_role_in_one_server(&state, "u", "a", "b", Permissions::KICK_MEMBERS).await;
+
+        assert!(moderated_server(&state, "u", "a", Permissions::KICK_MEMBERS).await.is_ok());
+        let err = moderated_server(&state, "u", "b", Permissions::KICK_MEMBERS).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,111 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use sea_orm::*;
+use serde::{Deserialize, Serialize};
+
+use crate::entities::user;
+use crate::presence::{broadcast_presence, Presence, PresenceSettings, PresenceStatus};
+use crate::pubsub::BusEvent;
+use crate::routes::auth;
+use crate::state::AppState;
+
+const MAX_CUSTOM_STATUS: usize = 128;
+
+#[derive(Debug, Deserialize)]
+pub struct UpdatePresenceRequest {
+    /// online, idle, dnd or invisible
+    pub status: Option<String>,
+    /// Empty string clears the custom status
+    pub custom_status: Option<String>,
+    /// Seconds until the custom status clears itself (omit to keep it until changed)
+    pub custom_status_expires_in: Option<i64>,
+}
+
+/// The caller's own chosen presence (may be invisible, unlike what others see)
+#[derive(Debug, Serialize)]
+pub struct PresenceSettingsResponse {
+    pub status: PresenceStatus,
+    pub custom_status: Option<String>,
+    pub custom_status_expires_at: Option<String>,
+}
+
+/// PUT /api/me/presence — set status and custom status (persisted across restarts)
+pub async fn update_presence(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<UpdatePresenceRequest>,
+) -> Result<Json<PresenceSettingsResponse>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+
+    let existing = user::Entity::find_by_id(&claims.sub)
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+        .ok_or((StatusCode::NOT_FOUND, "User not found".into()))?;
+
+    let mut settings = PresenceSettings::from_user(&existing);
+
+    if let Some(status) = req.status {
+        settings.status = PresenceStatus::parse_chosen(&status).ok_or((
+            StatusCode::BAD_REQUEST,
+            "Status must be one of online, idle, dnd, invisible".into(),
+        ))?;
+    }
+    if let Some(text) = req.custom_status {
+        let text = text.trim().to_string();
+        if text.chars().count() > MAX_CUSTOM_STATUS {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Custom status must be at most {MAX_CUSTOM_STATUS} characters"),
+            ));
+        }
+        settings.custom_status = (!text.is_empty()).then_some(text);
+        settings.custom_status_expires_at = None;
+    }
+    if let Some(secs) = req.custom_status_expires_in {
+        if secs <= 0 || secs > 60 * 60 * 24 * 30 {
+            return Err((StatusCode::BAD_REQUEST, "Expiry must be between 1 second and 30 days".into()));
+        }
+        settings.custom_status_expires_at = Some(chrono::Utc::now() + chrono::Duration::seconds(secs));
+    }
+
+    let expires_at = settings
+        .custom_status_expires_at
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string());
+
+    let mut active: user::ActiveModel = existing.into();
+    active.status = Set(settings.status.as_str().to_string());
+    active.custom_status = Set(settings.custom_status.clone());
+    active.custom_status_expires_at = Set(expires_at.clone());
+    active
+        .update(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+    let response = PresenceSettingsResponse {
+        status: settings.status,
+        custom_status: settings.custom_status.clone(),
+        custom_status_expires_at: expires_at,
+    };
+
+    if let Some(presence) = state.presence.update(&claims.sub, settings) {
+        broadcast_presence(&state, &claims.sub, presence).await;
+    }
+    // Connections on other instances pick the new settings up from the database
+    state.pubsub.publish(&BusEvent::PresenceSettings { user_id: claims.sub.clone() });
+
+    Ok(Json(response))
+}
+
+/// GET /api/users/:user_id/presence — presence as other users see it
+pub async fn get_presence(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+) -> Result<Json<Presence>, (StatusCode, String)> {
+    auth::extract_claims(&state.jwt_secret, &headers)?;
+    Ok(Json(state.user_presence(&user_id)))
+}
@@ -0,0 +1,174 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use sea_orm::*;
+use sea_orm::prelude::Expr;
+use serde::Deserialize;
+
+use crate::blobs;
+use crate::entities::{custom_emoji, message_attachment, sticker, upload, user};
+use crate::models::UploaderUsage;
+use crate::routes::audit_logs::create_audit_log;
+use crate::routes::auth;
+use crate::routes::roles::is_instance_admin;
+use crate::state::AppState;
+
+const DEFAULT_UPLOADERS: u64 = 25;
+const MAX_UPLOADERS: u64 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct UploadersQuery {
+    /// Only count files shared in this server
+    pub server_id: Option<String>,
+    pub limit: Option<u64>,
+}
+
+async fn require_instance_admin(state: &AppState, headers: &HeaderMap) -> Result<auth::Claims, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, headers)?;
+    if !is_instance_admin(state, &claims.sub).await.map_err(|s| (s, "Permission check failed".to_string()))? {
+        return Err((StatusCode::FORBIDDEN, "Instance admins only".into()));
+    }
+    Ok(claims)
+}
+
+fn server_filter(server_id: Option<&str>) -> Condition {
+    match server_id {
+        Some(id) => Condition::all().add(upload::Column::ServerId.eq(id)),
+        None => Condition::all(),
+    }
+}
+
+/// Users storing the most bytes, largest first (instance admins only)
+pub async fn list_uploaders(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<UploadersQuery>,
+) -> Result<Json<Vec<UploaderUsage>>, (StatusCode, String)> {
+    require_instance_admin(&state, &headers).await?;
+    let limit = query.limit.unwrap_or(DEFAULT_UPLOADERS).clamp(1, MAX_UPLOADERS);
+
+    let totals: Vec<(String, Option<i64>, i64)> = upload::Entity::find()
+        .select_only()
+        .column(upload::Column::UserId)
+        .column_as(upload::Column::Size.sum(), "bytes")
+        .column_as(upload::Column::Id.count(), "files")
+        .filter(server_filter(query.server_id.as_deref()))
+        .group_by(upload::Column::UserId)
+        .order_by(Expr::cust("bytes"), Order::Desc)
+        .limit(limit)
+        .into_tuple()
+        .all(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+    let user_ids: Vec<&String> = totals.iter().map(|(id, _, _)| id).collect();
+    let users: std::collections::HashMap<String, user::Model> = user::Entity::find()
+        .filter(user::Column::Id.is_in(user_ids))
+        .all(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+        .into_iter()
+        .map(|u| (u.id.clone(), u))
+        .collect();
+
+    let result = totals
+        .into_iter()
+        .map(|(user_id, bytes, files)| {
+            let user = users.get(&user_id);
+            UploaderUsage {
+                username: user.map(|u| u.username.clone()).unwrap_or_default(),
+                display_name: user.map(|u| u.display_name.clone()).unwrap_or_default(),
+                user_id,
+                bytes: bytes.unwrap_or(0),
+                files,
+            }
+        })
+        .collect();
+    Ok(Json(result))
+}
+
+/// Delete every file `user_id` uploaded (optionally only in one server), detaching
+/// them from messages and clearing emoji, stickers and avatars that used them (instance admins only)
+pub async fn purge_uploader(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+    Query(query): Query<UploadersQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let claims = require_instance_admin(&state, &headers).await?;
+    let db_err = |e: DbErr| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"));
+
+    let uploads = upload::Entity::find()
+        .filter(upload::Column::UserId.eq(&user_id))
+        .filter(server_filter(query.server_id.as_deref()))
+        .all(&state.db)
+        .await
+        .map_err(db_err)?;
+
+    let mut bytes = 0i64;
+    for row in &uploads {
+        message_attachment::Entity::delete_by_id(&row.id)
+            .exec(&state.db)
+            .await
+            .map_err(db_err)?;
+        custom_emoji::Entity::delete_many()
+            .filter(custom_emoji::Column::UploadId.eq(&row.id))
+            .exec(&state.db)
+            .await
+            .map_err(db_err)?;
+        sticker::Entity::delete_many()
+            .filter(sticker::Column::UploadId.eq(&row.id))
+            .exec(&state.db)
+            .await
+            .map_err(db_err)?;
+        user::Entity::update_many()
+            .col_expr(user::Column::AvatarUrl, Expr::value(Option::<String>::None))
+            .filter(user::Column::AvatarUrl.eq(format!("/api/uploads/{}", row.id)))
+            .exec(&state.db)
+            .await
+            .map_err(db_err)?;
+        blobs::delete_upload(&state, row).await.map_err(db_err)?;
+        bytes += row.size;
+    }
+
+    let details = format!("{} files, {bytes} bytes", uploads.len());
+    create_audit_log(
+        &state.db,
+        &claims.sub,
+        &claims.username,
+        "PURGE_UPLOADS",
+        Some(&user_id),
+        None,
+        Some(&details),
+    )
+    .await;
+
+    Ok(Json(serde_json::json!({ "removed": uploads.len(), "bytes": bytes })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GcQuery {
+    /// Only report what would be removed
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Run the orphaned-upload GC now, or preview it with `?dry_run=true` (instance admins only)
+pub async fn run_gc(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<GcQuery>,
+) -> Result<Json<blobs::OrphanReport>, (StatusCode, String)> {
+    let claims = require_instance_admin(&state, &headers).await?;
+    let report = blobs::collect_orphans(&state, state.uploads.orphan_grace, query.dry_run)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+    if !query.dry_run {
+        let details = format!("{} uploads, {} bytes reclaimed", report.uploads, report.bytes_reclaimed);
+        create_audit_log(&state.db, &claims.sub, &claims.username, "UPLOAD_GC", None, None, Some(&details)).await;
+    }
+    Ok(Json(report))
+}
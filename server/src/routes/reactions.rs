@@ -7,9 +7,9 @@ use sea_orm::*;
 use serde::Deserialize;
 use uuid::Uuid;
 
-use crate::entities::{message, reaction, user};
+use crate::entities::{channel, message, reaction, user};
 use crate::models::{Permissions, WsServerMessage};
-use crate::routes::{auth, roles::user_has_permission};
+use crate::routes::{auth, onboarding, roles::user_has_permission};
 use crate::state::AppState;
 
 #[derive(Debug, Deserialize)]
@@ -43,6 +43,18 @@ pub async fn add_reaction(
     {
         return Err((StatusCode::FORBIDDEN, "ADD_REACTIONS permission required".into()));
     }
+    let server_id = channel::Entity::find_by_id(&msg.channel_id)
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+        .map(|c| c.server_id)
+        .unwrap_or_default();
+    if onboarding::is_pending(&state, &server_id, &claims.sub)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+    {
+        return Err((StatusCode::FORBIDDEN, "Finish onboarding before reacting".into()));
+    }
 
     let reaction_id = Uuid::new_v4().to_string();
     let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
@@ -87,8 +99,7 @@ pub async fn add_reaction(
                 emoji: emoji.to_string(),
             };
 
-            let tx = state.get_channel_tx(&msg.channel_id);
-            let _ = tx.send(broadcast_msg);
+            state.publish_channel(&msg.channel_id, broadcast_msg);
 
             Ok(StatusCode::CREATED)
         }
@@ -129,8 +140,7 @@ pub async fn remove_reaction(
             emoji,
         };
 
-        let tx = state.get_channel_tx(&msg.channel_id);
-        let _ = tx.send(broadcast_msg);
+        state.publish_channel(&msg.channel_id, broadcast_msg);
     }
 
     Ok(StatusCode::NO_CONTENT)
@@ -6,7 +6,7 @@ use axum::{
 use sea_orm::*;
 use uuid::Uuid;
 
-use crate::entities::{role, user_role};
+use crate::entities::{role, server, server_member, user_role};
 use crate::models::{
     AssignRoleRequest, CreateRoleRequest, Permissions, Role, RoleWithMembers, UpdateRoleRequest,
 };
@@ -42,6 +42,77 @@ pub async fn user_has_permission(
     Ok(false)
 }
 
+// ─── Helper: A member's permissions in one server ───
+// The owner has every permission. Other members get the union of their roles in
+// the server, or the default member permissions if they hold none; non-members get none.
+pub async fn server_permissions(
+    state: &AppState,
+    server_id: &str,
+    user_id: &str,
+) -> Result<Permissions, StatusCode> {
+    let srv = server::Entity::find_by_id(server_id)
+        .one(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if srv.owner_id == user_id {
+        return Ok(Permissions::all());
+    }
+
+    let is_member = server_member::Entity::find_by_id((server_id.to_string(), user_id.to_string()))
+        .one(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .is_some();
+    if !is_member {
+        return Ok(Permissions::empty());
+    }
+
+    let roles: Vec<role::Model> = role::Entity::find()
+        .inner_join(user_role::Entity)
+        .filter(user_role::Column::UserId.eq(user_id))
+        .filter(role::Column::ServerId.eq(server_id))
+        .all(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if roles.is_empty() {
+        return Ok(Permissions::default_member());
+    }
+
+    let perms = roles
+        .iter()
+        .fold(Permissions::empty(), |acc, r| acc | Permissions::from_bits_truncate(r.permissions));
+    if perms.contains(Permissions::ADMINISTRATOR) {
+        return Ok(Permissions::all());
+    }
+    Ok(perms)
+}
+
+// ─── Helper: Check a permission in one server ───
+pub async fn user_has_server_permission(
+    state: &AppState,
+    server_id: &str,
+    user_id: &str,
+    required: Permissions,
+) -> Result<bool, StatusCode> {
+    Ok(server_permissions(state, server_id, user_id).await?.contains(required))
+}
+
+// ─── Helper: Check if user is an instance admin (ADMINISTRATOR on the default server) ───
+pub async fn is_instance_admin(state: &AppState, user_id: &str) -> Result<bool, StatusCode> {
+    let roles: Vec<role::Model> = role::Entity::find()
+        .inner_join(user_role::Entity)
+        .filter(user_role::Column::UserId.eq(user_id))
+        .filter(role::Column::ServerId.eq("default"))
+        .all(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(roles
+        .iter()
+        .any(|r| Permissions::from_bits_truncate(r.permissions).contains(Permissions::ADMINISTRATOR)))
+}
+
 // ─── List all roles ───
 pub async fn list_roles(
     State(state): State<AppState>,
@@ -134,6 +205,9 @@ pub async fn create_role(
         permissions: Set(req.permissions),
         created_at: Set(now.clone()),
         server_id: Set(server_id.clone()),
+        managed: Set(false),
+        max_upload_size: Set(None),
+        storage_quota: Set(None),
     };
 
     role::Entity::insert(new_role)
@@ -152,6 +226,9 @@ pub async fn create_role(
         permissions: req.permissions,
         created_at: now,
         server_id,
+        managed: false,
+        max_upload_size: None,
+        storage_quota: None,
     };
 
     Ok((StatusCode::CREATED, Json(role)))
@@ -176,11 +253,24 @@ pub async fn update_role(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::NOT_FOUND)?;
 
+    // Managed roles follow their bot's granted permissions
+    if existing.managed {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
     // Update fields
     let name = req.name.unwrap_or(existing.name);
     let color = req.color.or(existing.color);
     let position = req.position.unwrap_or(existing.position);
     let permissions = req.permissions.unwrap_or(existing.permissions);
+    let max_upload_size = match req.max_upload_size {
+        Some(limit) => (limit > 0).then_some(limit),
+        None => existing.max_upload_size,
+    };
+    let storage_quota = match req.storage_quota {
+        Some(quota) => (quota > 0).then_some(quota),
+        None => existing.storage_quota,
+    };
 
     let mut update = role::ActiveModel {
         id: Set(role_id.clone()),
@@ -190,6 +280,8 @@ pub async fn update_role(
     update.color = Set(color.clone());
     update.position = Set(position);
     update.permissions = Set(permissions);
+    update.max_upload_size = Set(max_upload_size);
+    update.storage_quota = Set(storage_quota);
 
     role::Entity::update(update)
         .exec(&state.db)
@@ -207,6 +299,9 @@ pub async fn update_role(
         permissions,
         created_at: existing.created_at,
         server_id: existing.server_id,
+        managed: false,
+        max_upload_size,
+        storage_quota,
     }))
 }
 
@@ -226,6 +321,16 @@ pub async fn delete_role(
         return Err(StatusCode::FORBIDDEN);
     }
 
+    // Managed roles are removed together with their bot
+    let existing = role::Entity::find_by_id(&role_id)
+        .one(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if existing.managed {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
     role::Entity::delete_by_id(&role_id)
         .exec(&state.db)
         .await
@@ -249,13 +354,15 @@ pub async fn assign_role(
     }
 
     // Check if role exists
-    let exists = role::Entity::find_by_id(&req.role_id)
-        .count(&state.db)
+    let target_role = role::Entity::find_by_id(&req.role_id)
+        .one(&state.db)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
 
-    if exists == 0 {
-        return Err(StatusCode::NOT_FOUND);
+    // Managed roles belong to a single bot and cannot be handed out
+    if target_role.managed {
+        return Err(StatusCode::FORBIDDEN);
     }
 
     let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
@@ -326,3 +433,18 @@ pub async fn get_user_roles(
 
     Ok(Json(roles))
 }
+
+/// Two servers owned by someone else, both joined by `user_id`, who holds a
+/// role with `permissions` in `granted_in` only
+#[cfg(test)]
+pub async fn seed_role_in_one_server(state: &AppState, user_id: &str, granted_in: &str, other: &str, permissions: Permissions) {
+    let sql = format!(
+        "INSERT INTO users (id, username, display_name, password_hash) VALUES ('{user_id}', '{user_id}', '{user_id}', 'x');
+         INSERT INTO servers (id, name, owner_id) VALUES ('{granted_in}', '{granted_in}', 'someone-else'), ('{other}', '{other}', 'someone-else');
+         INSERT INTO server_members (server_id, user_id) VALUES ('{granted_in}', '{user_id}'), ('{other}', '{user_id}');
+         INSERT INTO roles (id, name, permissions, server_id, created_at) VALUES ('{granted_in}-role', 'Staff', {}, '{granted_in}', datetime('now'));
+         INSERT INTO user_roles (user_id, role_id, assigned_at) VALUES ('{user_id}', '{granted_in}-role', datetime('now'));",
+        permissions.bits()
+    );
+    state.db.execute_unprepared(&sql).await.unwrap();
+}
@@ -1,17 +1,22 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::{HeaderMap, StatusCode},
     Json,
 };
 use sea_orm::*;
 use sea_orm::prelude::Expr;
+use sea_orm::sea_query::LikeExpr;
 use uuid::Uuid;
 
 use crate::entities::{ban, bot, category, channel, invite_code, role, server, server_member, user, user_role};
-use crate::models::{CreateServerRequest, Permissions, Server, ServerMember};
+use crate::models::{
+    CreateServerRequest, Permissions, Server, ServerMember, TransferOwnershipRequest, WsServerMessage,
+};
+use crate::presence::{Presence, PresenceStatus};
 use crate::routes::auth::extract_claims;
 use crate::routes::audit_logs::create_audit_log;
 use crate::state::AppState;
+use crate::templates;
 
 /// Helper: extract server_id from X-Server-Id header, defaulting to "default"
 pub fn extract_server_id(headers: &HeaderMap) -> String {
@@ -48,16 +53,30 @@ pub async fn create_server(
     State(state): State<AppState>,
     headers: HeaderMap,
     Json(req): Json<CreateServerRequest>,
-) -> Result<(StatusCode, Json<Server>), StatusCode> {
-    let claims = extract_claims(&state.jwt_secret, &headers).map_err(|e| e.0)?;
+) -> Result<(StatusCode, Json<Server>), (StatusCode, String)> {
+    let claims = extract_claims(&state.jwt_secret, &headers)?;
 
     let name = req.name.trim().to_string();
     if name.is_empty() || name.len() > 100 {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err((StatusCode::BAD_REQUEST, "Server name must be 1-100 characters".into()));
     }
 
+    let template = match (req.template_code, req.template) {
+        (Some(code), _) => Some(
+            crate::routes::templates::find_by_code(&state, &code)
+                .await?
+                .ok_or((StatusCode::NOT_FOUND, "Template not found".to_string()))?,
+        ),
+        (None, template) => template,
+    };
+    if let Some(t) = &template {
+        templates::validate(t).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+    let settings = template.as_ref().map(|t| t.settings.clone()).unwrap_or_default();
+
     let description = req
         .description
+        .or_else(|| template.as_ref().map(|t| t.description.clone()))
         .unwrap_or_else(|| "Welcome!".to_string())
         .chars()
         .take(500)
@@ -75,35 +94,24 @@ pub async fn create_server(
         owner_id: Set(claims.sub.clone()),
         join_sound_url: Set(None),
         leave_sound_url: Set(None),
-        sound_chance: Set(100),
+        sound_chance: Set(settings.sound_chance),
+        allowed_upload_types: Set(settings.allowed_upload_types.clone()),
+        max_upload_size: Set(settings.max_upload_size),
+        storage_quota: Set(None),
+        system_channel_id: Set(None),
+        welcome_message: Set(None),
+        discoverable: Set(false),
+        tags: Set(None),
         created_at: Set(now.clone()),
         updated_at: Set(None),
     };
 
-    let category_id = Uuid::new_v4().to_string();
-
-    let default_category = category::ActiveModel {
-        id: Set(category_id.clone()),
-        name: Set("Uncategorized".to_string()),
-        position: Set(0),
-        server_id: Set(server_id.clone()),
-    };
-
-    // Insert server and default category in a transaction
-
     server::Entity::insert(new_server)
         .exec(&state.db)
         .await
         .map_err(|e| {
             tracing::error!("Failed to create server: {e}");
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-    category::Entity::insert(default_category)
-        .exec(&state.db)
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to create default category: {e}");
-            StatusCode::INTERNAL_SERVER_ERROR
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"))
         })?;
 
     // Add creator as member
@@ -111,14 +119,20 @@ pub async fn create_server(
         server_id: Set(server_id.clone()),
         user_id: Set(claims.sub.clone()),
         joined_at: Set(now.clone()),
+        temporary: Set(false),
+        nickname: Set(None),
+        pending: Set(false),
+        screened_at: Set(None),
+        screening_answers: Set(None),
     };
     server_member::Entity::insert(member)
         .exec(&state.db)
         .await
         .map_err(|e| {
             tracing::error!("Failed to add creator as member: {e}");
-            StatusCode::INTERNAL_SERVER_ERROR
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"))
         })?;
+    state.membership_changed(&claims.sub, &server_id, true);
 
     // Create an Admin role for this server
     let admin_role_id = format!("{}-admin", server_id);
@@ -130,13 +144,16 @@ pub async fn create_server(
         permissions: Set(Permissions::ADMINISTRATOR.bits()),
         created_at: Set(now.clone()),
         server_id: Set(server_id.clone()),
+        managed: Set(false),
+        max_upload_size: Set(None),
+        storage_quota: Set(None),
     };
     role::Entity::insert(admin_role)
         .exec(&state.db)
         .await
         .map_err(|e| {
             tracing::error!("Failed to create admin role: {e}");
-            StatusCode::INTERNAL_SERVER_ERROR
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"))
         })?;
 
     // Assign admin role to creator
@@ -156,46 +173,24 @@ pub async fn create_server(
         .await
         .map_err(|e| {
             tracing::error!("Failed to assign admin role: {e}");
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-
-    // Create default channels
-    let general_id = Uuid::new_v4().to_string();
-    let general_ch = channel::ActiveModel {
-        id: Set(general_id.clone()),
-        name: Set("general".to_string()),
-        description: Set("General chat".to_string()),
-        position: Set(0),
-        channel_type: Set("text".to_string()),
-        server_id: Set(server_id.clone()),
-        category_id: Set(Some(category_id.clone())),
-        ..Default::default()};
-    channel::Entity::insert(general_ch)
-        .exec(&state.db)
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to create general channel: {e}");
-            StatusCode::INTERNAL_SERVER_ERROR
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"))
         })?;
 
-    let voice_id = Uuid::new_v4().to_string();
-    let voice_ch = channel::ActiveModel {
-        id: Set(voice_id.clone()),
-        name: Set("Voice Lounge".to_string()),
-        description: Set("Voice channel".to_string()),
-        position: Set(1),
-        channel_type: Set("voice".to_string()),
-        server_id: Set(server_id.clone()),
-        category_id: Set(Some(category_id.clone())),
-        ..Default::default()
-    };
-    channel::Entity::insert(voice_ch)
-        .exec(&state.db)
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to create voice channel: {e}");
-            StatusCode::INTERNAL_SERVER_ERROR
+    if let Some(t) = &template {
+        // The template's layout replaces the default one
+        let applied = async {
+            let txn = state.db.begin().await?;
+            templates::apply(&txn, &server_id, &admin_role_id, t, &now).await?;
+            txn.commit().await
+        }
+        .await;
+        applied.map_err(|e| {
+            tracing::error!("Failed to apply server template: {e}");
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"))
         })?;
+    } else {
+        create_default_layout(&state, &server_id).await?;
+    }
 
     // Create a server-specific invite code
     let invite_code_val = crate::token::generate_invite_code();
@@ -205,6 +200,8 @@ pub async fn create_server(
         max_uses: Set(None),
         uses: Set(0),
         server_id: Set(server_id.clone()),
+        created_by: Set(Some(claims.sub.clone())),
+        ..Default::default()
     };
     invite_code::Entity::insert(ic)
         .exec(&state.db)
@@ -230,7 +227,14 @@ pub async fn create_server(
         owner_id: claims.sub,
         join_sound_url: None,
         leave_sound_url: None,
-        sound_chance: 100,
+        sound_chance: settings.sound_chance,
+        allowed_upload_types: settings.allowed_upload_types,
+        max_upload_size: settings.max_upload_size,
+        storage_quota: None,
+        system_channel_id: None,
+        welcome_message: None,
+        discoverable: false,
+        tags: None,
         created_at: now,
         updated_at: None,
     };
@@ -238,6 +242,65 @@ pub async fn create_server(
     Ok((StatusCode::CREATED, Json(server)))
 }
 
+/// The "Uncategorized" category with a text and a voice channel
+async fn create_default_layout(state: &AppState, server_id: &str) -> Result<(), (StatusCode, String)> {
+    let category_id = Uuid::new_v4().to_string();
+
+    let default_category = category::ActiveModel {
+        id: Set(category_id.clone()),
+        name: Set("Uncategorized".to_string()),
+        position: Set(0),
+        server_id: Set(server_id.to_string()),
+    };
+
+    category::Entity::insert(default_category)
+        .exec(&state.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to create default category: {e}");
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"))
+        })?;
+
+    let general_id = Uuid::new_v4().to_string();
+    let general_ch = channel::ActiveModel {
+        id: Set(general_id.clone()),
+        name: Set("general".to_string()),
+        description: Set("General chat".to_string()),
+        position: Set(0),
+        channel_type: Set("text".to_string()),
+        server_id: Set(server_id.to_string()),
+        category_id: Set(Some(category_id.clone())),
+        ..Default::default()};
+    channel::Entity::insert(general_ch)
+        .exec(&state.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to create general channel: {e}");
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"))
+        })?;
+
+    let voice_id = Uuid::new_v4().to_string();
+    let voice_ch = channel::ActiveModel {
+        id: Set(voice_id.clone()),
+        name: Set("Voice Lounge".to_string()),
+        description: Set("Voice channel".to_string()),
+        position: Set(1),
+        channel_type: Set("voice".to_string()),
+        server_id: Set(server_id.to_string()),
+        category_id: Set(Some(category_id.clone())),
+        ..Default::default()
+    };
+    channel::Entity::insert(voice_ch)
+        .exec(&state.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to create voice channel: {e}");
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"))
+        })?;
+
+    Ok(())
+}
+
 // ─── Get a specific server ───
 pub async fn get_server(
     State(state): State<AppState>,
@@ -279,6 +342,43 @@ pub async fn update_server(
         }
     }
 
+    // Upload allow-lists may only name types we can verify by sniffing
+    if let Some(types) = &req.allowed_upload_types {
+        if types.iter().any(|t| !crate::sniff::SUPPORTED_TYPES.contains(&t.as_str())) {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+
+    // Welcome messages go to a text channel of this server
+    if let Some(channel_id) = req.system_channel_id.as_deref().filter(|c| !c.is_empty()) {
+        let valid = channel::Entity::find_by_id(channel_id)
+            .one(&state.db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .is_some_and(|c| c.server_id == server_id && c.channel_type == "text");
+        if !valid {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+    if req.welcome_message.as_ref().is_some_and(|m| m.chars().count() > 500) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Listed servers need a description; tags must be valid
+    let tags = match &req.tags {
+        Some(tags) => Some(crate::routes::discovery::normalize_tags(tags).ok_or(StatusCode::BAD_REQUEST)?),
+        None => None,
+    };
+    let description = req.description.as_deref().unwrap_or(&server.description);
+    if req.discoverable == Some(true) && description.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Server admins must not be able to lift their own storage quota
+    if req.storage_quota.is_some() && !crate::routes::roles::is_instance_admin(&state, &claims.sub).await? {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
     let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
 
     if let Some(name) = &req.name {
@@ -331,6 +431,79 @@ pub async fn update_server(
             .ok();
     }
 
+    if let Some(limit) = req.max_upload_size {
+        server::Entity::update_many()
+            .col_expr(server::Column::MaxUploadSize, Expr::value((limit > 0).then_some(limit)))
+            .col_expr(server::Column::UpdatedAt, Expr::value(now.clone()))
+            .filter(server::Column::Id.eq(&server_id))
+            .exec(&state.db)
+            .await
+            .ok();
+    }
+
+    if let Some(quota) = req.storage_quota {
+        server::Entity::update_many()
+            .col_expr(server::Column::StorageQuota, Expr::value((quota > 0).then_some(quota)))
+            .col_expr(server::Column::UpdatedAt, Expr::value(now.clone()))
+            .filter(server::Column::Id.eq(&server_id))
+            .exec(&state.db)
+            .await
+            .ok();
+    }
+
+    if let Some(channel_id) = &req.system_channel_id {
+        server::Entity::update_many()
+            .col_expr(server::Column::SystemChannelId, Expr::value((!channel_id.is_empty()).then(|| channel_id.clone())))
+            .col_expr(server::Column::UpdatedAt, Expr::value(now.clone()))
+            .filter(server::Column::Id.eq(&server_id))
+            .exec(&state.db)
+            .await
+            .ok();
+    }
+
+    if let Some(text) = &req.welcome_message {
+        let text = text.trim();
+        server::Entity::update_many()
+            .col_expr(server::Column::WelcomeMessage, Expr::value((!text.is_empty()).then(|| text.to_string())))
+            .col_expr(server::Column::UpdatedAt, Expr::value(now.clone()))
+            .filter(server::Column::Id.eq(&server_id))
+            .exec(&state.db)
+            .await
+            .ok();
+    }
+
+    if let Some(discoverable) = req.discoverable {
+        server::Entity::update_many()
+            .col_expr(server::Column::Discoverable, Expr::value(discoverable))
+            .col_expr(server::Column::UpdatedAt, Expr::value(now.clone()))
+            .filter(server::Column::Id.eq(&server_id))
+            .exec(&state.db)
+            .await
+            .ok();
+    }
+
+    if let Some(tags) = tags {
+        let value = (!tags.is_empty()).then(|| tags.join(","));
+        server::Entity::update_many()
+            .col_expr(server::Column::Tags, Expr::value(value))
+            .col_expr(server::Column::UpdatedAt, Expr::value(now.clone()))
+            .filter(server::Column::Id.eq(&server_id))
+            .exec(&state.db)
+            .await
+            .ok();
+    }
+
+    if let Some(types) = &req.allowed_upload_types {
+        let value = (!types.is_empty()).then(|| types.join(","));
+        server::Entity::update_many()
+            .col_expr(server::Column::AllowedUploadTypes, Expr::value(value))
+            .col_expr(server::Column::UpdatedAt, Expr::value(now.clone()))
+            .filter(server::Column::Id.eq(&server_id))
+            .exec(&state.db)
+            .await
+            .ok();
+    }
+
     Ok(StatusCode::OK)
 }
 
@@ -414,14 +587,44 @@ pub async fn join_server_by_id(
         return Err(StatusCode::NOT_FOUND);
     }
 
+    add_member(&state, &server_id, &claims.sub).await.map(Json).map_err(|e| e.0)
+}
+
+/// Make a user a member unless they're banned. New members wait in screening if the
+/// server requires it, otherwise they're welcomed. Joining twice returns the existing row.
+pub async fn add_member(
+    state: &AppState,
+    server_id: &str,
+    user_id: &str,
+) -> Result<ServerMember, (StatusCode, String)> {
+    let db_err = |e: DbErr| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"));
+
+    let banned = ban::Entity::find_by_id(user_id)
+        .filter(ban::Column::ServerId.eq(server_id))
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .is_some();
+    if banned {
+        return Err((StatusCode::FORBIDDEN, "You are banned from this server".into()));
+    }
+
     let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let pending = crate::routes::onboarding::screening_required(&state.db, server_id)
+        .await
+        .map_err(db_err)?;
 
     let member = server_member::ActiveModel {
-        server_id: Set(server_id.clone()),
-        user_id: Set(claims.sub.clone()),
-        joined_at: Set(now.clone()),
+        server_id: Set(server_id.to_string()),
+        user_id: Set(user_id.to_string()),
+        joined_at: Set(now),
+        temporary: Set(false),
+        nickname: Set(None),
+        pending: Set(pending),
+        screened_at: Set(None),
+        screening_answers: Set(None),
     };
-    server_member::Entity::insert(member)
+    let inserted = server_member::Entity::insert(member)
         .on_conflict(
             sea_query::OnConflict::columns([server_member::Column::ServerId, server_member::Column::UserId])
                 .do_nothing()
@@ -432,14 +635,21 @@ pub async fn join_server_by_id(
         .await
         .map_err(|e| {
             tracing::error!("Failed to join server: {e}");
-            StatusCode::INTERNAL_SERVER_ERROR
+            db_err(e)
         })?;
 
-    Ok(Json(ServerMember {
-        server_id,
-        user_id: claims.sub,
-        joined_at: now,
-    }))
+    if matches!(inserted, TryInsertResult::Inserted(_)) {
+        state.membership_changed(user_id, server_id, true);
+        if !pending {
+            crate::routes::onboarding::send_welcome(state, server_id, user_id).await;
+        }
+    }
+
+    server_member::Entity::find_by_id((server_id.to_string(), user_id.to_string()))
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Membership vanished".to_string()))
 }
 
 // ─── Leave a server ───
@@ -455,60 +665,231 @@ pub async fn leave_server(
         return Err(StatusCode::FORBIDDEN);
     }
 
+    // The owner has to transfer the server (or delete it) first
+    let owns = server::Entity::find_by_id(&server_id)
+        .one(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .is_some_and(|srv| srv.owner_id == claims.sub);
+    if owns {
+        return Err(StatusCode::CONFLICT);
+    }
+
     server_member::Entity::delete_many()
         .filter(server_member::Column::ServerId.eq(&server_id))
         .filter(server_member::Column::UserId.eq(&claims.sub))
         .exec(&state.db)
         .await
         .ok();
+    state.membership_changed(&claims.sub, &server_id, false);
 
     Ok(StatusCode::NO_CONTENT)
 }
 
+// ─── Transfer ownership (owner only, re-authenticated by password) ───
+pub async fn transfer_ownership(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(server_id): Path<String>,
+    Json(req): Json<TransferOwnershipRequest>,
+) -> Result<Json<Server>, (StatusCode, String)> {
+    let claims = extract_claims(&state.jwt_secret, &headers)?;
+    let db_err = |e: DbErr| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"));
+
+    let srv = server::Entity::find_by_id(&server_id)
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .ok_or((StatusCode::NOT_FOUND, "Server not found".to_string()))?;
+    if srv.owner_id != claims.sub {
+        return Err((StatusCode::FORBIDDEN, "Only the owner can transfer a server".into()));
+    }
+    if req.new_owner_id == claims.sub {
+        return Err((StatusCode::BAD_REQUEST, "You already own this server".into()));
+    }
+
+    let owner = user::Entity::find_by_id(&claims.sub)
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .ok_or((StatusCode::UNAUTHORIZED, "User not found".to_string()))?;
+    if !crate::routes::auth::verify_password(&owner, &req.password)? {
+        return Err((StatusCode::UNAUTHORIZED, "Invalid password".into()));
+    }
+
+    // Bots are members too, but can't own a server
+    let new_owner = user::Entity::find_by_id(&req.new_owner_id)
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .ok_or((StatusCode::NOT_FOUND, "User not found".to_string()))?;
+    let is_member = server_member::Entity::find()
+        .filter(server_member::Column::ServerId.eq(&server_id))
+        .filter(server_member::Column::UserId.eq(&new_owner.id))
+        .count(&state.db)
+        .await
+        .map_err(db_err)?
+        > 0;
+    if !is_member {
+        return Err((StatusCode::BAD_REQUEST, "The new owner must be a member of the server".into()));
+    }
+
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let mut update: server::ActiveModel = srv.into();
+    update.owner_id = Set(new_owner.id.clone());
+    update.updated_at = Set(Some(now.clone()));
+    let server = update.update(&state.db).await.map_err(db_err)?;
+
+    // The new owner also gets the server's Admin role, like a server's creator
+    let admin_role_id = format!("{server_id}-admin");
+    if role::Entity::find_by_id(&admin_role_id).one(&state.db).await.map_err(db_err)?.is_some() {
+        user_role::Entity::insert(user_role::ActiveModel {
+            user_id: Set(new_owner.id.clone()),
+            role_id: Set(admin_role_id),
+            assigned_at: Set(now),
+        })
+        .on_conflict(
+            sea_query::OnConflict::columns([user_role::Column::UserId, user_role::Column::RoleId])
+                .do_nothing()
+                .to_owned(),
+        )
+        .do_nothing()
+        .exec(&state.db)
+        .await
+        .map_err(db_err)?;
+    }
+
+    create_audit_log(
+        &state.db,
+        &claims.sub,
+        &claims.username,
+        "TRANSFER_OWNERSHIP",
+        Some(&server_id),
+        Some(&server.name),
+        Some(&format!("New owner: {} ({})", new_owner.username, new_owner.id)),
+    )
+    .await;
+
+    state.broadcast_global(Some(&server_id), WsServerMessage::ServerUpdate { server: server.clone() });
+
+    Ok(Json(server))
+}
+
+const MEMBER_PAGE_DEFAULT: u64 = 1000;
+const MEMBER_PAGE_MAX: u64 = 1000;
+
+/// Escape `%`, `_` and `\` for a LIKE pattern
+fn like_prefix(prefix: &str) -> LikeExpr {
+    let escaped: String = prefix
+        .chars()
+        .flat_map(|c| match c {
+            '%' | '_' | '\\' => vec!['\\', c],
+            c => vec![c],
+        })
+        .collect();
+    LikeExpr::new(format!("{escaped}%")).escape('\\')
+}
+
 // ─── List members of a server (rich info) ───
+// Ordered by join date and paged with `after` (the last user_id seen). Bots aren't
+// members; they come after the humans on the first page.
 pub async fn list_server_members(
     State(state): State<AppState>,
     Path(server_id): Path<String>,
+    Query(query): Query<crate::models::MembersQuery>,
 ) -> Result<Json<Vec<crate::models::MemberInfo>>, StatusCode> {
+    let db_err = |e: DbErr| {
+        tracing::error!("Failed to list server members: {e}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    let limit = query.limit.unwrap_or(MEMBER_PAGE_DEFAULT).clamp(1, MEMBER_PAGE_MAX);
+    let prefix = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+
+    // Presence of everyone who appears online
+    let online = state.get_online_presences();
+
     // 1. Fetch human members: join server_members + users
-    let member_rows = server_member::Entity::find()
+    let mut select = server_member::Entity::find()
         .filter(server_member::Column::ServerId.eq(&server_id))
         .find_also_related(user::Entity)
         .order_by_asc(server_member::Column::JoinedAt)
-        .all(&state.db)
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to list server members: {e}");
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .order_by_asc(server_member::Column::UserId)
+        .limit(limit);
 
-    let rows: Vec<(String, String, Option<String>, String)> = member_rows
+    if let Some(after) = &query.after {
+        let cursor = server_member::Entity::find_by_id((server_id.clone(), after.clone()))
+            .one(&state.db)
+            .await
+            .map_err(db_err)?
+            .ok_or(StatusCode::BAD_REQUEST)?;
+        select = select.filter(
+            Condition::any()
+                .add(server_member::Column::JoinedAt.gt(&cursor.joined_at))
+                .add(
+                    Condition::all()
+                        .add(server_member::Column::JoinedAt.eq(&cursor.joined_at))
+                        .add(server_member::Column::UserId.gt(&cursor.user_id)),
+                ),
+        );
+    }
+    if let Some(prefix) = prefix {
+        select = select.filter(
+            Condition::any()
+                .add(server_member::Column::Nickname.like(like_prefix(prefix)))
+                .add(user::Column::DisplayName.like(like_prefix(prefix)))
+                .add(user::Column::Username.like(like_prefix(prefix))),
+        );
+    }
+    if let Some(role_id) = &query.role_id {
+        let holders: Vec<String> = user_role::Entity::find()
+            .filter(user_role::Column::RoleId.eq(role_id))
+            .all(&state.db)
+            .await
+            .map_err(db_err)?
+            .into_iter()
+            .map(|ur| ur.user_id)
+            .collect();
+        select = select.filter(server_member::Column::UserId.is_in(holders));
+    }
+    match query.online {
+        Some(true) => select = select.filter(server_member::Column::UserId.is_in(online.keys().cloned())),
+        Some(false) => select = select.filter(server_member::Column::UserId.is_not_in(online.keys().cloned())),
+        None => {}
+    }
+
+    let member_rows = select.all(&state.db).await.map_err(db_err)?;
+
+    let rows: Vec<(server_member::Model, user::Model)> = member_rows
         .into_iter()
-        .filter_map(|(sm, u)| {
-            u.map(|u| (u.id, u.display_name, u.avatar_url, sm.joined_at))
-        })
+        .filter_map(|(sm, u)| u.map(|u| (sm, u)))
         .collect();
 
-    // 2. Fetch bots belonging to this server
-    let bot_rows = bot::Entity::find()
-        .filter(bot::Column::ServerId.eq(&server_id))
-        .order_by_asc(bot::Column::CreatedAt)
-        .all(&state.db)
-        .await
-        .unwrap_or_default();
+    // 2. Fetch bots belonging to this server (first page only, never with a role filter)
+    let bot_rows = if query.after.is_none() && query.role_id.is_none() {
+        bot::Entity::find()
+            .filter(bot::Column::ServerId.eq(&server_id))
+            .order_by_asc(bot::Column::CreatedAt)
+            .all(&state.db)
+            .await
+            .unwrap_or_default()
+    } else {
+        vec![]
+    };
 
     let bots: Vec<(String, String, Option<String>, String)> = bot_rows
         .into_iter()
+        .filter(|b| {
+            prefix.is_none_or(|p| b.name.to_lowercase().starts_with(&p.to_lowercase()))
+                && query.online.is_none_or(|o| online.contains_key(&b.id) == o)
+        })
         .map(|b| (b.id, b.name, b.avatar_url, b.created_at))
         .collect();
 
-    // 3. Get all online user IDs for presence
-    let online_ids = state.get_online_user_ids().await;
-
-    // 4. Fetch role assignments for all users in this server
+    // 3. Fetch role assignments for the users on this page
     let role_rows = user_role::Entity::find()
         .find_also_related(role::Entity)
         .filter(role::Column::ServerId.eq(&server_id))
+        .filter(user_role::Column::UserId.is_in(rows.iter().map(|(_, u)| u.id.clone())))
         .all(&state.db)
         .await
         .unwrap_or_default();
@@ -535,30 +916,40 @@ pub async fn list_server_members(
             });
     }
 
-    // 5. Build MemberInfo list
+    // 4. Build MemberInfo list
     let mut members: Vec<crate::models::MemberInfo> = Vec::new();
 
-    for (uid, display_name, avatar_url, joined_at) in rows {
-        let roles = role_map.remove(&uid).unwrap_or_default();
+    for (sm, u) in rows {
+        let roles = role_map.remove(&u.id).unwrap_or_default();
+        let presence = online.get(&u.id).cloned().unwrap_or_else(Presence::offline);
         members.push(crate::models::MemberInfo {
-            user_id: uid.clone(),
-            display_name,
-            avatar_url,
+            user_id: u.id,
+            display_name: u.display_name,
+            nickname: sm.nickname,
+            pending: sm.pending,
+            avatar_url: u.avatar_url,
             is_bot: false,
-            is_online: online_ids.contains(&uid),
-            joined_at,
+            is_online: presence.status != PresenceStatus::Offline,
+            status: presence.status,
+            custom_status: presence.custom_status,
+            joined_at: sm.joined_at,
             roles,
         });
     }
 
     // Add bots (always "online")
     for (bot_id, bot_name, avatar_url, created_at) in bots {
+        let presence = online.get(&bot_id).cloned().unwrap_or_else(Presence::offline);
         members.push(crate::models::MemberInfo {
             user_id: bot_id.clone(),
             display_name: bot_name,
+            nickname: None,
+            pending: false,
             avatar_url,
             is_bot: true,
-            is_online: online_ids.contains(&bot_id),
+            is_online: presence.status != PresenceStatus::Offline,
+            status: presence.status,
+            custom_status: presence.custom_status,
             joined_at: created_at,
             roles: vec![],
         });
@@ -1,7 +1,8 @@
 use axum::{extract::State, http::HeaderMap, Json};
 use sea_orm::{EntityTrait, QuerySelect, ColumnTrait, QueryFilter, JoinType, RelationTrait, PaginatorTrait};
-use crate::entities::{server_member, message, channel, role, invite_code};
+use crate::entities::{server, server_member, message, channel, role, invite_code};
 use crate::models::ServerStats;
+use crate::quotas;
 use crate::routes::servers::extract_server_id;
 use crate::state::AppState;
 
@@ -43,11 +44,22 @@ pub async fn get_server_stats(
         .await
         .unwrap_or(0) as i64;
 
+    let storage_used = quotas::server_usage(&state.db, &server_id).await.unwrap_or(0);
+    let storage_quota = server::Entity::find_by_id(&server_id)
+        .one(&state.db)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|srv| quotas::server_quota(&state, &srv))
+        .map(|quota| quota as i64);
+
     Json(ServerStats {
         total_users,
         total_messages,
         total_channels,
         total_roles,
         total_invites,
+        storage_used,
+        storage_quota,
     })
 }
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+
+use axum::{
+    body::Bytes,
+    extract::{Multipart, Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use sea_orm::*;
+use uuid::Uuid;
+
+use crate::blobs;
+use crate::entities::{sticker, sticker_pack, upload};
+use crate::models::{CreateStickerPackRequest, Message, Permissions, Sticker, StickerPack};
+use crate::quotas;
+use crate::routes::{auth, emoji, roles, uploads};
+use crate::state::AppState;
+
+const MAX_STICKER_SIZE: usize = 512 * 1024; // 512KB
+/// Stickers larger than this on either side are rejected
+const MAX_STICKER_SIDE: u32 = 320;
+const MAX_PACK_STICKERS: u64 = 50;
+const MAX_PACK_NAME: usize = 64;
+const MAX_PACK_DESCRIPTION: usize = 256;
+
+fn to_sticker(row: sticker::Model) -> Sticker {
+    Sticker {
+        url: format!("/api/uploads/{}", row.upload_id),
+        id: row.id,
+        pack_id: row.pack_id,
+        name: row.name,
+        animated: row.animated,
+    }
+}
+
+pub async fn find_sticker(state: &AppState, id: &str) -> Result<Option<Sticker>, DbErr> {
+    Ok(sticker::Entity::find_by_id(id).one(&state.db).await?.map(to_sticker))
+}
+
+/// Stickers sent as `messages`, by sticker id
+pub async fn for_messages(state: &AppState, messages: &[Message]) -> Result<HashMap<String, Sticker>, DbErr> {
+    let ids: Vec<&String> = messages.iter().filter_map(|m| m.sticker_id.as_ref()).collect();
+    if ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    Ok(sticker::Entity::find()
+        .filter(sticker::Column::Id.is_in(ids))
+        .all(&state.db)
+        .await?
+        .into_iter()
+        .map(|s| (s.id.clone(), to_sticker(s)))
+        .collect())
+}
+
+/// The pack, if `user_id` may change it (its creator, or MANAGE_EMOJIS)
+async fn editable_pack(
+    state: &AppState,
+    user_id: &str,
+    pack_id: &str,
+) -> Result<sticker_pack::Model, (StatusCode, String)> {
+    let pack = sticker_pack::Entity::find_by_id(pack_id)
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+        .ok_or((StatusCode::NOT_FOUND, "Sticker pack not found".to_string()))?;
+    if pack.user_id != user_id
+        && !roles::user_has_permission(state, user_id, Permissions::MANAGE_EMOJIS)
+            .await
+            .map_err(|s| (s, "Permission check failed".to_string()))?
+    {
+        return Err((StatusCode::FORBIDDEN, "You cannot edit this sticker pack".into()));
+    }
+    Ok(pack)
+}
+
+/// List sticker packs with their stickers
+pub async fn list_packs(State(state): State<AppState>) -> Result<Json<Vec<StickerPack>>, StatusCode> {
+    let packs = sticker_pack::Entity::find()
+        .order_by_asc(sticker_pack::Column::CreatedAt)
+        .all(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut stickers: HashMap<String, Vec<Sticker>> = HashMap::new();
+    for s in sticker::Entity::find()
+        .order_by_asc(sticker::Column::Position)
+        .all(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    {
+        stickers.entry(s.pack_id.clone()).or_default().push(to_sticker(s));
+    }
+
+    Ok(Json(
+        packs
+            .into_iter()
+            .map(|p| StickerPack {
+                stickers: stickers.remove(&p.id).unwrap_or_default(),
+                id: p.id,
+                name: p.name,
+                description: p.description,
+                user_id: p.user_id,
+            })
+            .collect(),
+    ))
+}
+
+/// Create an empty sticker pack (requires MANAGE_EMOJIS)
+pub async fn create_pack(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<CreateStickerPackRequest>,
+) -> Result<(StatusCode, Json<StickerPack>), (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    if !roles::user_has_permission(&state, &claims.sub, Permissions::MANAGE_EMOJIS)
+        .await
+        .map_err(|s| (s, "Permission check failed".to_string()))?
+    {
+        return Err((StatusCode::FORBIDDEN, "Creating sticker packs requires MANAGE_EMOJIS".into()));
+    }
+
+    let name = req.name.trim().to_string();
+    if name.is_empty() || name.len() > MAX_PACK_NAME {
+        return Err((StatusCode::BAD_REQUEST, format!("Pack name must be 1-{MAX_PACK_NAME} characters")));
+    }
+    let description = req.description.map(|d| d.trim().to_string()).filter(|d| !d.is_empty());
+    if description.as_ref().is_some_and(|d| d.len() > MAX_PACK_DESCRIPTION) {
+        return Err((StatusCode::BAD_REQUEST, format!("Description is limited to {MAX_PACK_DESCRIPTION} characters")));
+    }
+
+    let pack = sticker_pack::Model {
+        id: Uuid::new_v4().to_string(),
+        name,
+        description,
+        user_id: claims.sub,
+        created_at: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    };
+    sticker_pack::Entity::insert(pack.clone().into_active_model().reset_all())
+        .exec(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(StickerPack {
+            id: pack.id,
+            name: pack.name,
+            description: pack.description,
+            user_id: pack.user_id,
+            stickers: Vec::new(),
+        }),
+    ))
+}
+
+/// Delete a sticker pack and its stickers (creator or MANAGE_EMOJIS)
+pub async fn delete_pack(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(pack_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    editable_pack(&state, &claims.sub, &pack_id).await?;
+
+    sticker_pack::Entity::delete_by_id(&pack_id)
+        .exec(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Add a sticker to a pack (creator or MANAGE_EMOJIS). PNG, GIF or WebP, animated or not.
+pub async fn add_sticker(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(pack_id): Path<String>,
+    mut multipart: Multipart,
+) -> Result<(StatusCode, Json<Sticker>), (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    editable_pack(&state, &claims.sub, &pack_id).await?;
+    let db_err = |e: DbErr| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"));
+
+    let mut name: Option<String> = None;
+    let mut file_data: Option<(Bytes, String)> = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Multipart error: {e}")))?
+    {
+        match field.name().unwrap_or("") {
+            "name" => {
+                let text = field.text().await.map_err(|e| (StatusCode::BAD_REQUEST, format!("Read error: {e}")))?;
+                name = Some(text.trim().to_lowercase());
+            }
+            "file" | "image" => {
+                let content_type = field.content_type().unwrap_or("").to_string();
+                let data = field.bytes().await.map_err(|e| (StatusCode::BAD_REQUEST, format!("Read error: {e}")))?;
+                file_data = Some((data, content_type));
+            }
+            _ => {}
+        }
+    }
+    let name = name.ok_or((StatusCode::BAD_REQUEST, "Missing sticker name".to_string()))?;
+    let (data, content_type) = file_data.ok_or((StatusCode::BAD_REQUEST, "Missing sticker image".to_string()))?;
+    emoji::validate_name("Sticker", &name).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let in_pack = sticker::Entity::find()
+        .filter(sticker::Column::PackId.eq(&pack_id))
+        .all(&state.db)
+        .await
+        .map_err(db_err)?;
+    if in_pack.iter().any(|s| s.name == name) {
+        return Err((StatusCode::CONFLICT, "A sticker with that name is already in this pack".into()));
+    }
+    if in_pack.len() as u64 >= MAX_PACK_STICKERS {
+        return Err((StatusCode::BAD_REQUEST, format!("A pack can hold at most {MAX_PACK_STICKERS} stickers")));
+    }
+
+    let (img, animated) =
+        emoji::process_emoji_image(data, &content_type, MAX_STICKER_SIDE, MAX_STICKER_SIZE, MAX_STICKER_SIZE).await?;
+    let policy = uploads::upload_policy(&state, &claims.sub, None).await?;
+    quotas::check(&state, &claims.sub, &policy, img.data.len() as u64).await?;
+
+    let upload_id = Uuid::new_v4().to_string();
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let new_upload = upload::ActiveModel {
+        id: Set(upload_id.clone()),
+        user_id: Set(claims.sub.clone()),
+        filename: Set(format!("{name}.{}", uploads::get_extension(&img.mime_type))),
+        mime_type: Set(img.mime_type.clone()),
+        size: Set(img.data.len() as i64),
+        created_at: Set(now.clone()),
+        width: Set(Some(img.width as i32)),
+        height: Set(Some(img.height as i32)),
+        ..Default::default()
+    };
+    blobs::insert_upload(&state, new_upload, img.data.into(), Vec::new()).await?;
+
+    let row = sticker::Model {
+        id: Uuid::new_v4().to_string(),
+        pack_id,
+        name,
+        upload_id,
+        user_id: claims.sub,
+        animated,
+        position: in_pack.iter().map(|s| s.position + 1).max().unwrap_or(0),
+        created_at: now,
+    };
+    sticker::Entity::insert(row.clone().into_active_model().reset_all())
+        .exec(&state.db)
+        .await
+        .map_err(db_err)?;
+
+    Ok((StatusCode::CREATED, Json(to_sticker(row))))
+}
+
+/// Remove a sticker (its pack's creator or MANAGE_EMOJIS); messages that used it keep no image
+pub async fn delete_sticker(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let row = sticker::Entity::find_by_id(&id)
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+        .ok_or((StatusCode::NOT_FOUND, "Sticker not found".to_string()))?;
+    editable_pack(&state, &claims.sub, &row.pack_id).await?;
+
+    sticker::Entity::delete_by_id(&id)
+        .exec(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,192 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use sea_orm::*;
+
+use crate::entities::{server, server_template};
+use crate::models::{Permissions, ServerTemplateInfo};
+use crate::routes::audit_logs::create_audit_log;
+use crate::routes::{auth, roles};
+use crate::state::AppState;
+use crate::templates::{self, Template};
+
+fn to_info(row: server_template::Model) -> Result<ServerTemplateInfo, (StatusCode, String)> {
+    let template: Template = serde_json::from_str(&row.data)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Corrupt template: {e}")))?;
+    Ok(ServerTemplateInfo {
+        code: row.code,
+        server_id: row.server_id,
+        name: row.name,
+        description: row.description,
+        user_id: row.user_id,
+        created_at: row.created_at,
+        updated_at: row.updated_at,
+        template,
+    })
+}
+
+/// The server, if `user_id` owns it or has MANAGE_SERVER
+async fn managed_server(
+    state: &AppState,
+    user_id: &str,
+    server_id: &str,
+) -> Result<server::Model, (StatusCode, String)> {
+    let srv = server::Entity::find_by_id(server_id)
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+        .ok_or((StatusCode::NOT_FOUND, "Server not found".to_string()))?;
+    if srv.owner_id != user_id
+        && !roles::user_has_server_permission(state, &srv.id, user_id, Permissions::MANAGE_SERVER)
+            .await
+            .map_err(|s| (s, "Permission check failed".to_string()))?
+    {
+        return Err((StatusCode::FORBIDDEN, "Managing templates requires MANAGE_SERVER".into()));
+    }
+    Ok(srv)
+}
+
+/// Look up a stored template by its share code
+pub async fn find_by_code(state: &AppState, code: &str) -> Result<Option<Template>, (StatusCode, String)> {
+    server_template::Entity::find_by_id(code)
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+        .map(|row| to_info(row).map(|info| info.template))
+        .transpose()
+}
+
+/// POST /api/servers/{id}/template — snapshot the server into its template,
+/// creating it (and its share code) the first time and refreshing it afterwards
+pub async fn sync_template(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(server_id): Path<String>,
+) -> Result<Json<ServerTemplateInfo>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let srv = managed_server(&state, &claims.sub, &server_id).await?;
+    let db_err = |e: DbErr| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}"));
+
+    let template = templates::snapshot(&state.db, &srv).await.map_err(db_err)?;
+    let data = serde_json::to_string(&template)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Serialize error: {e}")))?;
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+
+    let existing = server_template::Entity::find()
+        .filter(server_template::Column::ServerId.eq(&server_id))
+        .one(&state.db)
+        .await
+        .map_err(db_err)?;
+    let row = match existing {
+        Some(row) => {
+            let mut update: server_template::ActiveModel = row.into();
+            update.name = Set(srv.name.clone());
+            update.description = Set(srv.description.clone());
+            update.user_id = Set(claims.sub.clone());
+            update.data = Set(data);
+            update.updated_at = Set(now);
+            update.update(&state.db).await.map_err(db_err)?
+        }
+        None => {
+            let row = server_template::Model {
+                code: crate::token::generate_invite_code(),
+                server_id: server_id.clone(),
+                name: srv.name.clone(),
+                description: srv.description.clone(),
+                user_id: claims.sub.clone(),
+                data,
+                created_at: now.clone(),
+                updated_at: now,
+            };
+            server_template::Entity::insert(row.clone().into_active_model().reset_all())
+                .exec(&state.db)
+                .await
+                .map_err(db_err)?;
+            row
+        }
+    };
+
+    create_audit_log(
+        &state.db,
+        &claims.sub,
+        &claims.username,
+        "SYNC_TEMPLATE",
+        Some(&server_id),
+        Some(&srv.name),
+        Some(&row.code),
+    )
+    .await;
+
+    to_info(row).map(Json)
+}
+
+/// GET /api/servers/{id}/template — the server's template, e.g. to save it as a file
+pub async fn get_server_template(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(server_id): Path<String>,
+) -> Result<Json<ServerTemplateInfo>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    managed_server(&state, &claims.sub, &server_id).await?;
+
+    let row = server_template::Entity::find()
+        .filter(server_template::Column::ServerId.eq(&server_id))
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+        .ok_or((StatusCode::NOT_FOUND, "This server has no template".to_string()))?;
+    to_info(row).map(Json)
+}
+
+/// DELETE /api/servers/{id}/template — stop sharing the server's template
+pub async fn delete_server_template(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(server_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    managed_server(&state, &claims.sub, &server_id).await?;
+
+    let result = server_template::Entity::delete_many()
+        .filter(server_template::Column::ServerId.eq(&server_id))
+        .exec(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+    if result.rows_affected == 0 {
+        return Err((StatusCode::NOT_FOUND, "This server has no template".into()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /api/templates/{code} — preview a template before creating a server from it
+pub async fn get_template(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(code): Path<String>,
+) -> Result<Json<ServerTemplateInfo>, (StatusCode, String)> {
+    auth::extract_claims(&state.jwt_secret, &headers)?;
+
+    let row = server_template::Entity::find_by_id(&code)
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+        .ok_or((StatusCode::NOT_FOUND, "Template not found".to_string()))?;
+    to_info(row).map(Json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn managing_one_server_grants_nothing_in_another() {
+        let state = AppState::for_tests().await;
+        roles::seed_role_in_one_server(&state, "u", "a", "b", Permissions::ADMINISTRATOR).await;
+
+        assert!(managed_server(&state, "u", "a").await.is_ok());
+        let err = managed_server(&state, "u", "b").await.unwrap_err();
+        assert_eq!(err.0, StatusCode::FORBIDDEN);
+    }
+}
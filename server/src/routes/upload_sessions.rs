@@ -0,0 +1,345 @@
+//! Resumable uploads: create a session, PATCH chunks at the current offset
+//! (re-reading it after a dropped connection), then finalize into `uploads`.
+//!
+//! Chunks and the busy flag live on the instance that created the session, so with
+//! several instances every request for a session has to be routed to that one.
+
+use std::path::PathBuf;
+use std::sync::LazyLock;
+
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use dashmap::DashSet;
+use futures::StreamExt;
+use sea_orm::*;
+use sea_orm::prelude::Expr;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::entities::upload_session;
+use crate::images;
+use crate::models::CreateUploadSessionRequest;
+use crate::quotas;
+use crate::routes::{auth, uploads};
+use crate::sniff;
+use crate::state::AppState;
+
+/// Header carrying the byte offset a chunk starts at
+const OFFSET_HEADER: &str = "upload-offset";
+/// Unfinished sessions per user, to bound scratch disk usage
+const MAX_OPEN_SESSIONS: u64 = 10;
+/// Sessions without a chunk for this long are discarded
+const SESSION_TTL_HOURS: i64 = 24;
+
+/// Sessions currently receiving a chunk or finalizing on this instance; a second request gets 409
+static BUSY: LazyLock<DashSet<String>> = LazyLock::new(DashSet::new);
+
+struct BusyGuard(String);
+
+impl BusyGuard {
+    fn acquire(id: &str) -> Result<Self, (StatusCode, String)> {
+        if BUSY.insert(id.to_string()) {
+            Ok(Self(id.to_string()))
+        } else {
+            Err((StatusCode::CONFLICT, "Upload session is busy".into()))
+        }
+    }
+}
+
+impl Drop for BusyGuard {
+    fn drop(&mut self) {
+        BUSY.remove(&self.0);
+    }
+}
+
+fn partial_path(state: &AppState, id: &str) -> PathBuf {
+    state.uploads.partial_dir.join(id)
+}
+
+/// The session's chunks, unless they were received by another instance
+async fn require_partial(state: &AppState, id: &str) -> Result<PathBuf, (StatusCode, String)> {
+    let path = partial_path(state, id);
+    match tokio::fs::try_exists(&path).await {
+        Ok(true) => Ok(path),
+        Ok(false) => Err((
+            StatusCode::CONFLICT,
+            "Upload session is held by another instance; route its requests to one instance".into(),
+        )),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Read error: {e}"))),
+    }
+}
+
+fn now() -> String {
+    chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn session_json(s: &upload_session::Model) -> serde_json::Value {
+    serde_json::json!({
+        "id": s.id,
+        "filename": s.filename,
+        "mime_type": s.mime_type,
+        "size": s.size,
+        "offset": s.received,
+    })
+}
+
+async fn find_session(
+    state: &AppState,
+    id: &str,
+    user_id: &str,
+) -> Result<upload_session::Model, (StatusCode, String)> {
+    upload_session::Entity::find_by_id(id)
+        .filter(upload_session::Column::UserId.eq(user_id))
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+        .ok_or((StatusCode::NOT_FOUND, "Upload session not found".into()))
+}
+
+async fn remove_session(state: &AppState, id: &str) -> Result<(), (StatusCode, String)> {
+    upload_session::Entity::delete_by_id(id)
+        .exec(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+    if let Err(e) = tokio::fs::remove_file(partial_path(state, id)).await {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            tracing::warn!("Failed to remove partial upload {id}: {e}");
+        }
+    }
+    Ok(())
+}
+
+/// Start a resumable upload (requires auth)
+pub async fn create_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<CreateUploadSessionRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let policy = uploads::upload_policy(&state, &claims.sub, req.server_id.as_deref()).await?;
+
+    if req.size <= 0 {
+        return Err((StatusCode::BAD_REQUEST, "Size must be positive".into()));
+    }
+    if req.size as u64 > policy.max_size {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("File too large (max {}MB)", policy.max_size / 1024 / 1024),
+        ));
+    }
+    // The contents are sniffed at finalize; reject obviously disallowed types up front
+    let mime_type = sniff::canonical(&req.mime_type).to_string();
+    if !policy.allowed.contains(&mime_type) {
+        return Err((StatusCode::BAD_REQUEST, format!("File type {mime_type} not allowed")));
+    }
+    // Images are decoded in memory at finalize, so they can't use the larger limit
+    if images::is_image(&mime_type) && req.size as u64 > uploads::MAX_IMAGE_SIZE {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Images can be at most {}MB", uploads::MAX_IMAGE_SIZE / 1024 / 1024),
+        ));
+    }
+    // Checked again at finalize, once the bytes are in
+    quotas::check(&state, &claims.sub, &policy, req.size as u64).await?;
+    let filename = req.filename.trim();
+    if filename.is_empty() || filename.len() > 255 {
+        return Err((StatusCode::BAD_REQUEST, "Filename must be 1-255 characters".into()));
+    }
+
+    let open = upload_session::Entity::find()
+        .filter(upload_session::Column::UserId.eq(&claims.sub))
+        .count(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+    if open >= MAX_OPEN_SESSIONS {
+        return Err((StatusCode::TOO_MANY_REQUESTS, "Too many unfinished uploads".into()));
+    }
+
+    let id = Uuid::new_v4().to_string();
+    tokio::fs::File::create(partial_path(&state, &id))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Write error: {e}")))?;
+
+    let session = upload_session::Model {
+        id,
+        user_id: claims.sub,
+        server_id: req.server_id,
+        filename: filename.to_string(),
+        mime_type,
+        size: req.size,
+        received: 0,
+        created_at: now(),
+        updated_at: now(),
+    };
+    upload_session::Entity::insert(session.clone().into_active_model().reset_all())
+        .exec_without_returning(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+    Ok((StatusCode::CREATED, Json(session_json(&session))))
+}
+
+/// Current offset, for resuming after a dropped connection
+pub async fn get_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let session = find_session(&state, &id, &claims.sub).await?;
+    Ok(Json(session_json(&session)))
+}
+
+/// Append a chunk. `Upload-Offset` must equal the session's current offset;
+/// bytes received before a dropped connection are kept.
+pub async fn upload_chunk(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    body: Body,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let offset: i64 = headers
+        .get(OFFSET_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .ok_or((StatusCode::BAD_REQUEST, "Missing or invalid Upload-Offset header".into()))?;
+
+    let _busy = BusyGuard::acquire(&id)?;
+    let session = find_session(&state, &id, &claims.sub).await?;
+    if offset != session.received {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("Offset mismatch: upload is at {}", session.received),
+        ));
+    }
+
+    let write_err = |e: std::io::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("Write error: {e}"));
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(require_partial(&state, &id).await?)
+        .await
+        .map_err(write_err)?;
+    // Drop anything past the last recorded offset (a write cut short by a crash)
+    file.set_len(session.received as u64).await.map_err(write_err)?;
+    tokio::io::AsyncSeekExt::seek(&mut file, std::io::SeekFrom::End(0))
+        .await
+        .map_err(write_err)?;
+
+    let mut received = session.received;
+    let mut failure = None;
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                failure = Some((StatusCode::BAD_REQUEST, format!("Read error: {e}")));
+                break;
+            }
+        };
+        if received + chunk.len() as i64 > session.size {
+            failure = Some((StatusCode::PAYLOAD_TOO_LARGE, "Chunk exceeds declared size".into()));
+            break;
+        }
+        if let Err(e) = file.write_all(&chunk).await {
+            failure = Some(write_err(e));
+            break;
+        }
+        received += chunk.len() as i64;
+    }
+    file.sync_data().await.map_err(write_err)?;
+
+    // Record progress even on failure, so the client resumes from here
+    upload_session::Entity::update_many()
+        .col_expr(upload_session::Column::Received, Expr::value(received))
+        .col_expr(upload_session::Column::UpdatedAt, Expr::value(now()))
+        .filter(upload_session::Column::Id.eq(&id))
+        .exec(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+    if let Some(err) = failure {
+        return Err(err);
+    }
+    Ok(Json(serde_json::json!({ "offset": received })))
+}
+
+/// Verify the completed file and store it as a regular upload
+pub async fn finalize_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let _busy = BusyGuard::acquire(&id)?;
+    let session = find_session(&state, &id, &claims.sub).await?;
+    if session.received != session.size {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("Upload incomplete: {} of {} bytes", session.received, session.size),
+        ));
+    }
+    let partial = require_partial(&state, &id).await?;
+
+    // The server's rules may have changed since the session started
+    let policy = uploads::upload_policy(&state, &claims.sub, session.server_id.as_deref()).await?;
+    if session.size as u64 > policy.max_size {
+        remove_session(&state, &id).await?;
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("File too large (max {}MB)", policy.max_size / 1024 / 1024),
+        ));
+    }
+
+    let result = uploads::store_upload_file(
+        &state,
+        &claims.sub,
+        &session.filename,
+        &session.mime_type,
+        &partial,
+        &policy,
+    )
+    .await;
+    // A file that fails verification won't pass on retry either
+    remove_session(&state, &id).await?;
+    Ok((StatusCode::CREATED, Json(result?)))
+}
+
+/// Abandon an upload
+pub async fn cancel_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let _busy = BusyGuard::acquire(&id)?;
+    find_session(&state, &id, &claims.sub).await?;
+    remove_session(&state, &id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Discard sessions nobody has touched within `SESSION_TTL_HOURS`; returns how many
+pub async fn expire_sessions(state: &AppState) -> Result<usize, DbErr> {
+    let cutoff = (chrono::Utc::now() - chrono::Duration::hours(SESSION_TTL_HOURS))
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string();
+    let stale = upload_session::Entity::find()
+        .filter(upload_session::Column::UpdatedAt.lt(cutoff))
+        .all(&state.db)
+        .await?;
+
+    let mut removed = 0;
+    for session in stale {
+        let Ok(_busy) = BusyGuard::acquire(&session.id) else {
+            continue;
+        };
+        if remove_session(state, &session.id).await.is_ok() {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
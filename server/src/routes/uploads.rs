@@ -1,38 +1,35 @@
 use axum::{
     body::Body,
-    extract::{Multipart, Path, State},
+    body::Bytes,
+    extract::{Multipart, Path, Query, State},
     http::{header, HeaderMap, StatusCode},
-    response::IntoResponse,
+    response::{IntoResponse, Redirect, Response},
 };
+use futures::{StreamExt, TryStreamExt};
 use sea_orm::*;
 use sea_orm::prelude::Expr;
+use serde::Deserialize;
+use std::ops::Range;
 use uuid::Uuid;
 
-use crate::entities::{upload, user};
+use crate::attachments;
+use crate::blobs;
+use crate::entities::{role, server, server_member, upload, user, user_role};
+use crate::images::{self, ProcessedImage};
+use crate::quotas;
 use crate::routes::auth;
+use crate::sniff;
 use crate::state::AppState;
 
-const MAX_AVATAR_SIZE: usize = 8 * 1024 * 1024; // 8MB
-const MAX_FILE_SIZE: usize = 25 * 1024 * 1024; // 25MB
+pub const MAX_AVATAR_SIZE: usize = 8 * 1024 * 1024; // 8MB
+/// Largest file accepted in a single (buffered) request
+pub const MAX_FILE_SIZE: usize = 25 * 1024 * 1024; // 25MB
+/// Largest image accepted, uploaded in one request or in chunks: images are decoded in memory
+pub const MAX_IMAGE_SIZE: u64 = MAX_FILE_SIZE as u64;
 
 const ALLOWED_IMAGE_TYPES: &[&str] = &["image/png", "image/jpeg", "image/gif", "image/webp"];
-const ALLOWED_FILE_TYPES: &[&str] = &[
-    "image/png",
-    "image/jpeg",
-    "image/gif",
-    "image/webp",
-    "application/pdf",
-    "text/plain",
-    "audio/mpeg",
-    "audio/ogg",
-    "audio/wav",
-    "video/mp4",
-    "video/webm",
-    "application/zip",
-    "application/gzip",
-];
-
-fn get_extension(mime: &str) -> &str {
+
+pub fn get_extension(mime: &str) -> &str {
     match mime {
         "image/png" => "png",
         "image/jpeg" => "jpg",
@@ -51,13 +48,212 @@ fn get_extension(mime: &str) -> &str {
     }
 }
 
-/// Upload a file (requires auth)
+/// Decode and re-process an image on the blocking pool
+async fn process_image(
+    data: Bytes,
+    mime: String,
+    process: fn(&[u8], &str) -> Result<ProcessedImage, String>,
+) -> Result<ProcessedImage, (StatusCode, String)> {
+    tokio::task::spawn_blocking(move || process(&data, &mime))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Image processing failed: {e}")))?
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+/// What a user may upload in a given place
+pub struct UploadPolicy {
+    pub allowed: Vec<String>,
+    /// Size limit in bytes
+    pub max_size: u64,
+    /// Server the file is shared in, which it counts towards
+    pub server_id: Option<String>,
+    /// Storage quotas in bytes (None = unlimited)
+    pub user_quota: Option<u64>,
+    pub server_quota: Option<u64>,
+}
+
+/// Resolve the allow-list, size limit and quotas for `user_id` uploading to `server_id`.
+/// The size limit and user quota are the largest ones set on the user's roles there,
+/// else the server's (size limit only), else the instance defaults.
+pub async fn upload_policy(
+    state: &AppState,
+    user_id: &str,
+    server_id: Option<&str>,
+) -> Result<UploadPolicy, (StatusCode, String)> {
+    let default_size = state.uploads.max_size;
+    let Some(server_id) = server_id else {
+        return Ok(UploadPolicy {
+            allowed: sniff::default_allowed(),
+            max_size: default_size,
+            server_id: None,
+            user_quota: state.uploads.user_quota,
+            server_quota: None,
+        });
+    };
+
+    let srv = server::Entity::find_by_id(server_id)
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+        .ok_or((StatusCode::NOT_FOUND, "Server not found".to_string()))?;
+
+    // Files count towards the server's quota, so only members may share them there
+    let is_member = server_member::Entity::find()
+        .filter(server_member::Column::ServerId.eq(server_id))
+        .filter(server_member::Column::UserId.eq(user_id))
+        .count(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
+        > 0;
+    if !is_member {
+        return Err((StatusCode::FORBIDDEN, "Not a member of this server".to_string()));
+    }
+
+    let roles = role::Entity::find()
+        .inner_join(user_role::Entity)
+        .filter(user_role::Column::UserId.eq(user_id))
+        .filter(role::Column::ServerId.eq(server_id))
+        .all(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+    let role_limit = roles.iter().filter_map(|r| r.max_upload_size).max();
+    let role_quota = roles.iter().filter_map(|r| r.storage_quota).max();
+
+    let max_size = role_limit
+        .or(srv.max_upload_size)
+        .map(|limit| limit.max(0) as u64)
+        .unwrap_or(default_size);
+
+    Ok(UploadPolicy {
+        allowed: sniff::parse_allowed(srv.allowed_upload_types.as_deref()),
+        max_size,
+        server_id: Some(srv.id.clone()),
+        user_quota: role_quota.map(|q| q.max(0) as u64).or(state.uploads.user_quota),
+        server_quota: quotas::server_quota(state, &srv),
+    })
+}
+
+fn too_large(limit: u64) -> (StatusCode, String) {
+    (
+        StatusCode::PAYLOAD_TOO_LARGE,
+        format!("File too large (max {}MB)", limit / 1024 / 1024),
+    )
+}
+
+/// Response body for a stored upload
+fn upload_json(row: &upload::Model) -> serde_json::Value {
+    serde_json::json!({
+        "id": row.id,
+        "filename": row.filename,
+        "mime_type": row.mime_type,
+        "size": row.size,
+        "width": row.width,
+        "height": row.height,
+        "url": format!("/api/uploads/{}", row.id)
+    })
+}
+
+fn new_upload_row(
+    user_id: &str,
+    policy: &UploadPolicy,
+    filename: &str,
+    mime_type: &str,
+    size: i64,
+    dimensions: Option<(i32, i32)>,
+) -> upload::Model {
+    upload::Model {
+        id: Uuid::new_v4().to_string(),
+        user_id: user_id.to_string(),
+        server_id: policy.server_id.clone(),
+        filename: filename.to_string(),
+        mime_type: mime_type.to_string(),
+        size,
+        created_at: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        blob_hash: None,
+        width: dimensions.map(|(w, _)| w),
+        height: dimensions.map(|(_, h)| h),
+    }
+}
+
+/// Verify an in-memory file against `policy`, process images and store it
+pub async fn store_upload(
+    state: &AppState,
+    user_id: &str,
+    filename: &str,
+    declared_type: &str,
+    data: Bytes,
+    policy: &UploadPolicy,
+) -> Result<serde_json::Value, (StatusCode, String)> {
+    // Trust the bytes, not the client's Content-Type
+    let content_type =
+        sniff::verify(declared_type, &data, &policy.allowed).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    // Images are validated, stripped of metadata (EXIF GPS etc.) and thumbnailed
+    let (data, content_type, dimensions, thumbnails) = if images::is_image(content_type) {
+        let img = process_image(data, content_type.to_string(), images::process_upload).await?;
+        (Bytes::from(img.data), img.mime_type, Some((img.width as i32, img.height as i32)), img.thumbnails)
+    } else {
+        (data, content_type.to_string(), None, Vec::new())
+    };
+
+    quotas::check(state, user_id, policy, data.len() as u64).await?;
+    let row = new_upload_row(user_id, policy, filename, &content_type, data.len() as i64, dimensions);
+    // Identical bytes share one blob on disk
+    blobs::insert_upload(state, row.clone().into_active_model().reset_all(), data, thumbnails).await?;
+    Ok(upload_json(&row))
+}
+
+/// Verify a file on local disk and store it without loading it into memory
+/// (images up to `MAX_IMAGE_SIZE` are read in, since they are decoded anyway)
+pub async fn store_upload_file(
+    state: &AppState,
+    user_id: &str,
+    filename: &str,
+    declared_type: &str,
+    path: &std::path::Path,
+    policy: &UploadPolicy,
+) -> Result<serde_json::Value, (StatusCode, String)> {
+    let read_err = |e: std::io::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("Read error: {e}"));
+
+    let mut head = vec![0u8; 8192];
+    let mut file = tokio::fs::File::open(path).await.map_err(read_err)?;
+    let n = tokio::io::AsyncReadExt::read(&mut file, &mut head).await.map_err(read_err)?;
+    head.truncate(n);
+    let content_type =
+        sniff::verify(declared_type, &head, &policy.allowed).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let size = tokio::fs::metadata(path).await.map_err(read_err)?.len();
+    if images::is_image(content_type) {
+        if size > MAX_IMAGE_SIZE {
+            return Err(too_large(MAX_IMAGE_SIZE));
+        }
+        let data = tokio::fs::read(path).await.map_err(read_err)?;
+        return store_upload(state, user_id, filename, content_type, data.into(), policy).await;
+    }
+
+    quotas::check(state, user_id, policy, size).await?;
+    let row = new_upload_row(user_id, policy, filename, content_type, size as i64, None);
+    blobs::insert_upload_file(state, row.clone().into_active_model().reset_all(), path).await?;
+    Ok(upload_json(&row))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UploadQuery {
+    /// Server the file is being shared in; applies that server's allow-list, size limit and quota
+    pub server_id: Option<String>,
+}
+
+/// Upload a file in one request (requires auth). Files above `MAX_FILE_SIZE`
+/// go through resumable upload sessions instead.
 pub async fn upload_file(
     State(state): State<AppState>,
     headers: HeaderMap,
+    Query(query): Query<UploadQuery>,
     mut multipart: Multipart,
 ) -> Result<(StatusCode, axum::Json<serde_json::Value>), (StatusCode, String)> {
     let claims = auth::extract_claims(&state.jwt_secret, &headers)?;
+    let policy = upload_policy(&state, &claims.sub, query.server_id.as_deref()).await?;
+    let limit = policy.max_size.min(MAX_FILE_SIZE as u64);
 
     while let Some(field) = multipart
         .next_field()
@@ -75,94 +271,249 @@ pub async fn upload_file(
             .unwrap_or("application/octet-stream")
             .to_string();
 
-        if !ALLOWED_FILE_TYPES.contains(&content_type.as_str()) {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                format!("File type {content_type} not allowed"),
-            ));
-        }
-
         let data = field
             .bytes()
             .await
             .map_err(|e| (StatusCode::BAD_REQUEST, format!("Read error: {e}")))?;
 
-        if data.len() > MAX_FILE_SIZE {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                format!("File too large (max {}MB)", MAX_FILE_SIZE / 1024 / 1024),
-            ));
+        if data.len() as u64 > limit {
+            return Err(too_large(limit));
         }
 
-        let id = Uuid::new_v4().to_string();
-        let ext = get_extension(&content_type);
-        let disk_filename = format!("{id}.{ext}");
+        let body = store_upload(&state, &claims.sub, &filename, &content_type, data, &policy).await?;
+        return Ok((StatusCode::CREATED, axum::Json(body)));
+    }
 
-        tokio::fs::write(format!("./uploads/{disk_filename}"), &data)
-            .await
-            .map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Write error: {e}"),
-                )
-            })?;
+    Err((StatusCode::BAD_REQUEST, "No file field found".into()))
+}
 
-        let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+/// How long presigned download URLs stay valid
+const PRESIGNED_URL_TTL: std::time::Duration = std::time::Duration::from_secs(3600);
 
-        let new_upload = upload::ActiveModel {
-            id: Set(id.clone()),
-            user_id: Set(claims.sub.clone()),
-            filename: Set(filename.clone()),
-            mime_type: Set(content_type.clone()),
-            size: Set(data.len() as i64),
-            created_at: Set(now),
-        };
+#[derive(Debug, Deserialize)]
+pub struct ServeUploadQuery {
+    /// Thumbnail size (longest side); one of `images::THUMBNAIL_SIZES`
+    pub size: Option<u32>,
+    /// Signed-URL expiry (unix seconds) and signature, for message attachments
+    pub expires: Option<i64>,
+    pub sig: Option<String>,
+}
 
-        upload::Entity::insert(new_upload)
-            .exec(&state.db)
-            .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+/// Bytes read from the start of a file to pick its content type and disposition
+const HEAD_LEN: u64 = 512;
 
-        return Ok((
-            StatusCode::CREATED,
-            axum::Json(serde_json::json!({
-                "id": id,
-                "filename": filename,
-                "mime_type": content_type,
-                "size": data.len(),
-                "url": format!("/api/uploads/{id}")
-            })),
-        ));
+/// Parse a single-range `Range: bytes=...` header against a file of `size` bytes.
+/// `None` means serve the whole file (no header, other units, multiple or malformed
+/// ranges); `Some(Err(()))` means the range can't be satisfied (416).
+fn parse_range(value: &str, size: u64) -> Option<Result<Range<u64>, ()>> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
     }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+
+    let range = if start.is_empty() {
+        // Suffix range: the last N bytes
+        let suffix: u64 = end.parse().ok()?;
+        if suffix == 0 {
+            return Some(Err(()));
+        }
+        size.saturating_sub(suffix)..size
+    } else {
+        let start: u64 = start.parse().ok()?;
+        let end = match end {
+            "" => size,
+            end => {
+                let last: u64 = end.parse().ok()?;
+                if last < start {
+                    return None;
+                }
+                last.saturating_add(1).min(size)
+            }
+        };
+        start..end
+    };
 
-    Err((StatusCode::BAD_REQUEST, "No file field found".into()))
+    if range.start >= size {
+        return Some(Err(()));
+    }
+    Some(Ok(range))
 }
 
-/// Serve an uploaded file
+/// Whether an `If-None-Match` header matches `etag` (weak comparison)
+fn etag_matches(header: &str, etag: &str) -> bool {
+    header
+        .split(',')
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Serve an uploaded file (or redirect to the storage backend when it presigns URLs).
+/// `?size=` serves a thumbnail, or the original when the image is already that small.
+/// Supports `Range`/`If-Range` for seeking and `ETag`/`If-None-Match` for caching,
+/// and streams from storage rather than loading the file into memory.
 pub async fn serve_upload(
     State(state): State<AppState>,
     Path(id): Path<String>,
-) -> Result<impl IntoResponse, StatusCode> {
+    Query(query): Query<ServeUploadQuery>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    if query.size.is_some_and(|size| !images::THUMBNAIL_SIZES.contains(&size)) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
     let upload_row = upload::Entity::find_by_id(&id)
         .one(&state.db)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::NOT_FOUND)?;
 
-    let ext = get_extension(&upload_row.mime_type);
-    let path = format!("./uploads/{}.{ext}", upload_row.id);
+    let protected =
+        attachments::authorize_download(&state, &id, query.expires, query.sig.as_deref(), &headers).await?;
 
-    let data = tokio::fs::read(&path)
-        .await
-        .map_err(|_| StatusCode::NOT_FOUND)?;
+    // Thumbnails exist for every size smaller than the image's longest side
+    let thumbnail = match (query.size, &upload_row.blob_hash, upload_row.width, upload_row.height) {
+        (Some(size), Some(hash), Some(w), Some(h)) if (size as i32) < w.max(h) => {
+            Some((size, blobs::thumbnail_key(hash, size)))
+        }
+        _ => None,
+    };
+    let key = match &thumbnail {
+        Some((_, key)) => key.clone(),
+        None => blobs::upload_key(&upload_row),
+    };
+
+    // Stored bytes never change, so the content hash (or the upload id for
+    // files stored before hashing) identifies them
+    let etag = match (&thumbnail, &upload_row.blob_hash) {
+        (Some((size, _)), Some(hash)) => format!("\"{hash}-{size}\""),
+        (None, Some(hash)) => format!("\"{hash}\""),
+        _ => format!("\"{}\"", upload_row.id),
+    };
+    // Attachments must not land in shared caches, where access checks can't follow
+    let cache_control = if protected { "private, max-age=86400" } else { "public, max-age=86400" };
+
+    if headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| etag_matches(v, &etag))
+    {
+        return Ok((
+            StatusCode::NOT_MODIFIED,
+            [(header::ETAG, etag), (header::CACHE_CONTROL, cache_control.to_string())],
+        )
+            .into_response());
+    }
+
+    let storage_err = |e: std::io::Error| match e.kind() {
+        std::io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    let size = state.storage.size(&key).await.map_err(storage_err)?;
+
+    let head = if size == 0 {
+        Bytes::new()
+    } else {
+        let chunks: Vec<Bytes> = state
+            .storage
+            .get_stream(&key, Some(0..size.min(HEAD_LEN)))
+            .await
+            .map_err(storage_err)?
+            .try_collect()
+            .await
+            .map_err(storage_err)?;
+        chunks.concat().into()
+    };
+
+    // Only media whose bytes match its type renders inline; everything else downloads
+    let (content_type, inline) = match thumbnail {
+        Some(_) => (images::thumbnail_mime(&head).to_string(), true),
+        None => {
+            let inline = sniff::is_inline_safe(&upload_row.mime_type, &head);
+            (upload_row.mime_type, inline)
+        }
+    };
+    let disposition = if inline {
+        "inline".to_string()
+    } else {
+        sniff::attachment_disposition(&upload_row.filename)
+    };
+
+    // The bucket can't send our download headers, so only media safe to show inline
+    // (stored under its own type) is handed off to it
+    if inline {
+        if let Some(url) = state.storage.presigned_url(&key, PRESIGNED_URL_TTL).await {
+            return Ok(Redirect::temporary(&url).into_response());
+        }
+    }
 
-    Ok((
-        [
-            (header::CONTENT_TYPE, upload_row.mime_type),
-            (header::CACHE_CONTROL, "public, max-age=86400".to_string()),
-        ],
-        Body::from(data),
-    ))
+    // If-Range: only honor Range when the client's copy is still current
+    let range_valid = headers
+        .get(header::IF_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .is_none_or(|v| v.trim() == etag);
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .filter(|_| range_valid)
+        .and_then(|v| parse_range(v, size));
+
+    let mut response = Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CACHE_CONTROL, cache_control)
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header(header::CONTENT_DISPOSITION, disposition)
+        .header(header::ETAG, &etag)
+        .header(header::ACCEPT_RANGES, "bytes");
+
+    let (body_range, body) = match range {
+        Some(Err(())) => {
+            let response = response
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{size}"))
+                .body(Body::empty())
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            return Ok(response);
+        }
+        Some(Ok(range)) => {
+            response = response.status(StatusCode::PARTIAL_CONTENT).header(
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{size}", range.start, range.end - 1),
+            );
+            (range.clone(), Some(range))
+        }
+        None => (0..size, None),
+    };
+
+    let stream = if body_range.is_empty() {
+        futures::stream::empty().boxed()
+    } else {
+        state.storage.get_stream(&key, body).await.map_err(storage_err)?
+    };
+
+    response
+        .header(header::CONTENT_LENGTH, body_range.end - body_range.start)
+        .body(Body::from_stream(stream))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Fresh download URL for an upload, signed when it is a message attachment
+pub async fn upload_url(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<axum::Json<serde_json::Value>, (StatusCode, String)> {
+    auth::extract_claims(&state.jwt_secret, &headers)?;
+    let protected = attachments::authorize_download(&state, &id, None, None, &headers)
+        .await
+        .map_err(|status| (status, "You do not have access to this file".to_string()))?;
+    let url = if protected {
+        attachments::signed_url(&state, &id)
+    } else {
+        format!("/api/uploads/{id}")
+    };
+    Ok(axum::Json(serde_json::json!({ "url": url })))
 }
 
 /// Upload user avatar (requires auth, images only, 8MB max)
@@ -184,7 +535,7 @@ pub async fn upload_avatar(
         }
 
         let content_type = field.content_type().unwrap_or("").to_string();
-        if !ALLOWED_IMAGE_TYPES.contains(&content_type.as_str()) {
+        if !ALLOWED_IMAGE_TYPES.contains(&sniff::canonical(&content_type)) {
             return Err((
                 StatusCode::BAD_REQUEST,
                 "Avatar must be PNG, JPEG, GIF, or WebP".into(),
@@ -200,34 +551,31 @@ pub async fn upload_avatar(
             return Err((StatusCode::BAD_REQUEST, "Avatar too large (max 8MB)".into()));
         }
 
-        let id = Uuid::new_v4().to_string();
-        let ext = get_extension(&content_type);
-        let disk_filename = format!("{id}.{ext}");
+        let content_type = sniff::verify(&content_type, &data, ALLOWED_IMAGE_TYPES)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e))?
+            .to_string();
 
-        tokio::fs::write(format!("./uploads/{disk_filename}"), &data)
-            .await
-            .map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Write error: {e}"),
-                )
-            })?;
+        // Square, fixed-size and free of metadata
+        let avatar = process_image(data, content_type, images::process_avatar).await?;
+        let policy = upload_policy(&state, &claims.sub, None).await?;
+        quotas::check(&state, &claims.sub, &policy, avatar.data.len() as u64).await?;
 
+        let id = Uuid::new_v4().to_string();
         let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
 
         let new_upload = upload::ActiveModel {
             id: Set(id.clone()),
             user_id: Set(claims.sub.clone()),
             filename: Set("avatar".to_string()),
-            mime_type: Set(content_type.clone()),
-            size: Set(data.len() as i64),
+            mime_type: Set(avatar.mime_type),
+            size: Set(avatar.data.len() as i64),
             created_at: Set(now),
+            width: Set(Some(avatar.width as i32)),
+            height: Set(Some(avatar.height as i32)),
+            ..Default::default()
         };
 
-        upload::Entity::insert(new_upload)
-            .exec(&state.db)
-            .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+        blobs::insert_upload(&state, new_upload, avatar.data.into(), Vec::new()).await?;
 
         let avatar_url = format!("/api/uploads/{id}");
 
@@ -243,3 +591,20 @@ pub async fn upload_avatar(
 
     Err((StatusCode::BAD_REQUEST, "No file found".into()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some(Ok(0..100)));
+        assert_eq!(parse_range("bytes=900-", 1000), Some(Ok(900..1000)));
+        assert_eq!(parse_range("bytes=-100", 1000), Some(Ok(900..1000)));
+        assert_eq!(parse_range("bytes=500-5000", 1000), Some(Ok(500..1000)));
+        assert_eq!(parse_range("bytes=1000-", 1000), Some(Err(())));
+        assert_eq!(parse_range("bytes=0-1,5-6", 1000), None);
+        assert_eq!(parse_range("items=0-1", 1000), None);
+        assert!(etag_matches("W/\"abc\", \"def\"", "\"abc\""));
+    }
+}
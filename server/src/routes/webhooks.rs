@@ -10,6 +10,7 @@ use uuid::Uuid;
 
 use crate::entities::{channel, message, webhook};
 use crate::models::{Webhook, WsServerMessage};
+use crate::routes::announcements::check_can_post;
 use crate::routes::auth;
 use crate::state::AppState;
 
@@ -63,8 +64,17 @@ pub async fn create_webhook(
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
 
-    if exists.is_none() {
-        return Err((StatusCode::NOT_FOUND, "Channel not found".into()));
+    match exists {
+        None => return Err((StatusCode::NOT_FOUND, "Channel not found".into())),
+        // Webhook messages can't pick a forum post
+        Some(ch) if ch.channel_type == "forum" => {
+            return Err((StatusCode::BAD_REQUEST, "Webhooks can't post in forum channels".into()))
+        }
+        // Only moderators post announcements, and a webhook isn't one
+        Some(ch) if ch.channel_type == "announcement" => {
+            return Err((StatusCode::BAD_REQUEST, "Webhooks can't post in announcement channels".into()))
+        }
+        Some(_) => {}
     }
 
     let id = Uuid::new_v4().to_string();
@@ -160,6 +170,10 @@ pub async fn execute_webhook(
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?
         .ok_or((StatusCode::UNAUTHORIZED, "Invalid webhook".into()))?;
+    // Also covers webhooks created before announcement channels turned them away
+    check_can_post(&state, &wh.id, &wh.channel_id)
+        .await
+        .map_err(|e| (StatusCode::FORBIDDEN, e))?;
 
     let content = req.content.trim().to_string();
     if content.is_empty() || content.len() > 2000 {
@@ -188,6 +202,9 @@ pub async fn execute_webhook(
         reply_to: Set(None),
         pinned_at: Set(None),
         pinned_by: Set(None),
+        sticker_id: Set(None),
+        post_id: Set(None),
+        published_at: Set(None),
     };
 
     message::Entity::insert(new_msg)
@@ -207,10 +224,12 @@ pub async fn execute_webhook(
         is_bot: false,
         reply_to: None,
         replied_message: None,
+        attachments: Vec::new(),
+        sticker: None,
+        post_id: None,
     };
 
-    let tx = state.get_channel_tx(&wh.channel_id);
-    let _ = tx.send(broadcast_msg);
+    state.publish_channel(&wh.channel_id, broadcast_msg);
 
     Ok(Json(serde_json::json!({ "id": msg_id })))
 }
@@ -0,0 +1,292 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::models::WsServerMessage;
+
+/// How long a dropped session stays resumable
+pub const RESUME_WINDOW: Duration = Duration::from_secs(60);
+/// Max events kept for replay per session
+const REPLAY_BUFFER_SIZE: usize = 512;
+/// Socket writer queue size; large enough to take a full replay at once
+pub const SOCKET_QUEUE_SIZE: usize = REPLAY_BUFFER_SIZE + 256;
+
+/// A frame on its way to the socket: sequenced events carry `Some(seq)`,
+/// direct replies (errors, pongs, resume acks) carry `None`
+pub type Frame = (Option<u64>, WsServerMessage);
+
+struct Replay {
+    seq: u64,
+    buffer: VecDeque<(u64, WsServerMessage)>,
+    /// Writer of the socket currently attached (None while disconnected)
+    socket_tx: Option<mpsc::Sender<Frame>>,
+    /// Bumped on every attach so a stale connection can't detach its successor
+    generation: u64,
+    detached_at: Option<Instant>,
+}
+
+/// A WebSocket session that outlives its socket for `RESUME_WINDOW`.
+/// Broadcast subscriptions feed `events_tx`; the pump task numbers each event,
+/// keeps it in the replay buffer and forwards it to whichever socket is attached.
+pub struct WsSession {
+    pub id: String,
+    pub user_id: String,
+    pub events_tx: mpsc::Sender<WsServerMessage>,
+    pub subscribed_channels: Mutex<HashSet<String>>,
+    replay: Arc<Mutex<Replay>>,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+/// Outcome of a `Resume` request
+pub enum ResumeResult {
+    /// Session adopted; the missed events were already queued to the new socket
+    Resumed { session: Arc<WsSession>, replayed: usize, generation: u64 },
+    /// Unknown, expired or foreign session, or events fell out of the buffer
+    Invalid(&'static str),
+}
+
+impl WsSession {
+    fn new(user_id: &str) -> Arc<Self> {
+        let (events_tx, mut events_rx) = mpsc::channel::<WsServerMessage>(256);
+        let replay = Arc::new(Mutex::new(Replay {
+            seq: 0,
+            buffer: VecDeque::new(),
+            socket_tx: None,
+            generation: 0,
+            detached_at: None,
+        }));
+
+        let pump_replay = replay.clone();
+        let pump = tokio::spawn(async move {
+            while let Some(msg) = events_rx.recv().await {
+                let (seq, target) = {
+                    let mut r = pump_replay.lock().unwrap();
+                    r.seq += 1;
+                    let seq = r.seq;
+                    r.buffer.push_back((seq, msg.clone()));
+                    if r.buffer.len() > REPLAY_BUFFER_SIZE {
+                        r.buffer.pop_front();
+                    }
+                    (seq, r.socket_tx.clone().map(|tx| (tx, r.generation)))
+                };
+                // While detached the event only lands in the buffer
+                if let Some((tx, generation)) = target {
+                    if tx.send((Some(seq), msg)).await.is_err() {
+                        let mut r = pump_replay.lock().unwrap();
+                        if r.generation == generation {
+                            r.socket_tx = None;
+                            r.detached_at.get_or_insert_with(Instant::now);
+                        }
+                    }
+                }
+            }
+        });
+
+        Arc::new(Self {
+            id: Uuid::new_v4().to_string(),
+            user_id: user_id.to_string(),
+            events_tx,
+            subscribed_channels: Mutex::new(HashSet::new()),
+            replay,
+            tasks: Mutex::new(vec![pump]),
+        })
+    }
+
+    /// Track a forwarding task so it dies with the session
+    pub fn track(&self, task: JoinHandle<()>) {
+        self.tasks.lock().unwrap().push(task);
+    }
+
+    /// Attach a socket writer, returning its generation for `detach`
+    pub fn attach(&self, socket_tx: mpsc::Sender<Frame>) -> u64 {
+        let mut r = self.replay.lock().unwrap();
+        r.generation += 1;
+        r.socket_tx = Some(socket_tx);
+        r.detached_at = None;
+        r.generation
+    }
+
+    /// Detach the socket if it is still the current one; the session stays resumable
+    pub fn detach(&self, generation: u64) {
+        let mut r = self.replay.lock().unwrap();
+        if r.generation == generation {
+            r.socket_tx = None;
+            r.detached_at = Some(Instant::now());
+        }
+    }
+
+    /// Queue everything after `last_seq` to a new socket and attach it.
+    /// Runs under the replay lock so no live event can slip in between.
+    fn replay_into(&self, last_seq: u64, socket_tx: mpsc::Sender<Frame>) -> Result<(usize, u64), &'static str> {
+        let mut r = self.replay.lock().unwrap();
+        if last_seq > r.seq {
+            return Err("Sequence is ahead of the session");
+        }
+        let oldest = r.buffer.front().map(|(s, _)| *s).unwrap_or(r.seq + 1);
+        if last_seq + 1 < oldest {
+            return Err("Missed events are no longer buffered");
+        }
+
+        let mut replayed = 0;
+        for (seq, msg) in r.buffer.iter().filter(|(s, _)| *s > last_seq) {
+            if socket_tx.try_send((Some(*seq), msg.clone())).is_err() {
+                return Err("Too many missed events to replay");
+            }
+            replayed += 1;
+        }
+
+        r.generation += 1;
+        r.socket_tx = Some(socket_tx);
+        r.detached_at = None;
+        Ok((replayed, r.generation))
+    }
+
+    fn expired(&self) -> bool {
+        let r = self.replay.lock().unwrap();
+        r.detached_at.is_some_and(|t| t.elapsed() > RESUME_WINDOW)
+    }
+
+    fn abort(&self) {
+        for task in self.tasks.lock().unwrap().drain(..) {
+            task.abort();
+        }
+    }
+}
+
+/// All live and recently dropped WebSocket sessions
+#[derive(Default)]
+pub struct SessionStore {
+    sessions: DashMap<String, Arc<WsSession>>,
+}
+
+impl SessionStore {
+    pub fn create(&self, user_id: &str) -> Arc<WsSession> {
+        let session = WsSession::new(user_id);
+        self.sessions.insert(session.id.clone(), session.clone());
+        session
+    }
+
+    /// Drop a session and stop its tasks
+    pub fn remove(&self, session_id: &str) {
+        if let Some((_, session)) = self.sessions.remove(session_id) {
+            session.abort();
+        }
+    }
+
+    /// Try to resume `session_id` for `user_id`, replaying everything after `last_seq`
+    /// into `socket_tx`
+    pub fn resume(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        last_seq: u64,
+        socket_tx: mpsc::Sender<Frame>,
+    ) -> ResumeResult {
+        let Some(session) = self.sessions.get(session_id).map(|s| s.clone()) else {
+            return ResumeResult::Invalid("Unknown session");
+        };
+        if session.user_id != user_id {
+            return ResumeResult::Invalid("Unknown session");
+        }
+        if session.expired() {
+            self.remove(session_id);
+            return ResumeResult::Invalid("Session expired");
+        }
+
+        match session.replay_into(last_seq, socket_tx) {
+            Ok((replayed, generation)) => ResumeResult::Resumed { session, replayed, generation },
+            Err(reason) => ResumeResult::Invalid(reason),
+        }
+    }
+
+    /// Remove sessions whose resume window has passed (call from a background task)
+    pub fn cleanup_expired(&self) {
+        self.sessions.retain(|_, session| {
+            let keep = !session.expired();
+            if !keep {
+                session.abort();
+            }
+            keep
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(n: u64) -> WsServerMessage {
+        WsServerMessage::Error { message: n.to_string() }
+    }
+
+    /// Feed `count` events through the pump and wait until they're numbered
+    async fn push(session: &WsSession, count: u64) {
+        let target = session.replay.lock().unwrap().seq + count;
+        for n in 0..count {
+            session.events_tx.send(event(n)).await.unwrap();
+        }
+        while session.replay.lock().unwrap().seq < target {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn resume_replays_only_events_after_last_seq() {
+        let store = SessionStore::default();
+        let session = store.create("alice");
+        push(&session, 3).await;
+
+        let (tx, mut rx) = mpsc::channel(SOCKET_QUEUE_SIZE);
+        match store.resume(&session.id, "alice", 1, tx) {
+            ResumeResult::Resumed { replayed, .. } => assert_eq!(replayed, 2),
+            ResumeResult::Invalid(reason) => panic!("resume failed: {reason}"),
+        }
+        assert_eq!(rx.try_recv().unwrap().0, Some(2));
+        assert_eq!(rx.try_recv().unwrap().0, Some(3));
+        assert!(rx.try_recv().is_err());
+
+        // Someone else can't take the session over
+        let (tx, _rx) = mpsc::channel(SOCKET_QUEUE_SIZE);
+        assert!(matches!(store.resume(&session.id, "mallory", 3, tx), ResumeResult::Invalid(_)));
+    }
+
+    #[tokio::test]
+    async fn overflowed_buffer_forces_a_fresh_session() {
+        let store = SessionStore::default();
+        let session = store.create("alice");
+        push(&session, REPLAY_BUFFER_SIZE as u64 + 2).await;
+
+        let (tx, _rx) = mpsc::channel(SOCKET_QUEUE_SIZE);
+        assert!(matches!(
+            store.resume(&session.id, "alice", 0, tx),
+            ResumeResult::Invalid("Missed events are no longer buffered")
+        ));
+        // The oldest event still buffered can be resumed from
+        let (tx, _rx) = mpsc::channel(SOCKET_QUEUE_SIZE);
+        assert!(matches!(store.resume(&session.id, "alice", 2, tx), ResumeResult::Resumed { .. }));
+    }
+
+    #[tokio::test]
+    async fn stale_detach_keeps_the_newer_connection() {
+        let store = SessionStore::default();
+        let session = store.create("alice");
+        let (old_tx, _old_rx) = mpsc::channel(SOCKET_QUEUE_SIZE);
+        let old = session.attach(old_tx);
+        let (new_tx, mut new_rx) = mpsc::channel(SOCKET_QUEUE_SIZE);
+        let new = session.attach(new_tx);
+
+        // The old socket closing late must not cut off its successor
+        session.detach(old);
+        push(&session, 1).await;
+        assert_eq!(new_rx.recv().await.unwrap().0, Some(1));
+        assert!(!session.expired());
+
+        session.detach(new);
+        assert!(session.replay.lock().unwrap().socket_tx.is_none());
+    }
+}
@@ -0,0 +1,174 @@
+/// Types the server can recognize from file contents; per-server allow-lists pick from these
+pub const SUPPORTED_TYPES: &[&str] = &[
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "application/pdf",
+    "text/plain",
+    "audio/mpeg",
+    "audio/ogg",
+    "audio/wav",
+    "video/mp4",
+    "video/webm",
+    "application/zip",
+    "application/gzip",
+];
+
+/// Media browsers can display inline without running anything
+const INLINE_SAFE_TYPES: &[&str] = &[
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "audio/mpeg",
+    "audio/ogg",
+    "audio/wav",
+    "video/mp4",
+    "video/webm",
+];
+
+/// Map the aliases browsers and OSes send to the type `sniff` reports
+pub fn canonical(mime: &str) -> &str {
+    match mime {
+        "image/jpg" | "image/pjpeg" => "image/jpeg",
+        "audio/mp3" | "audio/mpeg3" => "audio/mpeg",
+        "audio/x-wav" | "audio/wave" | "audio/vnd.wave" => "audio/wav",
+        "application/ogg" | "video/ogg" => "audio/ogg",
+        "audio/webm" => "video/webm",
+        "application/x-zip-compressed" | "application/x-zip" => "application/zip",
+        "application/x-gzip" => "application/gzip",
+        other => other,
+    }
+}
+
+/// Identify a supported type from the leading bytes
+pub fn sniff(data: &[u8]) -> Option<&'static str> {
+    let starts = |magic: &[u8]| data.starts_with(magic);
+    let at = |offset: usize, magic: &[u8]| data.get(offset..offset + magic.len()) == Some(magic);
+
+    if starts(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if starts(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if starts(b"GIF87a") || starts(b"GIF89a") {
+        Some("image/gif")
+    } else if starts(b"RIFF") && at(8, b"WEBP") {
+        Some("image/webp")
+    } else if starts(b"RIFF") && at(8, b"WAVE") {
+        Some("audio/wav")
+    } else if starts(b"%PDF-") {
+        Some("application/pdf")
+    } else if starts(b"OggS") {
+        Some("audio/ogg")
+    } else if starts(b"ID3") || (data.len() > 1 && data[0] == 0xFF && data[1] & 0xE0 == 0xE0) {
+        Some("audio/mpeg")
+    } else if at(4, b"ftyp") {
+        Some("video/mp4")
+    } else if starts(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        Some("video/webm")
+    } else if starts(b"PK\x03\x04") || starts(b"PK\x05\x06") {
+        Some("application/zip")
+    } else if starts(&[0x1F, 0x8B]) {
+        Some("application/gzip")
+    } else if looks_like_text(data) {
+        Some("text/plain")
+    } else {
+        None
+    }
+}
+
+/// UTF-8 without control bytes (other than whitespace) in the first 8KB
+fn looks_like_text(data: &[u8]) -> bool {
+    let head = &data[..data.len().min(8192)];
+    let valid = match std::str::from_utf8(head) {
+        Ok(_) => true,
+        // A multi-byte character cut off at the 8KB boundary is fine
+        Err(e) => e.error_len().is_none() && data.len() > head.len(),
+    };
+    valid && !head.iter().any(|&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0C))
+}
+
+/// Check that `data` really is `declared` and that the type is allowed; returns the canonical type
+pub fn verify<S: AsRef<str>>(declared: &str, data: &[u8], allowed: &[S]) -> Result<&'static str, String> {
+    let declared = canonical(declared);
+    let actual = sniff(data).ok_or_else(|| "Unrecognized file contents".to_string())?;
+    if actual != declared {
+        return Err(format!("File contents are {actual}, not {declared}"));
+    }
+    if !allowed.iter().any(|t| t.as_ref() == actual) {
+        return Err(format!("File type {actual} not allowed"));
+    }
+    Ok(actual)
+}
+
+/// Whether a stored file of `mime` whose contents are `data` may be shown inline
+pub fn is_inline_safe(mime: &str, data: &[u8]) -> bool {
+    INLINE_SAFE_TYPES.contains(&mime) && sniff(data) == Some(mime)
+}
+
+/// Content type to store `data` under where a storage backend serves it directly:
+/// media that is safe inline, anything else as an opaque download
+pub fn stored_content_type(data: &[u8]) -> &'static str {
+    match sniff(data) {
+        Some(mime) if INLINE_SAFE_TYPES.contains(&mime) => mime,
+        _ => "application/octet-stream",
+    }
+}
+
+/// The default per-server allow-list
+pub fn default_allowed() -> Vec<String> {
+    SUPPORTED_TYPES.iter().map(|t| t.to_string()).collect()
+}
+
+/// Parse a server's `allowed_upload_types` column (NULL = every supported type)
+pub fn parse_allowed(column: Option<&str>) -> Vec<String> {
+    match column {
+        Some(list) => list.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect(),
+        None => default_allowed(),
+    }
+}
+
+/// `Content-Disposition: attachment` with an ASCII fallback and the UTF-8 name per RFC 5987
+pub fn attachment_disposition(filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | ' ') { c } else { '_' })
+        .collect();
+    let encoded: String = filename
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || matches!(b, b'.' | b'-' | b'_') {
+                (b as char).to_string()
+            } else {
+                format!("%{b:02X}")
+            }
+        })
+        .collect();
+    format!("attachment; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mismatched_content_is_rejected() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        let allowed = default_allowed();
+        assert_eq!(verify("image/png", png, &allowed), Ok("image/png"));
+        assert!(verify("image/jpeg", png, &allowed).is_err());
+        // HTML renamed to .txt is still text, but a PNG declared as text is not
+        assert_eq!(verify("text/plain", b"<script>alert(1)</script>", &allowed), Ok("text/plain"));
+        assert!(verify("text/plain", png, &allowed).is_err());
+        assert!(verify("image/png", png, &["image/gif"]).is_err());
+        assert!(!is_inline_safe("text/plain", b"hello"));
+    }
+
+    #[test]
+    fn only_inline_media_is_stored_under_its_type() {
+        assert_eq!(stored_content_type(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), "image/png");
+        assert_eq!(stored_content_type(b"<svg onload=alert(1)>"), "application/octet-stream");
+        assert_eq!(stored_content_type(b"%PDF-1.7"), "application/octet-stream");
+    }
+}
@@ -8,7 +8,11 @@ use sea_orm::DatabaseConnection;
 use tokio::sync::broadcast;
 use tokio::sync::Mutex;
 
-use crate::models::{VoicePeer, WsServerMessage};
+use crate::models::{ServerEvent, VoicePeer, WsServerMessage};
+use crate::presence::{Presence, PresenceTracker, REMOTE_TTL};
+use crate::pubsub::{BusEvent, PubSub};
+use crate::session::SessionStore;
+use crate::storage::{Storage, UploadConfig};
 
 /// Simple per-IP rate limiter
 pub struct RateLimiter {
@@ -62,12 +66,14 @@ pub struct AppState {
     pub channels: Arc<DashMap<String, broadcast::Sender<WsServerMessage>>>,
     /// Number of connected WebSocket clients
     pub online: Arc<AtomicUsize>,
-    /// Set of currently online user IDs (for member list presence)
-    pub online_users: Arc<Mutex<HashSet<String>>>,
+    /// Per-user presence aggregated over all of their connections
+    pub presence: Arc<PresenceTracker>,
     /// Voice channel members: channel_id -> Vec<VoicePeer>
     pub voice_members: Arc<DashMap<String, Vec<VoicePeer>>>,
-    /// Server-wide broadcast channel (for global presence)
-    pub global_tx: broadcast::Sender<WsServerMessage>,
+    /// Voice members another instance reported: (channel_id, user_id) -> (instance, last seen)
+    pub remote_voice: Arc<DashMap<(String, String), (String, Instant)>>,
+    /// Server-wide broadcast channel (for global presence), tagged by guild for routing
+    pub global_tx: broadcast::Sender<ServerEvent>,
     /// JWT signing secret
     pub jwt_secret: String,
     pub external_host: String,
@@ -79,17 +85,33 @@ pub struct AppState {
     /// last typing event: (channel_id, user_id) -> Instant
     pub typing_limits: Arc<DashMap<(String, String), Instant>>,
     pub is_user_timed_out: Arc<Mutex<HashSet<String>>>, // Set of user IDs currently timed out
+    /// WebSocket sessions with replay buffers, kept briefly after disconnect for resume
+    pub sessions: Arc<SessionStore>,
+    /// Fan-out to other server instances (no-op when running a single instance)
+    pub pubsub: Arc<dyn PubSub>,
+    /// Backend holding upload bytes (local directory or S3-compatible bucket)
+    pub storage: Arc<dyn Storage>,
+    pub uploads: Arc<UploadConfig>,
 }
 
 impl AppState {
-    pub fn new(db: DatabaseConnection, jwt_secret: String, external_host: String, external_port: u16) -> Self {
+    pub fn new(
+        db: DatabaseConnection,
+        jwt_secret: String,
+        external_host: String,
+        external_port: u16,
+        pubsub: Arc<dyn PubSub>,
+        storage: Arc<dyn Storage>,
+        uploads: UploadConfig,
+    ) -> Self {
         let (global_tx, _) = broadcast::channel(1024);
         Self {
             db,
             channels: Arc::new(DashMap::new()),
             online: Arc::new(AtomicUsize::new(0)),
-            online_users: Arc::new(Mutex::new(HashSet::new())),
+            presence: Arc::new(PresenceTracker::default()),
             voice_members: Arc::new(DashMap::new()),
+            remote_voice: Arc::new(DashMap::new()),
             global_tx,
             jwt_secret,
             external_host,
@@ -98,9 +120,35 @@ impl AppState {
             auth_rate_limiter: Arc::new(RateLimiter::new(10, 60)), // 10 req/min per IP
             typing_limits: Arc::new(DashMap::new()),
             is_user_timed_out: Arc::new(Mutex::new(HashSet::new())),
+            sessions: Arc::new(SessionStore::default()),
+            pubsub,
+            storage,
+            uploads: Arc::new(uploads),
         }
     }
 
+    /// A state over a freshly migrated SQLite file in the temp dir
+    #[cfg(test)]
+    pub async fn for_tests() -> Self {
+        let root = std::env::temp_dir().join(format!("sivyspeak-test-{}", uuid::Uuid::new_v4()));
+        let db = crate::db::init_db(&format!("sqlite:{}/test.db?mode=rwc", root.display())).await;
+        Self::new(
+            db,
+            "test-secret".into(),
+            "localhost".into(),
+            0,
+            Arc::new(crate::pubsub::InProcessPubSub),
+            Arc::new(crate::storage::LocalStorage::new(root.join("uploads"))),
+            UploadConfig {
+                max_size: 25 * 1024 * 1024,
+                partial_dir: root.join("partial"),
+                user_quota: None,
+                server_quota: None,
+                orphan_grace: chrono::Duration::hours(1),
+            },
+        )
+    }
+
     /// Get or create a broadcast channel for the given channel ID
     pub fn get_channel_tx(&self, channel_id: &str) -> broadcast::Sender<WsServerMessage> {
         self.channels
@@ -109,6 +157,71 @@ impl AppState {
             .clone()
     }
 
+    /// Send to subscribers of a channel on every instance
+    pub fn publish_channel(&self, channel_id: &str, message: WsServerMessage) {
+        let _ = self.get_channel_tx(channel_id).send(message.clone());
+        self.pubsub.publish(&BusEvent::Channel {
+            channel_id: channel_id.to_string(),
+            message,
+        });
+    }
+
+    /// Broadcast to every connection scoped to `server_id` (None = all connections), on every instance
+    pub fn broadcast_global(&self, server_id: Option<&str>, message: WsServerMessage) {
+        let server_ids = server_id.map(|id| vec![id.to_string()]);
+        self.pubsub.publish(&BusEvent::Global {
+            server_ids: server_ids.clone(),
+            user_ids: None,
+            message: message.clone(),
+        });
+        let _ = self.global_tx.send(ServerEvent { server_ids, user_ids: None, message });
+    }
+
+    /// Broadcast to every connection scoped to any of `server_ids`, on every instance
+    pub fn broadcast_to_servers(&self, server_ids: Vec<String>, message: WsServerMessage) {
+        if server_ids.is_empty() {
+            return;
+        }
+        let server_ids = Some(server_ids);
+        self.pubsub.publish(&BusEvent::Global {
+            server_ids: server_ids.clone(),
+            user_ids: None,
+            message: message.clone(),
+        });
+        let _ = self.global_tx.send(ServerEvent { server_ids, user_ids: None, message });
+    }
+
+    /// Tell a user's connections, on every instance, that they joined or left a server
+    pub fn membership_changed(&self, user_id: &str, server_id: &str, joined: bool) {
+        self.send_to_users(
+            vec![user_id.to_string()],
+            WsServerMessage::MembershipUpdate { server_id: server_id.to_string(), joined },
+        );
+    }
+
+    /// Send to every connection of `user_ids`, on every instance
+    pub fn send_to_users(&self, user_ids: Vec<String>, message: WsServerMessage) {
+        if user_ids.is_empty() {
+            return;
+        }
+        let user_ids = Some(user_ids);
+        self.pubsub.publish(&BusEvent::Global {
+            server_ids: None,
+            user_ids: user_ids.clone(),
+            message: message.clone(),
+        });
+        let _ = self.global_tx.send(ServerEvent { server_ids: None, user_ids, message });
+    }
+
+    /// Broadcast once to this instance's connections scoped to any of `server_ids`
+    pub fn broadcast_to_servers_local(&self, server_ids: Vec<String>, message: WsServerMessage) {
+        let _ = self.global_tx.send(ServerEvent {
+            server_ids: Some(server_ids),
+            user_ids: None,
+            message,
+        });
+    }
+
     pub fn online_count(&self) -> usize {
         self.online.load(Ordering::Relaxed)
     }
@@ -121,19 +234,9 @@ impl AppState {
         self.online.fetch_sub(1, Ordering::Relaxed);
     }
 
-    /// Mark a user as online
-    pub async fn user_online(&self, user_id: &str) {
-        self.online_users.lock().await.insert(user_id.to_string());
-    }
-
-    /// Mark a user as offline
-    pub async fn user_offline(&self, user_id: &str) {
-        self.online_users.lock().await.remove(user_id);
-    }
-
-    /// Check if a user is online
-    pub async fn is_user_online(&self, user_id: &str) -> bool {
-        self.online_users.lock().await.contains(user_id)
+    /// Current presence of a user as other users see it
+    pub fn user_presence(&self, user_id: &str) -> Presence {
+        self.presence.get(user_id)
     }
 
     /// Check if a user is timed out
@@ -154,9 +257,9 @@ impl AppState {
         });
     }
 
-    /// Get the set of all online user IDs
-    pub async fn get_online_user_ids(&self) -> HashSet<String> {
-        self.online_users.lock().await.clone()
+    /// Presence of every user that appears online (invisible users are left out)
+    pub fn get_online_presences(&self) -> std::collections::HashMap<String, Presence> {
+        self.presence.visible_online()
     }
 
     // ─── Voice member tracking ───
@@ -169,23 +272,123 @@ impl AppState {
         is_muted: bool,
         is_deafened: bool,
     ) -> Vec<VoicePeer> {
+        let peer = VoicePeer {
+            user_id: user_id.to_string(),
+            user_name: user_name.to_string(),
+            channel_id: channel_id.to_string(),
+            is_muted,
+            is_deafened,
+        };
+        self.pubsub.publish(&BusEvent::VoiceJoin { peer: peer.clone() });
+        self.remote_voice.remove(&(peer.channel_id.clone(), peer.user_id.clone()));
+        self.join_voice_local(peer)
+    }
+
+    /// Record a voice join without telling other instances
+    pub fn join_voice_local(&self, peer: VoicePeer) -> Vec<VoicePeer> {
         let mut members = self
             .voice_members
-            .entry(channel_id.to_string())
+            .entry(peer.channel_id.clone())
             .or_default();
         // Remove if already present (re-join)
-        members.retain(|p| p.user_id != user_id);
-        members.push(VoicePeer {
-            user_id: user_id.to_string(),
-            user_name: user_name.to_string(),
+        members.retain(|p| p.user_id != peer.user_id);
+        members.push(peer);
+        members.clone()
+    }
+
+    /// Record a voice join another instance reported
+    pub fn join_voice_remote(&self, origin: &str, peer: VoicePeer) {
+        self.remote_voice.insert(
+            (peer.channel_id.clone(), peer.user_id.clone()),
+            (origin.to_string(), Instant::now()),
+        );
+        self.join_voice_local(peer);
+    }
+
+    /// Record a voice leave another instance reported
+    pub fn leave_voice_remote(&self, channel_id: &str, user_id: &str) {
+        self.remote_voice.remove(&(channel_id.to_string(), user_id.to_string()));
+        self.leave_voice_local(channel_id, user_id);
+    }
+
+    /// Voice members connected to this instance, as republished to the others
+    pub fn local_voice_snapshot(&self) -> Vec<VoicePeer> {
+        self.get_all_voice_members()
+            .into_iter()
+            .filter(|p| !self.remote_voice.contains_key(&(p.channel_id.clone(), p.user_id.clone())))
+            .collect()
+    }
+
+    /// Replace what `origin` reported before with its current voice members.
+    /// Returns the members that were added and the (channel_id, user_id) pairs that left.
+    pub fn sync_voice_remote(&self, origin: &str, peers: Vec<VoicePeer>) -> (Vec<VoicePeer>, Vec<(String, String)>) {
+        let current: HashSet<(String, String)> =
+            peers.iter().map(|p| (p.channel_id.clone(), p.user_id.clone())).collect();
+        let mut left = vec![];
+        self.remote_voice.retain(|key, (instance, _)| {
+            if instance == origin && !current.contains(key) {
+                left.push(key.clone());
+                return false;
+            }
+            true
+        });
+        for (channel_id, user_id) in &left {
+            self.leave_voice_local(channel_id, user_id);
+        }
+
+        let mut joined = vec![];
+        for peer in peers {
+            let known = self
+                .voice_members
+                .get(&peer.channel_id)
+                .is_some_and(|m| m.iter().any(|p| p.user_id == peer.user_id));
+            if known {
+                self.update_voice_status_local(&peer.channel_id, &peer.user_id, peer.is_muted, peer.is_deafened);
+                self.remote_voice.insert(
+                    (peer.channel_id.clone(), peer.user_id.clone()),
+                    (origin.to_string(), Instant::now()),
+                );
+            } else {
+                joined.push(peer.clone());
+                self.join_voice_remote(origin, peer);
+            }
+        }
+        (joined, left)
+    }
+
+    /// Forget voice members of instances that stopped reporting; returns the (channel_id, user_id) pairs dropped
+    pub fn expire_remote_voice(&self) -> Vec<(String, String)> {
+        let mut expired = vec![];
+        self.remote_voice.retain(|key, (_, seen)| {
+            if seen.elapsed() < REMOTE_TTL {
+                return true;
+            }
+            expired.push(key.clone());
+            false
+        });
+        for (channel_id, user_id) in &expired {
+            self.leave_voice_local(channel_id, user_id);
+        }
+        expired
+    }
+
+    pub fn update_voice_status(
+        &self,
+        channel_id: &str,
+        user_id: &str,
+        is_muted: bool,
+        is_deafened: bool,
+    ) {
+        self.pubsub.publish(&BusEvent::VoiceStatus {
             channel_id: channel_id.to_string(),
+            user_id: user_id.to_string(),
             is_muted,
             is_deafened,
         });
-        members.clone()
+        self.update_voice_status_local(channel_id, user_id, is_muted, is_deafened);
     }
 
-    pub fn update_voice_status(
+    pub fn update_voice_status_local(
         &self,
         channel_id: &str,
         user_id: &str,
@@ -201,6 +404,14 @@ impl AppState {
     }
 
     pub fn leave_voice(&self, channel_id: &str, user_id: &str) {
+        self.pubsub.publish(&BusEvent::VoiceLeave {
+            channel_id: channel_id.to_string(),
+            user_id: user_id.to_string(),
+        });
+        self.leave_voice_local(channel_id, user_id);
+    }
+
+    pub fn leave_voice_local(&self, channel_id: &str, user_id: &str) {
         if let Some(mut members) = self.voice_members.get_mut(channel_id) {
             members.retain(|p| p.user_id != user_id);
         }
@@ -216,6 +427,12 @@ impl AppState {
                 left_channels.push((entry.key().clone(), user_id.to_string()));
             }
         }
+        for (channel_id, user_id) in &left_channels {
+            self.pubsub.publish(&BusEvent::VoiceLeave {
+                channel_id: channel_id.clone(),
+                user_id: user_id.clone(),
+            });
+        }
         left_channels
     }
 
@@ -0,0 +1,286 @@
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use axum::body::Bytes;
+use axum::http::Method;
+use futures::future::BoxFuture;
+use futures::stream::{BoxStream, StreamExt, TryStreamExt};
+use object_store::aws::{AmazonS3, AmazonS3Builder};
+use object_store::path::Path as ObjectPath;
+use object_store::signer::Signer;
+use object_store::{
+    Attribute, Attributes, GetOptions, GetRange, ObjectStore, PutMultipartOptions, PutOptions, WriteMultipart,
+};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+/// Bytes of a stored object, read as they are sent
+pub type ByteStream = BoxStream<'static, io::Result<Bytes>>;
+
+/// Where upload bytes live. Keys are relative paths such as `blobs/ab/abcd…`.
+pub trait Storage: Send + Sync {
+    fn put<'a>(&'a self, key: &'a str, data: Bytes) -> BoxFuture<'a, io::Result<()>>;
+    /// Store the contents of a local file without reading it all into memory
+    fn put_file<'a>(&'a self, key: &'a str, path: &'a Path) -> BoxFuture<'a, io::Result<()>>;
+    /// Stream `range` of an object (all of it when `None`) without buffering it.
+    /// Fails with `ErrorKind::NotFound` for missing keys
+    fn get_stream<'a>(&'a self, key: &'a str, range: Option<Range<u64>>) -> BoxFuture<'a, io::Result<ByteStream>>;
+    /// Size in bytes; fails with `ErrorKind::NotFound` for missing keys
+    fn size<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<u64>>;
+    fn exists<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<bool>>;
+    /// Deleting a missing key is not an error
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<()>>;
+    /// Direct download URL clients can be redirected to, if the backend supports it
+    fn presigned_url<'a>(&'a self, key: &'a str, expires_in: Duration) -> BoxFuture<'a, Option<String>>;
+}
+
+/// Upload limits and scratch space shared by the upload routes
+pub struct UploadConfig {
+    /// Instance-wide size limit in bytes, unless a server or role sets its own
+    pub max_size: u64,
+    /// Local directory holding resumable uploads until they are finalized
+    pub partial_dir: PathBuf,
+    /// Instance-wide storage quota per user in bytes (None = unlimited), unless a role sets one
+    pub user_quota: Option<u64>,
+    /// Instance-wide storage quota per server in bytes (None = unlimited), unless the server sets one
+    pub server_quota: Option<u64>,
+    /// Unreferenced uploads younger than this are left alone by the orphan GC
+    pub orphan_grace: chrono::Duration,
+}
+
+/// Files under a local directory (the default: `./uploads`)
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+}
+
+impl Storage for LocalStorage {
+    fn put<'a>(&'a self, key: &'a str, data: Bytes) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            let path = self.path(key);
+            if let Some(dir) = path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            // Write then rename so readers never see a half-written file;
+            // concurrent writers of the same key each use their own temp file
+            let tmp = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+            tokio::fs::write(&tmp, &data).await?;
+            tokio::fs::rename(&tmp, &path).await
+        })
+    }
+
+    fn put_file<'a>(&'a self, key: &'a str, source: &'a Path) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            let path = self.path(key);
+            if let Some(dir) = path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            let tmp = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+            tokio::fs::copy(source, &tmp).await?;
+            tokio::fs::rename(&tmp, &path).await
+        })
+    }
+
+    fn get_stream<'a>(&'a self, key: &'a str, range: Option<Range<u64>>) -> BoxFuture<'a, io::Result<ByteStream>> {
+        Box::pin(async move {
+            let mut file = tokio::fs::File::open(self.path(key)).await?;
+            let len = match range {
+                Some(range) => {
+                    file.seek(io::SeekFrom::Start(range.start)).await?;
+                    range.end - range.start
+                }
+                None => u64::MAX,
+            };
+            Ok(ReaderStream::new(file.take(len)).boxed())
+        })
+    }
+
+    fn size<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<u64>> {
+        Box::pin(async move { tokio::fs::metadata(self.path(key)).await.map(|m| m.len()) })
+    }
+
+    fn exists<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<bool>> {
+        Box::pin(async move { tokio::fs::try_exists(self.path(key)).await })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            match tokio::fs::remove_file(self.path(key)).await {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            }
+        })
+    }
+
+    fn presigned_url<'a>(&'a self, _key: &'a str, _expires_in: Duration) -> BoxFuture<'a, Option<String>> {
+        Box::pin(async { None })
+    }
+}
+
+/// S3 settings from the command line / environment
+pub struct S3Config {
+    pub bucket: String,
+    pub region: String,
+    /// Custom endpoint for S3-compatible services (e.g. MinIO at http://localhost:9000)
+    pub endpoint: Option<String>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    /// Redirect downloads to presigned URLs instead of proxying the bytes
+    pub presign: bool,
+}
+
+/// Read size when streaming files to S3 (parts themselves are 5MB+)
+const MULTIPART_CHUNK: usize = 1024 * 1024;
+
+/// Objects are stored with a content type sniffed from their bytes, so presigned
+/// downloads never serve uploaded markup as a page
+fn content_type(data: &[u8]) -> Attributes {
+    Attributes::from_iter([(Attribute::ContentType, crate::sniff::stored_content_type(data))])
+}
+
+/// Any S3-compatible object store
+pub struct S3Storage {
+    store: AmazonS3,
+    presign: bool,
+}
+
+impl S3Storage {
+    pub fn new(config: S3Config) -> object_store::Result<Self> {
+        let mut builder = AmazonS3Builder::from_env()
+            .with_bucket_name(config.bucket)
+            .with_region(config.region);
+        if let Some(endpoint) = config.endpoint {
+            // MinIO and friends: path-style URLs, usually plain HTTP locally
+            builder = builder
+                .with_allow_http(endpoint.starts_with("http://"))
+                .with_virtual_hosted_style_request(false)
+                .with_endpoint(endpoint);
+        }
+        if let Some(key) = config.access_key_id {
+            builder = builder.with_access_key_id(key);
+        }
+        if let Some(secret) = config.secret_access_key {
+            builder = builder.with_secret_access_key(secret);
+        }
+        Ok(Self {
+            store: builder.build()?,
+            presign: config.presign,
+        })
+    }
+}
+
+fn to_io(e: object_store::Error) -> io::Error {
+    match e {
+        object_store::Error::NotFound { .. } => io::Error::new(io::ErrorKind::NotFound, e),
+        other => io::Error::other(other),
+    }
+}
+
+impl Storage for S3Storage {
+    fn put<'a>(&'a self, key: &'a str, data: Bytes) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            let options = PutOptions {
+                attributes: content_type(&data),
+                ..Default::default()
+            };
+            self.store
+                .put_opts(&ObjectPath::from(key), data.into(), options)
+                .await
+                .map(|_| ())
+                .map_err(to_io)
+        })
+    }
+
+    fn put_file<'a>(&'a self, key: &'a str, source: &'a Path) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            let mut file = tokio::fs::File::open(source).await?;
+            let mut buf = vec![0u8; MULTIPART_CHUNK];
+            // The first chunk decides the content type
+            let mut n = file.read(&mut buf).await?;
+            let options = PutMultipartOptions {
+                attributes: content_type(&buf[..n]),
+                ..Default::default()
+            };
+            let upload = self
+                .store
+                .put_multipart_opts(&ObjectPath::from(key), options)
+                .await
+                .map_err(to_io)?;
+            let mut writer = WriteMultipart::new(upload);
+            while n > 0 {
+                // Keep a bounded number of parts in flight
+                writer.wait_for_capacity(4).await.map_err(to_io)?;
+                writer.write(&buf[..n]);
+                n = file.read(&mut buf).await?;
+            }
+            writer.finish().await.map(|_| ()).map_err(to_io)
+        })
+    }
+
+    fn get_stream<'a>(&'a self, key: &'a str, range: Option<Range<u64>>) -> BoxFuture<'a, io::Result<ByteStream>> {
+        Box::pin(async move {
+            let options = GetOptions {
+                range: range.map(GetRange::Bounded),
+                ..Default::default()
+            };
+            let result = self
+                .store
+                .get_opts(&ObjectPath::from(key), options)
+                .await
+                .map_err(to_io)?;
+            Ok(result.into_stream().map_err(to_io).boxed())
+        })
+    }
+
+    fn size<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<u64>> {
+        Box::pin(async move {
+            let meta = self.store.head(&ObjectPath::from(key)).await.map_err(to_io)?;
+            Ok(meta.size)
+        })
+    }
+
+    fn exists<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<bool>> {
+        Box::pin(async move {
+            match self.store.head(&ObjectPath::from(key)).await {
+                Ok(_) => Ok(true),
+                Err(object_store::Error::NotFound { .. }) => Ok(false),
+                Err(e) => Err(to_io(e)),
+            }
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            match self.store.delete(&ObjectPath::from(key)).await {
+                Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+                Err(e) => Err(to_io(e)),
+            }
+        })
+    }
+
+    fn presigned_url<'a>(&'a self, key: &'a str, expires_in: Duration) -> BoxFuture<'a, Option<String>> {
+        Box::pin(async move {
+            if !self.presign {
+                return None;
+            }
+            match self.store.signed_url(Method::GET, &ObjectPath::from(key), expires_in).await {
+                Ok(url) => Some(url.to_string()),
+                Err(e) => {
+                    tracing::warn!("Failed to presign {key}: {e}");
+                    None
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,398 @@
+//! Server templates: a versioned JSON snapshot of a server's structure (settings,
+//! roles, categories, channels and their role overrides, but no messages or members)
+//! that new servers can be created from.
+
+use std::collections::{HashMap, HashSet};
+
+use sea_orm::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::entities::{category, channel, channel_override, role, server};
+
+const FORMAT: &str = "sivyspeak-server-template";
+const VERSION: u32 = 1;
+/// Key of the server's Admin role, which every new server gets anyway
+pub const ADMIN_ROLE: &str = "admin";
+const MAX_ROLES: usize = 250;
+const MAX_CATEGORIES: usize = 50;
+const MAX_CHANNELS: usize = 500;
+const MAX_NAME: usize = 100;
+const MAX_CHANNEL_NAME: usize = 64;
+const MAX_DESCRIPTION: usize = 500;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Template {
+    pub format: String,
+    pub version: u32,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub settings: TemplateSettings,
+    #[serde(default)]
+    pub roles: Vec<TemplateRole>,
+    #[serde(default)]
+    pub categories: Vec<TemplateCategory>,
+    #[serde(default)]
+    pub channels: Vec<TemplateChannel>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateSettings {
+    pub sound_chance: i64,
+    #[serde(default)]
+    pub allowed_upload_types: Option<String>,
+    #[serde(default)]
+    pub max_upload_size: Option<i64>,
+}
+
+impl Default for TemplateSettings {
+    fn default() -> Self {
+        Self { sound_chance: 100, allowed_upload_types: None, max_upload_size: None }
+    }
+}
+
+/// Roles, categories and overrides refer to each other by template-local keys
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateRole {
+    pub key: String,
+    pub name: String,
+    #[serde(default)]
+    pub color: Option<String>,
+    pub position: i64,
+    pub permissions: i64,
+    #[serde(default)]
+    pub max_upload_size: Option<i64>,
+    #[serde(default)]
+    pub storage_quota: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateCategory {
+    pub key: String,
+    pub name: String,
+    pub position: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateChannel {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub position: i64,
+    pub channel_type: String,
+    #[serde(default)]
+    pub encrypted: bool,
+    #[serde(default)]
+    pub plugin_url: Option<String>,
+    /// Key of the channel's category
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub overrides: Vec<TemplateOverride>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateOverride {
+    /// Key of the role, or `ADMIN_ROLE`
+    pub role: String,
+    pub allow: i64,
+    pub deny: i64,
+}
+
+fn is_admin_role(server_id: &str, role: &role::Model) -> bool {
+    role.id == format!("{server_id}-admin") || (server_id == "default" && role.id == "admin-role")
+}
+
+/// Capture `srv`'s current structure. Bot-managed roles and member overrides are
+/// left out, since the bots and members don't come along.
+pub async fn snapshot<C: ConnectionTrait>(db: &C, srv: &server::Model) -> Result<Template, DbErr> {
+    let roles = role::Entity::find()
+        .filter(role::Column::ServerId.eq(&srv.id))
+        .filter(role::Column::Managed.eq(false))
+        .order_by_asc(role::Column::Position)
+        .all(db)
+        .await?;
+    let categories = category::Entity::find()
+        .filter(category::Column::ServerId.eq(&srv.id))
+        .order_by_asc(category::Column::Position)
+        .all(db)
+        .await?;
+    let channels = channel::Entity::find()
+        .filter(channel::Column::ServerId.eq(&srv.id))
+        .order_by_asc(channel::Column::Position)
+        .all(db)
+        .await?;
+    let overrides = channel_override::Entity::find()
+        .filter(channel_override::Column::ChannelId.is_in(channels.iter().map(|c| c.id.as_str())))
+        .filter(channel_override::Column::TargetType.eq("role"))
+        .all(db)
+        .await?;
+
+    let mut role_keys: HashMap<&str, String> = HashMap::new();
+    let mut template_roles = Vec::new();
+    for r in &roles {
+        if is_admin_role(&srv.id, r) {
+            role_keys.insert(&r.id, ADMIN_ROLE.to_string());
+            continue;
+        }
+        let key = format!("role-{}", template_roles.len());
+        role_keys.insert(&r.id, key.clone());
+        template_roles.push(TemplateRole {
+            key,
+            name: r.name.clone(),
+            color: r.color.clone(),
+            position: r.position,
+            permissions: r.permissions,
+            max_upload_size: r.max_upload_size,
+            storage_quota: r.storage_quota,
+        });
+    }
+
+    let category_keys: HashMap<&str, String> = categories
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (c.id.as_str(), format!("category-{i}")))
+        .collect();
+
+    let mut channel_overrides: HashMap<&str, Vec<TemplateOverride>> = HashMap::new();
+    for o in &overrides {
+        if let Some(key) = role_keys.get(o.target_id.as_str()) {
+            channel_overrides.entry(&o.channel_id).or_default().push(TemplateOverride {
+                role: key.clone(),
+                allow: o.allow,
+                deny: o.deny,
+            });
+        }
+    }
+
+    Ok(Template {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        name: srv.name.clone(),
+        description: srv.description.clone(),
+        settings: TemplateSettings {
+            sound_chance: srv.sound_chance,
+            allowed_upload_types: srv.allowed_upload_types.clone(),
+            max_upload_size: srv.max_upload_size,
+        },
+        roles: template_roles,
+        categories: categories
+            .iter()
+            .map(|c| TemplateCategory {
+                key: category_keys[c.id.as_str()].clone(),
+                name: c.name.clone(),
+                position: c.position,
+            })
+            .collect(),
+        channels: channels
+            .iter()
+            .map(|c| TemplateChannel {
+                name: c.name.clone(),
+                description: c.description.clone(),
+                position: c.position,
+                channel_type: c.channel_type.clone(),
+                encrypted: c.encrypted,
+                plugin_url: c.plugin_url.clone(),
+                category: c.category_id.as_deref().and_then(|id| category_keys.get(id)).cloned(),
+                overrides: channel_overrides.remove(c.id.as_str()).unwrap_or_default(),
+            })
+            .collect(),
+    })
+}
+
+/// Check a template (possibly uploaded by hand) before building a server from it
+pub fn validate(t: &Template) -> Result<(), String> {
+    if t.format != FORMAT {
+        return Err("Not a server template".into());
+    }
+    if t.version != VERSION {
+        return Err(format!("Unsupported template version {}", t.version));
+    }
+    if t.roles.len() > MAX_ROLES || t.categories.len() > MAX_CATEGORIES || t.channels.len() > MAX_CHANNELS {
+        return Err(format!(
+            "Templates are limited to {MAX_ROLES} roles, {MAX_CATEGORIES} categories and {MAX_CHANNELS} channels"
+        ));
+    }
+    if !(0..=100).contains(&t.settings.sound_chance) {
+        return Err("sound_chance must be 0-100".into());
+    }
+    if let Some(types) = &t.settings.allowed_upload_types {
+        if types.split(',').any(|t| !crate::sniff::SUPPORTED_TYPES.contains(&t)) {
+            return Err("Unsupported upload type".into());
+        }
+    }
+
+    let mut role_keys = HashSet::from([ADMIN_ROLE]);
+    for r in &t.roles {
+        if r.name.trim().is_empty() || r.name.len() > MAX_NAME {
+            return Err(format!("Invalid role name \"{}\"", r.name));
+        }
+        if !role_keys.insert(&r.key) {
+            return Err(format!("Duplicate role key \"{}\"", r.key));
+        }
+    }
+    let mut category_keys = HashSet::new();
+    for c in &t.categories {
+        if c.name.trim().is_empty() || c.name.len() > MAX_NAME {
+            return Err(format!("Invalid category name \"{}\"", c.name));
+        }
+        if !category_keys.insert(&c.key) {
+            return Err(format!("Duplicate category key \"{}\"", c.key));
+        }
+    }
+    for c in &t.channels {
+        if c.name.is_empty()
+            || c.name.len() > MAX_CHANNEL_NAME
+            || !c.name.chars().all(|ch| ch.is_alphanumeric() || ch == '-' || ch == '_' || ch == ' ')
+        {
+            return Err(format!("Invalid channel name \"{}\"", c.name));
+        }
+        if !["text", "voice", "plugin", "forum", "announcement"].contains(&c.channel_type.as_str()) {
+            return Err(format!("Invalid channel type \"{}\"", c.channel_type));
+        }
+        if c.description.len() > MAX_DESCRIPTION {
+            return Err(format!("Description of channel \"{}\" is too long", c.name));
+        }
+        if c.category.as_ref().is_some_and(|k| !category_keys.contains(k)) {
+            return Err(format!("Channel \"{}\" is in an unknown category", c.name));
+        }
+        if c.overrides.iter().any(|o| !role_keys.contains(o.role.as_str())) {
+            return Err(format!("Channel \"{}\" overrides an unknown role", c.name));
+        }
+    }
+    Ok(())
+}
+
+/// Create the template's roles, categories, channels and overrides in `server_id`.
+/// `admin_role_id` is the server's own Admin role. The template must be validated.
+pub async fn apply<C: ConnectionTrait>(
+    db: &C,
+    server_id: &str,
+    admin_role_id: &str,
+    t: &Template,
+    now: &str,
+) -> Result<(), DbErr> {
+    let mut role_ids: HashMap<&str, String> = HashMap::from([(ADMIN_ROLE, admin_role_id.to_string())]);
+    for r in &t.roles {
+        let id = Uuid::new_v4().to_string();
+        role::Entity::insert(role::ActiveModel {
+            id: Set(id.clone()),
+            name: Set(r.name.trim().to_string()),
+            color: Set(r.color.clone()),
+            position: Set(r.position),
+            permissions: Set(r.permissions),
+            created_at: Set(now.to_string()),
+            server_id: Set(server_id.to_string()),
+            managed: Set(false),
+            max_upload_size: Set(r.max_upload_size),
+            storage_quota: Set(r.storage_quota),
+        })
+        .exec(db)
+        .await?;
+        role_ids.insert(&r.key, id);
+    }
+
+    let mut category_ids: HashMap<&str, String> = HashMap::new();
+    for c in &t.categories {
+        let id = Uuid::new_v4().to_string();
+        category::Entity::insert(category::ActiveModel {
+            id: Set(id.clone()),
+            name: Set(c.name.trim().to_string()),
+            position: Set(c.position),
+            server_id: Set(server_id.to_string()),
+        })
+        .exec(db)
+        .await?;
+        category_ids.insert(&c.key, id);
+    }
+
+    for c in &t.channels {
+        let id = Uuid::new_v4().to_string();
+        channel::Entity::insert(channel::ActiveModel {
+            id: Set(id.clone()),
+            name: Set(c.name.clone()),
+            description: Set(c.description.clone()),
+            position: Set(c.position),
+            created_at: Set(now.to_string()),
+            channel_type: Set(c.channel_type.clone()),
+            encrypted: Set(c.encrypted),
+            server_id: Set(server_id.to_string()),
+            category_id: Set(c.category.as_deref().and_then(|k| category_ids.get(k)).cloned()),
+            plugin_url: Set(c.plugin_url.clone()),
+            remote_follows: Set(false),
+        })
+        .exec(db)
+        .await?;
+
+        for o in &c.overrides {
+            channel_override::Entity::insert(channel_override::ActiveModel {
+                channel_id: Set(id.clone()),
+                target_id: Set(role_ids[o.role.as_str()].clone()),
+                target_type: Set("role".to_string()),
+                allow: Set(o.allow),
+                deny: Set(o.deny),
+                ..Default::default()
+            })
+            .exec(db)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template() -> Template {
+        Template {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            name: "Team".to_string(),
+            description: String::new(),
+            settings: TemplateSettings::default(),
+            roles: vec![TemplateRole {
+                key: "role-0".to_string(),
+                name: "Member".to_string(),
+                color: None,
+                position: 1,
+                permissions: 0,
+                max_upload_size: None,
+                storage_quota: None,
+            }],
+            categories: vec![TemplateCategory { key: "category-0".to_string(), name: "Text".to_string(), position: 0 }],
+            channels: vec![TemplateChannel {
+                name: "general".to_string(),
+                description: String::new(),
+                position: 0,
+                channel_type: "text".to_string(),
+                encrypted: false,
+                plugin_url: None,
+                category: Some("category-0".to_string()),
+                overrides: vec![TemplateOverride { role: "role-0".to_string(), allow: 0, deny: 1 }],
+            }],
+        }
+    }
+
+    #[test]
+    fn templates_must_reference_known_keys() {
+        let mut t = template();
+        assert_eq!(validate(&t), Ok(()));
+
+        t.channels[0].overrides[0].role = ADMIN_ROLE.to_string();
+        assert_eq!(validate(&t), Ok(()));
+
+        t.channels[0].overrides[0].role = "role-9".to_string();
+        assert!(validate(&t).is_err());
+
+        let mut t = template();
+        t.channels[0].category = Some("category-9".to_string());
+        assert!(validate(&t).is_err());
+
+        let mut t = template();
+        t.version = VERSION + 1;
+        assert!(validate(&t).is_err());
+    }
+}
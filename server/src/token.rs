@@ -3,6 +3,15 @@ use rand::Rng;
 
 use crate::models::ConnectionToken;
 
+/// Whether `code` can be used as a server's vanity invite code
+/// (3-32 lowercase letters, digits and dashes)
+pub fn is_valid_vanity_code(code: &str) -> bool {
+    (3..=32).contains(&code.len())
+        && code.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !code.starts_with('-')
+        && !code.ends_with('-')
+}
+
 /// Generate a random invite code (8 alphanumeric chars)
 pub fn generate_invite_code() -> String {
     let mut rng = rand::thread_rng();
@@ -51,6 +60,15 @@ mod tests {
         assert_eq!(decoded.invite_code, "abc12345");
     }
 
+    #[test]
+    fn test_vanity_codes() {
+        assert!(is_valid_vanity_code("rust-pl"));
+        assert!(is_valid_vanity_code("team42"));
+        assert!(!is_valid_vanity_code("ab"));
+        assert!(!is_valid_vanity_code("Team"));
+        assert!(!is_valid_vanity_code("-team"));
+    }
+
     #[test]
     fn test_invite_code_length() {
         let code = generate_invite_code();
@@ -0,0 +1,127 @@
+use std::io::Write;
+
+use axum::extract::ws::Message;
+use serde::Serialize;
+
+use crate::models::{WsClientMessage, WsServerMessage};
+
+/// zstd level for gateway traffic: cheap enough to run per frame
+const ZSTD_LEVEL: i32 = 3;
+
+/// How frames are serialized on a connection (`encoding=` on /ws)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Json,
+    MsgPack,
+}
+
+/// Wire format negotiated at connect time
+#[derive(Debug, Clone, Copy)]
+pub struct WireFormat {
+    pub encoding: Encoding,
+    /// Server frames go through one zstd stream for the whole connection (`compress=zstd`)
+    pub compress: bool,
+}
+
+impl WireFormat {
+    pub fn negotiate(encoding: Option<&str>, compress: Option<&str>) -> Result<Self, String> {
+        let encoding = match encoding.unwrap_or("json") {
+            "json" => Encoding::Json,
+            "msgpack" => Encoding::MsgPack,
+            other => return Err(format!("Unsupported encoding '{other}' (expected json or msgpack)")),
+        };
+        let compress = match compress {
+            None => false,
+            Some("zstd") => true,
+            Some(other) => return Err(format!("Unsupported compression '{other}' (expected zstd)")),
+        };
+        Ok(Self { encoding, compress })
+    }
+}
+
+/// An outgoing frame: the message plus its sequence number when it is a session event
+#[derive(Serialize)]
+struct OutFrame<'a> {
+    #[serde(flatten)]
+    msg: &'a WsServerMessage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seq: Option<u64>,
+}
+
+/// Per-connection encoder; owns the zstd stream so its window carries across frames
+pub struct FrameEncoder {
+    encoding: Encoding,
+    zstd: Option<zstd::stream::write::Encoder<'static, Vec<u8>>>,
+}
+
+impl FrameEncoder {
+    pub fn new(format: WireFormat) -> std::io::Result<Self> {
+        let zstd = if format.compress {
+            Some(zstd::stream::write::Encoder::new(Vec::new(), ZSTD_LEVEL)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            encoding: format.encoding,
+            zstd,
+        })
+    }
+
+    /// Serialize a frame. JSON goes out as text unless compressed; everything else is binary.
+    pub fn encode(&mut self, seq: Option<u64>, msg: &WsServerMessage) -> Result<Message, String> {
+        let frame = OutFrame { msg, seq };
+        let bytes = match self.encoding {
+            Encoding::Json => serde_json::to_vec(&frame).map_err(|e| e.to_string())?,
+            Encoding::MsgPack => rmp_serde::to_vec_named(&frame).map_err(|e| e.to_string())?,
+        };
+
+        match &mut self.zstd {
+            Some(stream) => {
+                // Flushing ends the block, so the client can decode this frame
+                // without waiting for the next one
+                stream.write_all(&bytes).map_err(|e| e.to_string())?;
+                stream.flush().map_err(|e| e.to_string())?;
+                Ok(Message::Binary(std::mem::take(stream.get_mut()).into()))
+            }
+            None if self.encoding == Encoding::Json => {
+                // serde_json only produces valid UTF-8
+                Ok(Message::Text(String::from_utf8(bytes).map_err(|e| e.to_string())?.into()))
+            }
+            None => Ok(Message::Binary(bytes.into())),
+        }
+    }
+}
+
+/// Parse a client frame: text is always JSON, binary is MessagePack on msgpack connections
+pub fn decode_client(format: WireFormat, msg: &Message) -> Option<Result<WsClientMessage, String>> {
+    match msg {
+        Message::Text(text) => Some(serde_json::from_str(text).map_err(|e| e.to_string())),
+        Message::Binary(bytes) if format.encoding == Encoding::MsgPack => {
+            Some(rmp_serde::from_slice(bytes).map_err(|e| e.to_string()))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn msgpack_zstd_frames_decode_in_sequence() {
+        let format = WireFormat::negotiate(Some("msgpack"), Some("zstd")).unwrap();
+        let mut encoder = FrameEncoder::new(format).unwrap();
+        let mut decoder = zstd::stream::raw::Decoder::new().unwrap();
+
+        for seq in [Some(1), None] {
+            let Message::Binary(compressed) = encoder.encode(seq, &WsServerMessage::Pong).unwrap() else {
+                panic!("compressed frames are binary");
+            };
+            let mut out = vec![0u8; 1024];
+            let status = zstd::stream::raw::Operation::run_on_buffers(&mut decoder, &compressed, &mut out).unwrap();
+            let value: serde_json::Value = rmp_serde::from_slice(&out[..status.bytes_written]).unwrap();
+            assert_eq!(value["type"], "pong");
+            assert_eq!(value.get("seq").and_then(|s| s.as_u64()), seq);
+        }
+    }
+}
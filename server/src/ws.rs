@@ -3,19 +3,27 @@ use axum::{
         ws::{Message, WebSocket},
         Query, State, WebSocketUpgrade,
     },
-    response::IntoResponse,
+    http::StatusCode,
+    response::{IntoResponse, Response},
 };
 use futures::{SinkExt, StreamExt};
 use sea_orm::*;
 use serde::Deserialize;
 use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use tokio::sync::mpsc;
 use uuid::Uuid;
 
-use crate::{entities::{bot, federated_channel, federation_peer, message, server, user}, permissions::check_channel_permission, routes::roles::user_has_permission};
-use crate::models::{Bot, RepliedMessage, WsClientMessage, WsServerMessage};
-use crate::routes::auth;
+use crate::{entities::{bot, channel, federated_channel, federation_peer, message, server, server_member, user}, permissions::check_channel_permission, routes::roles::user_has_permission};
+use crate::models::{Bot, Intents, RepliedMessage, WsClientMessage, WsServerMessage};
+use crate::presence::{broadcast_presence, PresenceSettings};
+use crate::routes::{announcements, auth, forums, members, stickers};
+use crate::session::{Frame, ResumeResult, SOCKET_QUEUE_SIZE};
 use crate::state::AppState;
 use crate::models::Permissions;
+use crate::wire::{self, FrameEncoder, WireFormat};
+use crate::attachments;
+use crate::blobs;
 
 const MAX_MESSAGE_LENGTH: usize = 2000;
 const MAX_FIELD_LENGTH: usize = 256;
@@ -25,7 +33,14 @@ const MAX_SUBSCRIPTIONS: usize = 50;
 #[derive(Debug, Deserialize)]
 pub struct WsQuery {
     pub token: Option<String>,
+    /// Comma-separated list of servers to receive events for (default: all memberships)
     pub server_id: Option<String>,
+    /// Gateway intents bitmask (default: all)
+    pub intents: Option<u32>,
+    /// Frame encoding: json (default) or msgpack
+    pub encoding: Option<String>,
+    /// Stream compression for server frames: zstd
+    pub compress: Option<String>,
 }
 
 /// Authenticated identity for a WebSocket connection
@@ -34,11 +49,94 @@ enum WsIdentity {
     Bot(Bot),
 }
 
+/// Which events a connection receives: declared intents and the servers it is scoped to
+#[derive(Clone)]
+struct ConnectionScope {
+    intents: Intents,
+    servers: Arc<RwLock<HashSet<String>>>,
+    /// Servers the connection asked for; empty = follow every membership
+    requested: Arc<HashSet<String>>,
+}
+
+impl ConnectionScope {
+    fn wants(&self, msg: &WsServerMessage) -> bool {
+        msg.intent().is_none_or(|i| self.intents.contains(i))
+    }
+
+    fn covers(&self, server_ids: Option<&[String]>) -> bool {
+        let servers = self.servers.read().unwrap();
+        server_ids.is_none_or(|ids| ids.iter().any(|id| servers.contains(id)))
+    }
+
+    fn server_ids(&self) -> Vec<String> {
+        self.servers.read().unwrap().iter().cloned().collect()
+    }
+
+    fn contains(&self, server_id: &str) -> bool {
+        self.servers.read().unwrap().contains(server_id)
+    }
+
+    /// Follow a membership change of the connection's user
+    fn apply(&self, msg: &WsServerMessage) {
+        if let WsServerMessage::MembershipUpdate { server_id, joined } = msg {
+            let mut servers = self.servers.write().unwrap();
+            if !joined {
+                servers.remove(server_id);
+            } else if self.requested.is_empty() || self.requested.contains(server_id) {
+                servers.insert(server_id.clone());
+            }
+        }
+    }
+}
+
+/// Resolve the servers a connection may receive events for: the requested ones the
+/// identity belongs to, or every membership when none are requested
+async fn resolve_servers(
+    state: &AppState,
+    identity: Option<&WsIdentity>,
+    requested: &HashSet<String>,
+) -> HashSet<String> {
+    let memberships: HashSet<String> = match identity {
+        Some(WsIdentity::User(claims)) => server_member::Entity::find()
+            .filter(server_member::Column::UserId.eq(&claims.sub))
+            .all(&state.db)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|m| m.server_id)
+            .collect(),
+        Some(WsIdentity::Bot(bot)) => HashSet::from([bot.server_id.clone()]),
+        // Guests belong nowhere; they only ever see the instance's default server
+        None => HashSet::from(["default".to_string()]),
+    };
+
+    if requested.is_empty() {
+        memberships
+    } else {
+        requested.intersection(&memberships).cloned().collect()
+    }
+}
+
+/// Look up which server a channel belongs to
+async fn channel_server_id(state: &AppState, channel_id: &str) -> Option<String> {
+    channel::Entity::find_by_id(channel_id)
+        .one(&state.db)
+        .await
+        .ok()
+        .flatten()
+        .map(|c| c.server_id)
+}
+
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
     Query(query): Query<WsQuery>,
-) -> impl IntoResponse {
+) -> Response {
+    let format = match WireFormat::negotiate(query.encoding.as_deref(), query.compress.as_deref()) {
+        Ok(format) => format,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
     // Try to authenticate: first as bot token, then as JWT
     let identity = if let Some(ref t) = query.token {
         if t.starts_with("bot.") {
@@ -60,16 +158,31 @@ pub async fn ws_handler(
         None
     };
 
-    let server_id = query.server_id.unwrap_or_else(|| "default".to_string());
+    let requested: HashSet<String> = query
+        .server_id
+        .map(|ids| {
+            ids.split(',')
+                .map(|id| id.trim().to_string())
+                .filter(|id| !id.is_empty() && id.len() <= MAX_FIELD_LENGTH)
+                .take(MAX_SUBSCRIPTIONS)
+                .collect()
+        })
+        .unwrap_or_default();
+    let scope = ConnectionScope {
+        intents: query.intents.map(Intents::from_bits_truncate).unwrap_or(Intents::all()),
+        servers: Arc::new(RwLock::new(resolve_servers(&state, identity.as_ref(), &requested).await)),
+        requested: Arc::new(requested),
+    };
 
-    ws.on_upgrade(move |socket| handle_socket(socket, state, identity, server_id))
+    ws.on_upgrade(move |socket| handle_socket(socket, state, identity, scope, format))
 }
 
 async fn handle_socket(
     socket: WebSocket,
     state: AppState,
     identity: Option<WsIdentity>,
-    _server_id: String,
+    scope: ConnectionScope,
+    format: WireFormat,
 ) {
     state.inc_online();
 
@@ -80,38 +193,89 @@ async fn handle_socket(
     };
     let is_authenticated = identity.is_some();
 
-    // Track online presence
-    state.user_online(&user_id).await;
+    // Track presence per connection; guests have none
+    let conn_id = Uuid::new_v4().to_string();
+    if is_authenticated {
+        let settings = match &identity {
+            Some(WsIdentity::User(claims)) => user::Entity::find_by_id(&claims.sub)
+                .one(&state.db)
+                .await
+                .ok()
+                .flatten()
+                .map(|u| PresenceSettings::from_user(&u))
+                .unwrap_or_default(),
+            _ => PresenceSettings::default(),
+        };
+        if let Some(presence) = state.presence.connect(&user_id, &conn_id, settings) {
+            broadcast_presence(&state, &user_id, presence).await;
+        }
+    }
 
     let (mut sender, mut receiver) = socket.split();
-    let mut subscribed_channels: HashSet<String> = HashSet::new();
     let mut voice_user_id: Option<String> = None;
 
-    let (client_tx, mut client_rx) = tokio::sync::mpsc::channel::<WsServerMessage>(256);
+    // Events go through the session (sequenced + buffered for resume);
+    // direct replies go straight to this socket
+    let (reply_tx, mut out_rx) = mpsc::channel::<Frame>(SOCKET_QUEUE_SIZE);
+    let mut session = state.sessions.create(&user_id);
+    let mut generation = session.attach(reply_tx.clone());
 
     // Send identity to client
-    let _ = client_tx.send(WsServerMessage::Identity { user_id: user_id.clone() }).await;
-
-    // Send initial voice state sync
-    let voice_states = state.get_all_voice_members();
-    let _ = client_tx.send(WsServerMessage::VoiceStateSync { voice_states }).await;
+    let _ = session.events_tx.send(WsServerMessage::Identity {
+        user_id: user_id.clone(),
+        session_id: session.id.clone(),
+    }).await;
+
+    // Send initial voice state sync, limited to voice channels in scoped servers
+    if scope.intents.contains(Intents::VOICE) {
+        let scoped_channels: HashSet<String> = channel::Entity::find()
+            .filter(channel::Column::ServerId.is_in(scope.server_ids()))
+            .all(&state.db)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|c| c.id)
+            .collect();
+        let voice_states = state
+            .get_all_voice_members()
+            .into_iter()
+            .filter(|p| scoped_channels.contains(&p.channel_id))
+            .collect();
+        let _ = session.events_tx.send(WsServerMessage::VoiceStateSync { voice_states }).await;
+    }
 
-    // Subscribe to global broadcasts (voice presence, etc.)
+    // Subscribe to global broadcasts (voice presence, etc.) for scoped servers
     let mut global_rx = state.global_tx.subscribe();
-    let global_client_tx = client_tx.clone();
-    tokio::spawn(async move {
-        while let Ok(msg) = global_rx.recv().await {
-            if global_client_tx.send(msg).await.is_err() {
+    let global_events_tx = session.events_tx.clone();
+    let global_scope = scope.clone();
+    let global_user_id = user_id.clone();
+    session.track(tokio::spawn(async move {
+        while let Ok(event) = global_rx.recv().await {
+            if !global_scope.covers(event.server_ids.as_deref()) || !global_scope.wants(&event.message) {
+                continue;
+            }
+            if event.user_ids.as_ref().is_some_and(|ids| !ids.contains(&global_user_id)) {
+                continue;
+            }
+            global_scope.apply(&event.message);
+            if global_events_tx.send(event.message).await.is_err() {
                 break;
             }
         }
-    });
+    }));
 
     let send_task = tokio::spawn(async move {
-        while let Some(msg) = client_rx.recv().await {
-            match serde_json::to_string(&msg) {
-                Ok(json) => {
-                    if sender.send(Message::Text(json.into())).await.is_err() {
+        let mut encoder = match FrameEncoder::new(format) {
+            Ok(encoder) => encoder,
+            Err(e) => {
+                tracing::warn!("Failed to set up WS compression: {e}");
+                return;
+            }
+        };
+        while let Some((seq, msg)) = out_rx.recv().await {
+            match encoder.encode(seq, &msg) {
+                Ok(frame) => {
+                    if sender.send(frame).await.is_err() {
                         break;
                     }
                 }
@@ -124,34 +288,56 @@ async fn handle_socket(
 
     while let Some(Ok(msg)) = receiver.next().await {
         match msg {
-            Message::Text(text) => {
-                if text.len() > MAX_SDP_LENGTH + 1024 {
-                    let _ = client_tx
-                        .send(WsServerMessage::Error {
-                            message: "Message too large".to_string(),
-                        })
-                        .await;
+            Message::Text(_) | Message::Binary(_) => {
+                if msg.clone().into_data().len() > MAX_SDP_LENGTH + 1024 {
+                    reply(&reply_tx, WsServerMessage::Error {
+                        message: "Message too large".to_string(),
+                    }).await;
                     continue;
                 }
 
-                let parsed: Result<WsClientMessage, _> = serde_json::from_str(&text);
+                let Some(parsed) = wire::decode_client(format, &msg) else {
+                    continue;
+                };
+
+                // Anything but a heartbeat counts as activity for auto-idle
+                if !matches!(parsed, Ok(WsClientMessage::Ping)) {
+                    if let Some(presence) = state.presence.touch(&user_id, &conn_id) {
+                        broadcast_presence(&state, &user_id, presence).await;
+                    }
+                }
                 match parsed {
                     Ok(WsClientMessage::JoinChannel { channel_id }) => {
                         if channel_id.is_empty() || channel_id.len() > MAX_FIELD_LENGTH {
                             continue;
                         }
-                        if subscribed_channels.len() >= MAX_SUBSCRIPTIONS {
+                        let channel_server = channel_server_id(&state, &channel_id).await;
+                        if !channel_server.is_some_and(|id| scope.contains(&id)) {
+                            reply(&reply_tx, WsServerMessage::Error {
+                                message: "Channel is outside this connection's servers".to_string(),
+                            }).await;
                             continue;
                         }
-                        if subscribed_channels.insert(channel_id.clone()) {
+                        let newly_subscribed = {
+                            let mut subscribed = session.subscribed_channels.lock().unwrap();
+                            if subscribed.len() >= MAX_SUBSCRIPTIONS {
+                                continue;
+                            }
+                            subscribed.insert(channel_id.clone())
+                        };
+                        if newly_subscribed {
                             let tx = state.get_channel_tx(&channel_id);
                             let mut rx = tx.subscribe();
-                            let client_tx = client_tx.clone();
+                            let events_tx = session.events_tx.clone();
+                            let channel_scope = scope.clone();
                             let cid = channel_id.clone();
                             let uid = user_id.clone();
 
-                            tokio::spawn(async move {
+                            session.track(tokio::spawn(async move {
                                 while let Ok(msg) = rx.recv().await {
+                                    if !channel_scope.wants(&msg) {
+                                        continue;
+                                    }
                                     let should_send = match &msg {
                                         WsServerMessage::NewMessage { channel_id, .. } => channel_id == &cid,
                                         WsServerMessage::UserJoined { channel_id, .. } => channel_id == &cid,
@@ -168,33 +354,45 @@ async fn handle_socket(
                                         _ => true,
                                     };
                                     if should_send {
-                                        if client_tx.send(msg).await.is_err() {
+                                        if events_tx.send(msg).await.is_err() {
                                             break;
                                         }
                                     }
                                 }
-                            });
+                            }));
                         }
                     }
                     Ok(WsClientMessage::LeaveChannel { channel_id }) => {
-                        subscribed_channels.remove(&channel_id);
+                        session.subscribed_channels.lock().unwrap().remove(&channel_id);
                     }
                     Ok(WsClientMessage::SendMessage {
                         channel_id,
                         content,
                         reply_to,
+                        attachments: attachment_ids,
+                        sticker_id,
+                        post_id,
                         ..
                     }) => {
                         // REQUIRE AUTH for sending messages
                         if !is_authenticated {
-                            let _ = client_tx.send(WsServerMessage::Error {
+                            reply(&reply_tx, WsServerMessage::Error {
                                 message: "Authentication required to send messages".to_string(),
                             }).await;
                             continue;
                         }
 
                         let content = content.trim().to_string();
-                        if content.is_empty() || content.len() > MAX_MESSAGE_LENGTH {
+                        if (content.is_empty() && attachment_ids.is_empty() && sticker_id.is_none())
+                            || content.len() > MAX_MESSAGE_LENGTH
+                        {
+                            continue;
+                        }
+                        // A sticker is the whole message
+                        if sticker_id.is_some() && (!content.is_empty() || !attachment_ids.is_empty()) {
+                            reply(&reply_tx, WsServerMessage::Error {
+                                message: "Stickers cannot be sent with text or attachments".to_string(),
+                            }).await;
                             continue;
                         }
                         if channel_id.is_empty() {
@@ -203,20 +401,78 @@ async fn handle_socket(
                         
                         // Check channel permissions for SEND_MESSAGES
                         if !check_channel_permission(&state, &user_id, &channel_id, Permissions::SEND_MESSAGES).await.unwrap_or(false) {
-                            let _ = client_tx.send(WsServerMessage::Error {
+                            reply(&reply_tx, WsServerMessage::Error {
                                 message: "You do not have permission to send messages in this channel".to_string(),
                             }).await;
                             continue;
                         }
 
+                        if let Err(message) = announcements::check_can_post(&state, &user_id, &channel_id).await {
+                            reply(&reply_tx, WsServerMessage::Error { message }).await;
+                            continue;
+                        }
+
+                        // Forum channels only take replies to their posts
+                        if let Err(message) = forums::check_reply(&state, &user_id, &channel_id, post_id.as_deref()).await {
+                            reply(&reply_tx, WsServerMessage::Error { message }).await;
+                            continue;
+                        }
+
                         //check if timeout has expired for user and remove from set if so
                         if state.is_user_timed_out(&user_id).await {
-                            let _ = client_tx.send(WsServerMessage::Error {
+                            reply(&reply_tx, WsServerMessage::Error {
                                 message: "You are currently timed out and cannot send messages".to_string(),
                             }).await;
                             continue;
                         }
 
+                        let attached = if attachment_ids.is_empty() {
+                            Vec::new()
+                        } else {
+                            if !check_channel_permission(&state, &user_id, &channel_id, Permissions::SEND_FILES).await.unwrap_or(false) {
+                                reply(&reply_tx, WsServerMessage::Error {
+                                    message: "You do not have permission to send files in this channel".to_string(),
+                                }).await;
+                                continue;
+                            }
+                            match attachments::validate(&state, &user_id, &channel_id, &attachment_ids).await {
+                                Ok(uploads) => uploads,
+                                Err(message) => {
+                                    reply(&reply_tx, WsServerMessage::Error { message }).await;
+                                    continue;
+                                }
+                            }
+                        };
+
+                        let sticker = match &sticker_id {
+                            Some(id) => match stickers::find_sticker(&state, id).await {
+                                Ok(Some(sticker)) => Some(sticker),
+                                Ok(None) => {
+                                    reply(&reply_tx, WsServerMessage::Error {
+                                        message: "Sticker not found".to_string(),
+                                    }).await;
+                                    continue;
+                                }
+                                Err(e) => {
+                                    tracing::error!("Failed to load sticker: {e}");
+                                    continue;
+                                }
+                            },
+                            None => None,
+                        };
+
+                        // Authors go by their nickname in the channel's server, if they have one
+                        let author_name = if is_bot_connection {
+                            user_name.clone()
+                        } else {
+                            match channel_server_id(&state, &channel_id).await {
+                                Some(server_id) => members::server_nickname(&state, &server_id, &user_id)
+                                    .await
+                                    .unwrap_or_else(|| user_name.clone()),
+                                None => user_name.clone(),
+                            }
+                        };
+
                         let msg_id = Uuid::new_v4().to_string();
                         let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
 
@@ -224,20 +480,35 @@ async fn handle_socket(
                             id: Set(msg_id.clone()),
                             channel_id: Set(channel_id.clone()),
                             user_id: Set(user_id.clone()),
-                            user_name: Set(user_name.clone()),
+                            user_name: Set(author_name.clone()),
                             content: Set(content.clone()),
                             created_at: Set(now.clone()),
                             reply_to: Set(reply_to.clone()),
+                            sticker_id: Set(sticker_id),
+                            post_id: Set(post_id.clone()),
                             ..Default::default()
                         };
 
-                        if let Err(e) = message::Entity::insert(new_msg)
-                            .exec(&state.db)
-                            .await {
+                        // The message and its attachments are saved together
+                        let saved = async {
+                            let txn = state.db.begin().await?;
+                            message::Entity::insert(new_msg).exec(&txn).await?;
+                            attachments::link(&txn, &msg_id, &channel_id, &attached).await?;
+                            txn.commit().await
+                        }
+                        .await;
+                        if let Err(e) = saved {
                             tracing::error!("Failed to save message: {e}");
+                            reply(&reply_tx, WsServerMessage::Error {
+                                message: "Failed to send message".to_string(),
+                            }).await;
                             continue;
                         }
 
+                        if let Some(post_id) = &post_id {
+                            forums::touch_post(&state, post_id, &now).await;
+                        }
+
                         let avatar_url: Option<String> = if is_bot_connection {
                             // For bots, get avatar from bots table
                             bot::Entity::find_by_id(&user_id)
@@ -283,27 +554,33 @@ async fn handle_socket(
                             id: msg_id,
                             channel_id: channel_id.clone(),
                             user_id: user_id.clone(),
-                            user_name: user_name.clone(),
+                            user_name: author_name.clone(),
                             avatar_url,
                             content: content.clone(),
                             created_at: now,
                             is_bot: is_bot_connection,
                             reply_to,
                             replied_message,
+                            attachments: attached
+                                .into_iter()
+                                .map(|row| attachments::to_attachment(&state, row))
+                                .collect(),
+                            sticker: sticker.map(Box::new),
+                            post_id,
                         };
 
-                        let tx = state.get_channel_tx(&channel_id);
-                        let _ = tx.send(broadcast_msg);
+                        state.publish_channel(&channel_id, broadcast_msg);
 
                         // Federation: forward message to linked remote channels
                         let fed_state = state.clone();
                         let fed_channel_id = channel_id.clone();
-                        let fed_user_name = user_name.clone();
+                        let fed_user_name = author_name;
                         let fed_content = content;
                         tokio::spawn(async move {
-                            // Find federated channel links with active peers
+                            // Find federated channel links with active peers (follows only receive)
                             let links = federated_channel::Entity::find()
                                 .filter(federated_channel::Column::LocalChannelId.eq(&fed_channel_id))
+                                .filter(federated_channel::Column::Mode.eq("link"))
                                 .find_also_related(federation_peer::Entity)
                                 .all(&fed_state.db)
                                 .await
@@ -342,7 +619,7 @@ async fn handle_socket(
                     }
                     Ok(WsClientMessage::TimeoutUser { user_id, duration_seconds, reason }) => {
                         if !is_authenticated {
-                            let _ = client_tx.send(WsServerMessage::Error {
+                            reply(&reply_tx, WsServerMessage::Error {
                                 message: "Authentication required to timeout users".to_string(),
                             }).await;
                             continue;
@@ -353,7 +630,7 @@ async fn handle_socket(
 
                         // Check permission
                         if !user_has_permission(&state, &user_id, Permissions::MODERATE_MEMBERS).await.unwrap_or(false) {
-                            let _ = client_tx.send(WsServerMessage::Error {
+                            reply(&reply_tx, WsServerMessage::Error {
                                 message: "Insufficient permissions to timeout users".to_string(),
                             }).await;
                             continue;
@@ -362,8 +639,16 @@ async fn handle_socket(
                         // Apply timeout
                         state.timeout_user(&user_id, duration_seconds).await;
 
-                        // Optionally, broadcast timeout event to channels the user is in
-                        let _ = state.global_tx.send(WsServerMessage::UserTimedOut {
+                        // Timeouts apply everywhere, so tell every server the user is in
+                        let target_servers: Vec<String> = server_member::Entity::find()
+                            .filter(server_member::Column::UserId.eq(&user_id))
+                            .all(&state.db)
+                            .await
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|m| m.server_id)
+                            .collect();
+                        state.broadcast_to_servers(target_servers, WsServerMessage::UserTimedOut {
                             user_id: user_id.clone(),
                             duration_seconds,
                             reason,
@@ -372,7 +657,7 @@ async fn handle_socket(
                     }
                     Ok(WsClientMessage::DeleteMessage { message_id, channel_id }) => {
                         if !is_authenticated {
-                            let _ = client_tx.send(WsServerMessage::Error {
+                            reply(&reply_tx, WsServerMessage::Error {
                                 message: "Authentication required to delete messages".to_string(),
                             }).await;
                             continue;
@@ -394,13 +679,18 @@ async fn handle_socket(
 
                         let mut active_msg: message::ActiveModel = msg.into();
                         active_msg.deleted_at = Set(Some(chrono::Utc::now()));
-                        if let Err(e) = active_msg.update(&state.db).await {
-                            tracing::error!("Failed to delete message: {e}");
-                            continue;
+                        let deleted = match active_msg.update(&state.db).await {
+                            Ok(deleted) => deleted,
+                            Err(e) => {
+                                tracing::error!("Failed to delete message: {e}");
+                                continue;
+                            }
+                        };
+                        if let Err(e) = blobs::release_message_uploads(&state, &deleted).await {
+                            tracing::warn!("Failed to release uploads of message {}: {e}", deleted.id);
                         }
 
-                        let tx = state.get_channel_tx(&msg_channel_id);
-                        let _ = tx.send(WsServerMessage::MessageDeleted {
+                        state.publish_channel(&msg_channel_id, WsServerMessage::MessageDeleted {
                             id: message_id.clone(),
                             channel_id: channel_id.clone(),
                         });
@@ -408,7 +698,7 @@ async fn handle_socket(
 
                     Ok(WsClientMessage::EditMessage { message_id, content }) => {
                         if !is_authenticated {
-                            let _ = client_tx.send(WsServerMessage::Error {
+                            reply(&reply_tx, WsServerMessage::Error {
                                 message: "Authentication required to edit messages".to_string(),
                             }).await;
                             continue;
@@ -441,8 +731,7 @@ async fn handle_socket(
                             continue;
                         }
 
-                        let tx = state.get_channel_tx(&msg_channel_id);
-                        let _ = tx.send(WsServerMessage::MessageEdited {
+                        state.publish_channel(&msg_channel_id, WsServerMessage::MessageEdited {
                             id: message_id.clone(),
                             content: content.clone(),
                             edited_at: chrono::Utc::now(),
@@ -455,9 +744,8 @@ async fn handle_socket(
                         }
 
                         if state.check_typing_limit(&channel_id, &user_id) {
-                            let tx = state.get_channel_tx(&channel_id);
-                            let _ = tx.send(WsServerMessage::TypingStart {
-                                channel_id,
+                            state.publish_channel(&channel_id, WsServerMessage::TypingStart {
+                                channel_id: channel_id.clone(),
                                 user_id: user_id.clone(),
                                 user_name: user_name.clone(),
                             });
@@ -467,7 +755,7 @@ async fn handle_socket(
                     // ─── Voice signaling ───
                     Ok(WsClientMessage::JoinVoice { channel_id, .. }) => {
                         if !is_authenticated {
-                            let _ = client_tx.send(WsServerMessage::Error {
+                            reply(&reply_tx, WsServerMessage::Error {
                                 message: "Authentication required for voice".to_string(),
                             }).await;
                             continue;
@@ -475,18 +763,33 @@ async fn handle_socket(
                         if channel_id.is_empty() {
                             continue;
                         }
+                        let voice_server_id = channel_server_id(&state, &channel_id).await;
+                        if !voice_server_id.as_ref().is_some_and(|id| scope.contains(id)) {
+                            reply(&reply_tx, WsServerMessage::Error {
+                                message: "Channel is outside this connection's servers".to_string(),
+                            }).await;
+                            continue;
+                        }
 
                         voice_user_id = Some(user_id.clone());
 
-                        if subscribed_channels.len() < MAX_SUBSCRIPTIONS && subscribed_channels.insert(channel_id.clone()) {
+                        let newly_subscribed = {
+                            let mut subscribed = session.subscribed_channels.lock().unwrap();
+                            subscribed.len() < MAX_SUBSCRIPTIONS && subscribed.insert(channel_id.clone())
+                        };
+                        if newly_subscribed {
                             let tx = state.get_channel_tx(&channel_id);
                             let mut rx = tx.subscribe();
-                            let client_tx = client_tx.clone();
+                            let events_tx = session.events_tx.clone();
+                            let channel_scope = scope.clone();
                             let cid = channel_id.clone();
                             let uid = user_id.clone();
 
-                            tokio::spawn(async move {
+                            session.track(tokio::spawn(async move {
                                 while let Ok(msg) = rx.recv().await {
+                                    if !channel_scope.wants(&msg) {
+                                        continue;
+                                    }
                                     let should_send = match &msg {
                                         WsServerMessage::VoicePeerJoined { user_id, channel_id, .. } => {
                                             channel_id == &cid && user_id != &uid
@@ -501,30 +804,29 @@ async fn handle_socket(
                                         _ => true,
                                     };
                                     if should_send {
-                                        if client_tx.send(msg).await.is_err() {
+                                        if events_tx.send(msg).await.is_err() {
                                             break;
                                         }
                                     }
                                 }
-                            });
+                            }));
                         }
 
                         let members = state.join_voice(&channel_id, &user_id, &user_name, false, false);
 
-                        let _ = client_tx.send(WsServerMessage::VoiceMembers {
+                        let _ = session.events_tx.send(WsServerMessage::VoiceMembers {
                             channel_id: channel_id.clone(),
                             members,
                         }).await;
 
-                        let tx = state.get_channel_tx(&channel_id);
-                        let _ = tx.send(WsServerMessage::VoicePeerJoined {
+                        state.publish_channel(&channel_id, WsServerMessage::VoicePeerJoined {
                             channel_id: channel_id.clone(),
                             user_id: user_id.clone(),
                             user_name: user_name.clone(),
                         });
 
                         // Global broadcast
-                        let _ = state.global_tx.send(WsServerMessage::VoicePeerJoined {
+                        state.broadcast_global(voice_server_id.as_deref(), WsServerMessage::VoicePeerJoined {
                             channel_id,
                             user_id: user_id.clone(),
                             user_name: user_name.clone(),
@@ -532,23 +834,22 @@ async fn handle_socket(
                     }
                     Ok(WsClientMessage::LeaveVoice { channel_id, .. }) => {
                         state.leave_voice(&channel_id, &user_id);
-                        let tx = state.get_channel_tx(&channel_id);
-                        let _ = tx.send(WsServerMessage::VoicePeerLeft {
+                        state.publish_channel(&channel_id, WsServerMessage::VoicePeerLeft {
                             channel_id: channel_id.clone(),
                             user_id: user_id.clone(),
                         });
 
                         // Global broadcast
-                        let _ = state.global_tx.send(WsServerMessage::VoicePeerLeft {
+                        let voice_server_id = channel_server_id(&state, &channel_id).await;
+                        state.broadcast_global(voice_server_id.as_deref(), WsServerMessage::VoicePeerLeft {
                             channel_id,
                             user_id: user_id.clone(),
                         });
                     }
                     Ok(WsClientMessage::VoiceOffer { channel_id, target_user_id, from_user_id: _, sdp }) => {
                         if sdp.len() > MAX_SDP_LENGTH { continue; }
-                        let tx = state.get_channel_tx(&channel_id);
-                        let _ = tx.send(WsServerMessage::VoiceOffer {
-                            channel_id,
+                        state.publish_channel(&channel_id, WsServerMessage::VoiceOffer {
+                            channel_id: channel_id.clone(),
                             target_user_id,
                             from_user_id: user_id.clone(),
                             sdp,
@@ -556,9 +857,8 @@ async fn handle_socket(
                     }
                     Ok(WsClientMessage::VoiceAnswer { channel_id, target_user_id, from_user_id: _, sdp }) => {
                         if sdp.len() > MAX_SDP_LENGTH { continue; }
-                        let tx = state.get_channel_tx(&channel_id);
-                        let _ = tx.send(WsServerMessage::VoiceAnswer {
-                            channel_id,
+                        state.publish_channel(&channel_id, WsServerMessage::VoiceAnswer {
+                            channel_id: channel_id.clone(),
                             target_user_id,
                             from_user_id: user_id.clone(),
                             sdp,
@@ -566,26 +866,23 @@ async fn handle_socket(
                     }
                     Ok(WsClientMessage::IceCandidate { channel_id, target_user_id, from_user_id: _, candidate }) => {
                         if candidate.len() > MAX_SDP_LENGTH { continue; }
-                        let tx = state.get_channel_tx(&channel_id);
-                        let _ = tx.send(WsServerMessage::IceCandidate {
-                            channel_id,
+                        state.publish_channel(&channel_id, WsServerMessage::IceCandidate {
+                            channel_id: channel_id.clone(),
                             target_user_id,
                             from_user_id: user_id.clone(),
                             candidate,
                         });
                     }
                     Ok(WsClientMessage::VoiceTalking { channel_id, user_id: _, talking }) => {
-                        let tx = state.get_channel_tx(&channel_id);
-                        let _ = tx.send(WsServerMessage::VoiceTalking {
-                            channel_id,
+                        state.publish_channel(&channel_id, WsServerMessage::VoiceTalking {
+                            channel_id: channel_id.clone(),
                             user_id: user_id.clone(),
                             talking,
                         });
                     }
                     Ok(WsClientMessage::VoiceStatusUpdate { channel_id, user_id: _, is_muted, is_deafened }) => {
                         state.update_voice_status(&channel_id, &user_id, is_muted, is_deafened);
-                        let tx = state.get_channel_tx(&channel_id);
-                        let _ = tx.send(WsServerMessage::VoiceStatusUpdate {
+                        state.publish_channel(&channel_id, WsServerMessage::VoiceStatusUpdate {
                             channel_id: channel_id.clone(),
                             user_id: user_id.clone(),
                             is_muted,
@@ -593,7 +890,8 @@ async fn handle_socket(
                         });
 
                         // Global broadcast
-                        let _ = state.global_tx.send(WsServerMessage::VoiceStatusUpdate {
+                        let voice_server_id = channel_server_id(&state, &channel_id).await;
+                        state.broadcast_global(voice_server_id.as_deref(), WsServerMessage::VoiceStatusUpdate {
                             channel_id,
                             user_id: user_id.clone(),
                             is_muted,
@@ -602,14 +900,41 @@ async fn handle_socket(
                     }
 
                     Ok(WsClientMessage::Ping) => {
-                        let _ = client_tx.send(WsServerMessage::Pong).await;
+                        reply(&reply_tx, WsServerMessage::Pong).await;
+                    }
+                    Ok(WsClientMessage::Resume { session_id, last_seq }) => {
+                        if !is_authenticated {
+                            reply(&reply_tx, WsServerMessage::InvalidSession {
+                                reason: "Authentication required to resume".to_string(),
+                            }).await;
+                            continue;
+                        }
+                        if session_id == session.id {
+                            continue;
+                        }
+
+                        match state.sessions.resume(&session_id, &user_id, last_seq, reply_tx.clone()) {
+                            ResumeResult::Resumed { session: resumed, replayed, generation: resumed_generation } => {
+                                // Drop the fresh session and continue on the old one
+                                state.sessions.remove(&session.id);
+                                session = resumed;
+                                generation = resumed_generation;
+                                reply(&reply_tx, WsServerMessage::Resumed {
+                                    session_id: session.id.clone(),
+                                    replayed,
+                                }).await;
+                            }
+                            ResumeResult::Invalid(reason) => {
+                                reply(&reply_tx, WsServerMessage::InvalidSession {
+                                    reason: reason.to_string(),
+                                }).await;
+                            }
+                        }
                     }
                     Err(_) => {
-                        let _ = client_tx
-                            .send(WsServerMessage::Error {
-                                message: "Invalid message format".to_string(),
-                            })
-                            .await;
+                        reply(&reply_tx, WsServerMessage::Error {
+                            message: "Invalid message format".to_string(),
+                        }).await;
                     }
                 }
             }
@@ -622,17 +947,38 @@ async fn handle_socket(
     if voice_user_id.is_some() {
         let left = state.leave_all_voice(&user_id);
         for (channel_id, uid) in left {
-            let tx = state.get_channel_tx(&channel_id);
-            let _ = tx.send(WsServerMessage::VoicePeerLeft { channel_id: channel_id.clone(), user_id: uid.clone() });
+            state.publish_channel(&channel_id, WsServerMessage::VoicePeerLeft { channel_id: channel_id.clone(), user_id: uid.clone() });
 
             // Global broadcast
-            let _ = state.global_tx.send(WsServerMessage::VoicePeerLeft { channel_id, user_id: uid });
+            let voice_server_id = channel_server_id(&state, &channel_id).await;
+            state.broadcast_global(voice_server_id.as_deref(), WsServerMessage::VoicePeerLeft { channel_id, user_id: uid });
         }
     }
 
-    // Mark user/bot as offline
-    state.user_offline(&user_id).await;
+    // Keep the session resumable for a while; guests can never resume
+    if is_authenticated {
+        session.detach(generation);
+    } else {
+        state.sessions.remove(&session.id);
+    }
+
+    // Drop this connection from presence; offline once the last one goes
+    if let Some(presence) = state.presence.disconnect(&user_id, &conn_id) {
+        broadcast_presence(&state, &user_id, presence).await;
+    }
+
+    // Members who joined through a temporary invite leave with their last connection
+    if is_authenticated && !is_bot_connection && !state.presence.is_connected(&user_id) {
+        if let Err(e) = crate::routes::invite::drop_temporary_memberships(&state, &user_id).await {
+            tracing::error!("Failed to drop temporary memberships: {}", e);
+        }
+    }
 
     state.dec_online();
     send_task.abort();
 }
+
+/// Send a direct, unsequenced reply to this socket
+async fn reply(reply_tx: &mpsc::Sender<Frame>, msg: WsServerMessage) {
+    let _ = reply_tx.send((None, msg)).await;
+}